@@ -0,0 +1,365 @@
+// This is free and unencumbered software released into the public domain.
+
+//! A local cache of fetched sources, for `asimov source fetch --cache`.
+//!
+//! The output of a fetcher is cached under the normalized URL, the module
+//! and its version, the output format, and the configuration profile, so
+//! that a new release of a module, another format, or another profile's
+//! credentials fetches anew. Outputs are stored by the SHA-256 hash of
+//! their content in `$ASIMOV_ROOT/cache/sources/objects`, so that identical
+//! outputs are stored once, and each cached fetch is recorded as a JSON file
+//! in `$ASIMOV_ROOT/cache/sources/entries`, named by the hash of its key.
+//! The cache is managed with `asimov cache`.
+
+use asimov_env::paths::asimov_root;
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// What a fetch's output is cached under.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Key {
+    pub url: String,
+    pub module: String,
+    /// The version of the module installed, if it is known.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The output format asked of the fetcher, if any.
+    #[serde(default)]
+    pub format: Option<String>,
+    /// The configuration profile that the fetcher was run with.
+    #[serde(default)]
+    pub profile: String,
+}
+
+impl Key {
+    /// The hash of the key, which names its entry.
+    pub fn id(&self) -> String {
+        let mut hasher = Sha256::new();
+        for field in [
+            Some(self.url.as_str()),
+            Some(self.module.as_str()),
+            self.version.as_deref(),
+            self.format.as_deref(),
+            Some(self.profile.as_str()),
+        ] {
+            // absent fields differ from empty ones
+            match field {
+                Some(field) => {
+                    hasher.update([1]);
+                    hasher.update(field);
+                    hasher.update([0]);
+                },
+                None => hasher.update([0]),
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// A cached fetch.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub key: Key,
+    /// The SHA-256 hash of the output, which names its object.
+    pub object: String,
+    pub size: u64,
+    /// When the output was fetched, in RFC 3339 format.
+    pub time: String,
+}
+
+impl Entry {
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.time.parse().ok()
+    }
+
+    /// Whether the output was fetched at or after the given time. Entries
+    /// whose time can't be read are never fresh.
+    pub fn is_fresh(&self, since: Timestamp) -> bool {
+        self.timestamp().is_some_and(|time| time >= since)
+    }
+}
+
+/// The cache of fetched sources.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(asimov_root().join("cache").join("sources"))
+    }
+}
+
+impl Cache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn entry_path(&self, id: &str) -> PathBuf {
+        self.root.join("entries").join(format!("{id}.json"))
+    }
+
+    fn object_path(&self, object: &str) -> PathBuf {
+        self.root.join("objects").join(&object[..2]).join(object)
+    }
+
+    /// The cached output for a key, if it was fetched at or after the given
+    /// time.
+    pub fn get(&self, key: &Key, since: Timestamp) -> io::Result<Option<(Entry, Vec<u8>)>> {
+        let Some(entry) = self.entry(&key.id())? else {
+            return Ok(None);
+        };
+        if entry.key != *key || !entry.is_fresh(since) {
+            return Ok(None);
+        }
+        match std::fs::read(self.object_path(&entry.object)) {
+            Ok(content) => Ok(Some((entry, content))),
+            // purged meanwhile
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Caches the output for a key, replacing any cached before.
+    pub fn put(&self, key: &Key, content: &[u8]) -> io::Result<Entry> {
+        let object = hex::encode(Sha256::digest(content));
+        let object_path = self.object_path(&object);
+        if !object_path.exists() {
+            write_atomically(&object_path, content)?;
+        }
+
+        let entry = Entry {
+            key: key.clone(),
+            object,
+            size: content.len() as u64,
+            time: Timestamp::now().to_string(),
+        };
+        write_atomically(&self.entry_path(&key.id()), &serde_json::to_vec(&entry)?)?;
+        Ok(entry)
+    }
+
+    fn entry(&self, id: &str) -> io::Result<Option<Entry>> {
+        let path = self.entry_path(id);
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let entry: Entry = match serde_json::from_slice(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!(
+                    "ignoring the malformed cache entry `{}`: {e}",
+                    path.display()
+                );
+                return Ok(None);
+            },
+        };
+        // the object is joined into a path, so it must be a hash
+        if !is_object_name(&entry.object) {
+            tracing::warn!(
+                "ignoring the cache entry `{}`, whose output is misnamed",
+                path.display()
+            );
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    /// Every cached fetch, in no particular order.
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        let dir = match std::fs::read_dir(self.root.join("entries")) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for file in dir {
+            let path = file?.path();
+            let Some(id) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            entries.extend(self.entry(id)?);
+        }
+        Ok(entries)
+    }
+
+    /// Removes a cached fetch. Its output is removed by
+    /// [`Cache::collect_garbage`], since other entries may share it.
+    pub fn remove(&self, entry: &Entry) -> io::Result<()> {
+        match std::fs::remove_file(self.entry_path(&entry.key.id())) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes the outputs that no entry refers to anymore, returning how
+    /// many bytes were freed.
+    pub fn collect_garbage(&self) -> io::Result<u64> {
+        let referenced: HashSet<String> = self
+            .entries()?
+            .into_iter()
+            .map(|entry| entry.object)
+            .collect();
+
+        let dir = match std::fs::read_dir(self.root.join("objects")) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut freed = 0;
+        for subdir in dir {
+            let subdir = subdir?.path();
+            if !subdir.is_dir() {
+                continue;
+            }
+            for file in std::fs::read_dir(&subdir)? {
+                let file = file?;
+                let name = file.file_name();
+                let name = name.to_string_lossy();
+                // being written, or else left from a write that failed
+                if referenced.contains(name.as_ref()) || name.starts_with('.') {
+                    continue;
+                }
+                freed += file.metadata()?.len();
+                std::fs::remove_file(file.path())?;
+            }
+            // only removed once empty
+            let _ = std::fs::remove_dir(&subdir);
+        }
+        Ok(freed)
+    }
+}
+
+/// Whether a name is that of an object: the hex SHA-256 hash of its content.
+fn is_object_name(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Writes a file so that it is never seen partly written.
+fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let dir = path.parent().expect("path should have a parent");
+    std::fs::create_dir_all(dir)?;
+    let temp = dir.join(format!(
+        ".{}.{}.tmp",
+        path.file_name()
+            .expect("path should have a file name")
+            .to_string_lossy(),
+        std::process::id()
+    ));
+    let mut file = std::fs::File::create(&temp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(url: &str) -> Key {
+        Key {
+            url: url.into(),
+            module: "demo".into(),
+            version: Some("1.0.0".into()),
+            format: None,
+            profile: "default".into(),
+        }
+    }
+
+    #[test]
+    fn caches_outputs_by_key() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let cache = Cache::new(dir.path());
+        let since = Timestamp::now() - jiff::SignedDuration::from_secs(60);
+
+        assert!(
+            cache
+                .get(&key("https://a.example/"), since)
+                .unwrap()
+                .is_none()
+        );
+        cache.put(&key("https://a.example/"), b"a").unwrap();
+        cache.put(&key("https://b.example/"), b"a").unwrap();
+
+        let (entry, content) = cache
+            .get(&key("https://a.example/"), since)
+            .unwrap()
+            .unwrap();
+        assert_eq!(content, b"a");
+        assert_eq!(entry.size, 1);
+        assert!(
+            cache
+                .get(
+                    &key("https://a.example/"),
+                    Timestamp::now() + jiff::SignedDuration::from_secs(60)
+                )
+                .unwrap()
+                .is_none()
+        );
+
+        let other_version = Key {
+            version: Some("2.0.0".into()),
+            ..key("https://a.example/")
+        };
+        assert!(cache.get(&other_version, since).unwrap().is_none());
+        let other_profile = Key {
+            profile: "staging".into(),
+            ..key("https://a.example/")
+        };
+        assert!(cache.get(&other_profile, since).unwrap().is_none());
+    }
+
+    #[test]
+    fn entries_with_a_misnamed_output_are_skipped() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let cache = Cache::new(dir.path());
+        let since = Timestamp::now() - jiff::SignedDuration::from_secs(60);
+
+        let mut entry = cache.put(&key("https://a.example/"), b"a").unwrap();
+        for object in ["a", "é", "../../../../etc/passwd"] {
+            entry.object = object.into();
+            std::fs::write(
+                cache.entry_path(&entry.key.id()),
+                serde_json::to_vec(&entry).unwrap(),
+            )
+            .unwrap();
+            assert!(cache.entries().unwrap().is_empty());
+            assert!(
+                cache
+                    .get(&key("https://a.example/"), since)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn outputs_are_removed_once_unreferenced() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let cache = Cache::new(dir.path());
+
+        let a = cache.put(&key("https://a.example/"), b"same").unwrap();
+        let b = cache.put(&key("https://b.example/"), b"same").unwrap();
+        assert_eq!(a.object, b.object);
+
+        cache.remove(&a).unwrap();
+        assert_eq!(cache.collect_garbage().unwrap(), 0);
+        cache.remove(&b).unwrap();
+        assert_eq!(cache.collect_garbage().unwrap(), 4);
+        assert!(cache.entries().unwrap().is_empty());
+    }
+}
@@ -18,5 +18,5 @@ pub mod proxy;
 #[cfg(feature = "source")]
 pub mod source;
 
-#[cfg(feature = "unstable")]
+#[cfg(any(feature = "unstable", feature = "cache"))]
 pub mod unstable;
@@ -1,9 +1,13 @@
 // This is free and unencumbered software released into the public domain.
 
 use clientele::SysexitsError::{self, *};
-use std::process::Stdio;
+use color_print::ceprintln;
+use std::{path::Path, process::Stdio};
 
-use crate::{Result, shared::locate_subcommand};
+use crate::{
+    Result,
+    shared::{self, locate_subcommand},
+};
 
 pub struct ExternalResult {
     /// Return code of the executed command.
@@ -23,22 +27,24 @@ pub struct ExternalSubcommand {
 }
 
 impl ExternalSubcommand {
-    pub fn execute(&self, cmd: &str, args: impl AsRef<[String]>) -> Result<ExternalResult> {
+    pub async fn execute(&self, cmd: &str, args: impl AsRef<[String]>) -> Result<ExternalResult> {
         // Locate the given subcommand:
         let cmd = locate_subcommand(cmd)?;
 
         // Prepare the process:
+        let mut command = std::process::Command::new(&cmd.path);
+        command.args(args.as_ref());
+        configure(&mut command, &cmd.path).await?;
+
         let result = if self.pipe_output {
-            std::process::Command::new(&cmd.path)
-                .args(args.as_ref())
+            command
                 .stdin(Stdio::inherit())
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
                 .output()
                 .map(|x| (x.status, Some(x.stdout), Some(x.stderr)))
         } else {
-            std::process::Command::new(&cmd.path)
-                .args(args.as_ref())
+            command
                 .stdin(Stdio::inherit())
                 .stdout(Stdio::inherit())
                 .stderr(Stdio::inherit())
@@ -86,3 +92,39 @@ impl ExternalSubcommand {
         }
     }
 }
+
+/// Hands the configuration of the installed module that provides a program,
+/// if one does, to the program.
+async fn configure(command: &mut std::process::Command, path: &Path) -> Result<()> {
+    let Some(program) = path.file_stem().and_then(|name| name.to_str()) else {
+        return Ok(());
+    };
+    // not every subcommand is a module's program
+    let Ok(modules) = asimov_registry::Registry::default()
+        .installed_modules()
+        .await
+    else {
+        return Ok(());
+    };
+    let Some(manifest) = modules
+        .into_iter()
+        .map(|module| module.manifest)
+        .find(|manifest| manifest.provides.programs.iter().any(|p| p == program))
+    else {
+        return Ok(());
+    };
+
+    let profile = shared::profile(None)?;
+    shared::configure_program(command, &manifest, &profile).map_err(|e| {
+        match e.downcast_ref::<SysexitsError>() {
+            Some(code) => *code,
+            None => {
+                ceprintln!(
+                    "<s,r>error:</> failed to configure module <s>{}</>: {e}",
+                    manifest.name
+                );
+                EX_CONFIG
+            },
+        }
+    })
+}
@@ -1,10 +1,14 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::BoxError;
+use crate::{BoxError, shared};
 use asimov_module::ModuleName;
-use clientele::{StandardOptions, SysexitsError::*, crates::clap::Subcommand};
+use clientele::{
+    StandardOptions,
+    SysexitsError::*,
+    crates::clap::{Subcommand, builder::PossibleValuesParser},
+};
 use color_print::ceprintln;
-use std::{string::String, vec::Vec};
+use std::{path::PathBuf, string::String, vec::Vec};
 
 #[derive(Debug, Subcommand)]
 pub enum ModuleCommand {
@@ -23,6 +27,10 @@ pub enum ModuleCommand {
 
         /// The name of the module whose configuration to show
         name: Option<ModuleName>,
+
+        /// The configuration profile to use [default: $ASIMOV_PROFILE or `default`]
+        #[arg(value_name = "NAME", long, global = true)]
+        profile: Option<String>,
     },
 
     /// Disable modules
@@ -38,12 +46,34 @@ pub enum ModuleCommand {
         name: ModuleName,
     },
 
+    /// Check the health of all installed modules
+    ///
+    /// Checks their configuration, that their programs are present and
+    /// runnable, and that enabled modules don't handle the same URLs.
+    Doctor {
+        /// Set the output format [default: cli] [possible values: cli, json]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
+    },
+
     /// Enable modules
     Enable {
         /// The names of the modules to enable
         names: Vec<ModuleName>,
     },
 
+    /// Print the installed modules, their state, and their configuration
+    ///
+    /// Writes one document listing each installed module's version, whether
+    /// it's enabled, and its stored configuration. Secret values are left
+    /// out. Apply the document elsewhere with `asimov module import`.
+    Export {
+        /// Set the output format [default: yaml] [possible values: yaml, json]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        #[arg(value_parser = PossibleValuesParser::new(["yaml", "json"]), hide_possible_values = true)]
+        output: Option<String>,
+    },
+
     /// TBD
     #[cfg(feature = "unstable")]
     #[clap(alias = "which")]
@@ -52,6 +82,38 @@ pub enum ModuleCommand {
         name: ModuleName,
     },
 
+    /// Install, configure, and enable modules as in an exported environment
+    ///
+    /// Installs the missing modules, stores the configuration values that
+    /// differ, and prompts for the secrets that aren't set. Importing the
+    /// same environment again changes nothing.
+    Import {
+        /// The environment to apply, in YAML or JSON, or `-` for standard input
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// The number of modules to download and install at once
+        #[arg(value_name = "N", short = 'j', long, default_value_t = 4)]
+        jobs: usize,
+    },
+
+    /// Show an available module's details, without installing it
+    ///
+    /// The manifest comes from the module index, or else from the module's
+    /// latest release. Its releases and README are fetched as well.
+    Info {
+        /// The name of the module to show
+        name: ModuleName,
+
+        /// Set the output format [default: cli] [possible values: cli, json]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
+
+        /// Use the cached module index only, without using the network
+        #[arg(long)]
+        offline: bool,
+    },
+
     /// Inspect a module's manifest, state, and configuration status
     #[clap(alias = "show")]
     Inspect {
@@ -65,17 +127,45 @@ pub enum ModuleCommand {
 
     /// Install an available module locally
     Install {
-        /// The names of the modules to install
-        names: Vec<ModuleName>,
-
-        /// Optionally install a specific version instead of latest
-        #[arg(long)]
+        /// The names of the modules to install, each optionally followed by
+        /// `@` and a version or a semver range, e.g. `serpapi@1.2.0`
+        #[arg(value_name = "NAME[@VERSION]", conflicts_with = "locked")]
+        names: Vec<ModuleSpec>,
+
+        /// Optionally install a specific version instead of latest,
+        /// for the modules not given one with `@`
+        #[arg(long, conflicts_with = "locked")]
         version: Option<String>,
 
+        /// Install exactly the modules recorded in the given lockfile,
+        /// failing if anything installed differs from it
+        #[arg(value_name = "LOCKFILE", long)]
+        locked: Option<PathBuf>,
+
+        /// Install from a local release archive, a module directory with
+        /// `.asimov/module.yaml`, or a mirror directory of release archives
+        #[arg(value_name = "PATH", long, conflicts_with = "locked")]
+        from: Option<PathBuf>,
+
+        /// Don't install the modules that the given modules require
+        #[arg(long, conflicts_with = "locked")]
+        no_deps: bool,
+
         /// Optionally specify desired model size to download for module.
-        /// Only affects modules which require models.
-        #[arg(long)]
+        /// Only affects modules which require models. Models aren't
+        /// installed from local files, so this can't be used with `--from`.
+        #[arg(long, conflicts_with = "from")]
         model_size: Option<String>,
+
+        /// The number of modules to download and install at once
+        #[arg(
+            value_name = "N",
+            short = 'j',
+            long,
+            default_value_t = 4,
+            conflicts_with = "locked"
+        )]
+        jobs: usize,
     },
 
     /// Print the module's package link
@@ -93,21 +183,104 @@ pub enum ModuleCommand {
         output: Option<String>,
     },
 
+    /// Record the installed modules and their versions in a lockfile
+    Lock {
+        /// The path of the lockfile to write [default: asimov.lock]
+        path: Option<PathBuf>,
+    },
+
+    /// Show the logged runs of a module's programs
+    ///
+    /// Each run by `asimov source fetch`, `read`, and `list`, by `asimov
+    /// module config setup`, and by `asimov agent ask` is logged with its
+    /// command line, duration, exit status, and stderr.
+    Logs {
+        /// The name of the module to show the logs of
+        name: ModuleName,
+
+        /// Keep showing runs as they are logged
+        #[arg(short = 'f', long)]
+        follow: bool,
+
+        /// Show only runs since a time, such as `1h`, `2 days`, or `2025-06-01`
+        #[arg(value_name = "WHEN", long)]
+        since: Option<String>,
+
+        /// Set the output format [default: cli] [possible values: cli, jsonl]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
+    },
+
+    /// Check installed modules for newer releases
+    ///
+    /// Exits with a non-zero status when any module can be upgraded.
+    Outdated {
+        /// Set the output format [default: cli] [possible values: cli, json, jsonl]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
+    },
+
+    /// Prefer a module for the URLs that a pattern matches
+    ///
+    /// The pattern is a host such as `linkedin.com`, which matches its
+    /// subdomains too, optionally followed by a path, or a URL prefix such
+    /// as `https://example.org/api/`. Without arguments, lists the
+    /// preferences.
+    Prefer {
+        /// The hosts or URLs to prefer the module for
+        #[arg(requires = "name")]
+        pattern: Option<String>,
+
+        /// The name of the module to prefer
+        name: Option<ModuleName>,
+    },
+
     /// Resolve a given URL to modules which can handle it
     Resolve {
         /// The URL to resolve
         url: String,
+
+        /// Show every installed module that handles the URL, the rules that
+        /// matched, and which module each of fetch, read, and list would use
+        #[arg(long)]
+        explain: bool,
+
+        /// Set the output format [default: cli] [possible values: cli, json]
+        ///
+        /// JSON output is always the explanation.
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        #[arg(value_parser = PossibleValuesParser::new(["cli", "json"]), hide_possible_values = true)]
+        output: Option<String>,
+    },
+
+    /// Reinstall the previously installed version of a module
+    Rollback {
+        /// The name of the module to roll back
+        name: ModuleName,
     },
 
     /// Search the index of available modules
+    ///
+    /// Modules are ranked by how well they match the terms. Terms of the
+    /// form `provides:<<program>>`, `handles:<<url>>`, `type:<<rust|python|ruby>>`,
+    /// or `tag:<<tag>>` filter the modules instead. The index is cached, and
+    /// checked for changes after an hour.
     Search {
-        /// The terms to search for, all of which must match
+        /// The terms and filters to search for
         #[clap(required = true)]
         query: Vec<String>,
 
         /// Set the output format [default: cli] [possible values: cli, jsonl]
         #[arg(value_name = "FORMAT", short = 'o', long)]
         output: Option<String>,
+
+        /// Use the cached index only, without using the network
+        #[arg(long, conflicts_with = "refresh")]
+        offline: bool,
+
+        /// Download the index anew, even if the cached one is recent
+        #[arg(long)]
+        refresh: bool,
     },
 
     /// Uninstall a currently installed module
@@ -116,15 +289,29 @@ pub enum ModuleCommand {
         names: Vec<ModuleName>,
     },
 
+    /// Stop preferring a module, or any module, for a pattern
+    Unprefer {
+        /// The pattern the module is preferred for
+        pattern: String,
+
+        /// The name of the module to stop preferring [default: all of them]
+        name: Option<ModuleName>,
+    },
+
     /// Upgrade currently installed modules
     ///
-    /// By default upgrades all installed modules.
+    /// By default upgrades all installed modules. A module whose upgrade
+    /// fails is restored to its previous version, and the last few versions
+    /// of each module are kept for `asimov module rollback`.
     #[clap(alias = "update")]
     Upgrade {
-        /// The names of the modules to upgrade
-        names: Vec<ModuleName>,
+        /// The names of the modules to upgrade, each optionally followed by
+        /// `@` and a version or a semver range that caps the upgrade
+        #[arg(value_name = "NAME[@VERSION]")]
+        names: Vec<ModuleSpec>,
 
-        /// Optionally upgrade to a specific version instead of latest
+        /// Optionally upgrade to a specific version instead of latest,
+        /// for the modules not given one with `@`
         #[arg(long)]
         version: Option<String>,
 
@@ -132,6 +319,20 @@ pub enum ModuleCommand {
         /// Only affects modules which require models.
         #[arg(long)]
         model_size: Option<String>,
+
+        /// The number of modules to download and upgrade at once
+        #[arg(value_name = "N", short = 'j', long, default_value_t = 4)]
+        jobs: usize,
+    },
+
+    /// Check that installed modules' programs are present and unchanged
+    Verify {
+        /// The names of the modules to verify [default: all installed]
+        names: Vec<ModuleName>,
+
+        /// Set the output format [default: cli] [possible values: cli, jsonl]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
     },
 }
 
@@ -141,15 +342,23 @@ impl ModuleCommand {
         match self {
             Browse { name } => browse(name, flags).await,
 
-            Config { command, name } => match (command, name) {
-                (Some(command), _) => command.run(flags).await,
+            Config {
+                command,
+                name,
+                profile,
+            } => match (command, name) {
+                (Some(command), _) => {
+                    command
+                        .run(&shared::profile(profile.as_deref())?, flags)
+                        .await
+                },
                 // a bare module name lists its configuration
                 (None, Some(name)) => {
                     ConfigCommand::Show {
                         name: name.clone(),
                         output: None,
                     }
-                    .run(flags)
+                    .run(&shared::profile(profile.as_deref())?, flags)
                     .await
                 },
                 (None, None) => {
@@ -165,38 +374,118 @@ impl ModuleCommand {
 
             Doc { name } => doc(name, flags).await,
 
+            Doctor { output } => doctor(output.as_deref().unwrap_or("cli").into(), flags).await,
+
             Enable { names } => enable(names, flags).await,
 
+            Export { output } => export(output.as_deref().unwrap_or("yaml").into(), flags).await,
+
             #[cfg(feature = "unstable")]
             Find { name } => find(name, flags).await,
 
+            Import { path, jobs } => import(&path, jobs, flags).await,
+
+            Info {
+                name,
+                output,
+                offline,
+            } => {
+                info(
+                    name,
+                    output.as_deref().unwrap_or("cli").into(),
+                    offline,
+                    flags,
+                )
+                .await
+            },
+
             Inspect { name, output } => {
                 inspect(name, output.as_deref().unwrap_or("cli").into(), flags).await
             },
 
             Install {
+                locked: Some(lockfile),
+                model_size,
+                ..
+            } => install_locked(&lockfile, model_size, flags).await,
+
+            Install {
+                from: Some(path),
                 names,
                 version,
+                no_deps,
+                ..
+            } => install_from(&path, names, version, no_deps, flags).await,
+
+            Install {
+                names,
+                version,
+                no_deps,
                 model_size,
-            } => install(names, version, model_size, flags).await,
+                jobs,
+                ..
+            } => install(names, version, model_size, no_deps, jobs, flags).await,
 
             Link { name } => link(name, flags).await,
 
             List { output } => list(output.as_deref().unwrap_or("cli").into(), flags).await,
 
-            Resolve { url } => resolve(url, flags).await,
+            Lock { path } => lock(path, flags).await,
+
+            Logs {
+                name,
+                follow,
+                since,
+                output,
+            } => {
+                let output = output.as_deref().unwrap_or("cli").into();
+                logs(name, follow, since, output, flags).await
+            },
+
+            Outdated { output } => outdated(output.as_deref().unwrap_or("cli").into(), flags).await,
+
+            Prefer { pattern, name } => prefer(pattern, name, flags).await,
+
+            Resolve {
+                url,
+                explain,
+                output,
+            } => {
+                resolve(
+                    url,
+                    explain,
+                    output.as_deref().unwrap_or("cli").into(),
+                    flags,
+                )
+                .await
+            },
+
+            Rollback { name } => rollback(name, flags).await,
 
-            Search { query, output } => {
-                search(query, output.as_deref().unwrap_or("cli").into(), flags).await
+            Search {
+                query,
+                output,
+                offline,
+                refresh,
+            } => {
+                let output = output.as_deref().unwrap_or("cli").into();
+                search(query, output, offline, refresh, flags).await
             },
 
             Uninstall { names } => uninstall(names, flags).await,
 
+            Unprefer { pattern, name } => unprefer(pattern, name, flags).await,
+
             Upgrade {
                 names,
                 version,
                 model_size,
-            } => upgrade(names, version, model_size, flags).await,
+                jobs,
+            } => upgrade(names, version, model_size, jobs, flags).await,
+
+            Verify { names, output } => {
+                verify(names, output.as_deref().unwrap_or("cli").into(), flags).await
+            },
         }
     }
 }
@@ -207,38 +496,92 @@ pub use browse::*;
 mod config;
 pub use config::*;
 
+mod constraints;
+use constraints::*;
+
+mod deps;
+pub use deps::*;
+
 mod disable;
 pub use disable::*;
 
 mod doc;
 pub use doc::*;
 
+mod doctor;
+pub use doctor::*;
+
 mod enable;
 pub use enable::*;
 
+mod export;
+pub use export::*;
+
 mod find;
 pub use find::*;
 
+mod import;
+pub use import::*;
+
+mod index;
+use index::*;
+
+mod info;
+pub use info::*;
+
 mod inspect;
 pub use inspect::*;
 
 mod install;
 pub use install::*;
 
+mod jobs;
+use jobs::*;
+
 mod link;
 pub use link::*;
 
 mod list;
 pub use list::*;
 
+mod local;
+pub use local::*;
+
+mod lock;
+pub use lock::*;
+
+mod logs;
+pub use logs::*;
+
+mod outdated;
+pub use outdated::*;
+
+mod prefer;
+pub use prefer::*;
+
+mod release;
+use release::*;
+
+mod render;
+use render::*;
+
 mod resolve;
 pub use resolve::*;
 
+mod rollback;
+pub use rollback::*;
+
 mod search;
 pub use search::*;
 
+mod spec;
+pub use spec::*;
+
 mod uninstall;
 pub use uninstall::*;
 
 mod upgrade;
 pub use upgrade::*;
+
+mod verify;
+pub use verify::*;
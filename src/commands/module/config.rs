@@ -1,5 +1,6 @@
 // This is free and unencumbered software released into the public domain.
 
+use super::{Constraint, read_constraints};
 use crate::BoxError;
 use asimov_env::paths::asimov_root;
 use asimov_module::{ConfigurationVariable, ModuleManifest, ModuleName};
@@ -9,7 +10,7 @@ use clientele::{
     crates::clap::{Subcommand, builder::PossibleValuesParser},
 };
 use color_print::ceprintln;
-use std::{path::PathBuf, string::String, vec::Vec};
+use std::{collections::BTreeMap, io::IsTerminal, path::PathBuf, string::String, vec::Vec};
 
 #[derive(Debug, Subcommand)]
 pub enum ConfigCommand {
@@ -77,34 +78,125 @@ pub enum ConfigCommand {
         /// The name of the module
         name: ModuleName,
     },
+
+    /// Edit a module's configuration in a text editor
+    ///
+    /// Opens the stored values of all variables but secrets in `$VISUAL`
+    /// or `$EDITOR` as YAML, and applies the changes once they are valid.
+    Edit {
+        /// The name of the module
+        name: ModuleName,
+    },
+
+    /// Print a module's stored configuration, to import elsewhere
+    ///
+    /// Secrets are left out, unless included encrypted to a passphrase,
+    /// prompted for or given in `ASIMOV_CONFIG_PASSPHRASE`.
+    Export {
+        /// The name of the module
+        name: ModuleName,
+
+        /// Set the output format [default: yaml] [possible values: yaml, json]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        #[arg(value_parser = PossibleValuesParser::new(["yaml", "json"]), hide_possible_values = true)]
+        output: Option<String>,
+
+        /// Include secrets, encrypted to a passphrase
+        #[arg(long)]
+        include_secrets: bool,
+    },
+
+    /// Store the configuration in a document printed by `config export`
+    ///
+    /// Values in the environment still take precedence over the stored ones.
+    Import {
+        /// The document to import, or `-` for standard input
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Keep stored values that the document doesn't hold (the default)
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+
+        /// Unset stored values that the document doesn't hold
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// List the profiles that hold configuration for a module
+    Profiles {
+        /// The name of the module
+        name: ModuleName,
+    },
+
+    /// Encrypt the stored secrets of all modules with a new key
+    ///
+    /// Secrets are those variables that a module marks `secret`. By default
+    /// a new key is generated into a key file next to the secrets, which
+    /// only guards against disclosing them by accident. Secrets still stored
+    /// unencrypted are encrypted as well.
+    Rekey {
+        /// Derive the key from a passphrase, prompted for now and whenever
+        /// secrets are read, unless given in `ASIMOV_SECRETS_PASSPHRASE`
+        #[arg(long, conflicts_with = "key_file")]
+        passphrase: bool,
+
+        /// Use the key in the given file, generating one if it doesn't exist
+        #[arg(value_name = "PATH", long)]
+        key_file: Option<PathBuf>,
+    },
 }
 
 impl ConfigCommand {
-    pub async fn run(&self, flags: &StandardOptions) -> Result<(), BoxError> {
+    pub async fn run(&self, profile: &str, flags: &StandardOptions) -> Result<(), BoxError> {
         use ConfigCommand::*;
         match self {
             Show { name, output } => {
-                show::show(name, output.as_deref().unwrap_or("cli"), flags).await
+                show::show(name, output.as_deref().unwrap_or("cli"), profile, flags).await
             },
-            Get { name, key, stored } => get::get(name, key, *stored, flags).await,
+            Get { name, key, stored } => get::get(name, key, *stored, profile, flags).await,
             Set {
                 name,
                 assignments,
                 stdin,
                 from_json,
-            } => set::set(name, assignments, *stdin, *from_json, flags).await,
-            Unset { name, keys, all } => unset::unset(name, keys, *all, flags).await,
-            Setup { name } => setup::setup(name, flags).await,
+            } => set::set(name, assignments, *stdin, *from_json, profile, flags).await,
+            Unset { name, keys, all } => unset::unset(name, keys, *all, profile, flags).await,
+            Setup { name } => setup::setup(name, profile, flags).await,
+            Edit { name } => edit::edit(name, profile, flags).await,
+            Export {
+                name,
+                output,
+                include_secrets,
+            } => {
+                let output = output.as_deref().unwrap_or("yaml");
+                export::export(name, output, *include_secrets, profile, flags).await
+            },
+            Import { path, replace, .. } => import::import(path, *replace, profile, flags).await,
+            Profiles { name } => profiles::profiles(name, profile, flags).await,
+            Rekey {
+                passphrase,
+                key_file,
+            } => rekey::rekey(*passphrase, key_file.clone(), flags).await,
         }
     }
 }
 
+mod edit;
+mod export;
 mod get;
+mod import;
+mod profiles;
+mod rekey;
 mod set;
 mod setup;
 mod show;
 mod unset;
 
+/// The environment variable that supplies the passphrase for secrets in
+/// exported configuration, instead of prompting for it.
+pub(super) const TRANSFER_PASSPHRASE_ENV: &str = "ASIMOV_CONFIG_PASSPHRASE";
+
 /// Stands in for secret values, which are never displayed unless requested
 /// explicitly by name.
 pub(super) const MASK: &str = "******";
@@ -113,14 +205,17 @@ pub(super) const MASK: &str = "******";
 pub(super) struct Module {
     pub name: ModuleName,
     pub manifest: ModuleManifest,
-    pub profile: &'static str,
+    pub profile: String,
     pub conf_dir: PathBuf,
+    pub constraints: BTreeMap<String, Constraint>,
 }
 
 /// Reads the manifest of an installed module, rejecting manifests whose
-/// variable names cannot be used as file names.
-pub(super) async fn open(module_name: &ModuleName) -> Result<Module, BoxError> {
-    let manifest = asimov_registry::Registry::default()
+/// variable names cannot be used as file names, for configuring it in the
+/// given profile.
+pub(super) async fn open(module_name: &ModuleName, profile: &str) -> Result<Module, BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let manifest = registry
         .read_manifest(module_name)
         .await
         .map_err(|e| {
@@ -158,7 +253,6 @@ pub(super) async fn open(module_name: &ModuleName) -> Result<Module, BoxError> {
         return Err(EX_DATAERR.into());
     }
 
-    let profile = "default"; // TODO
     let conf_dir = asimov_root()
         .join("configs")
         .join(profile)
@@ -167,8 +261,9 @@ pub(super) async fn open(module_name: &ModuleName) -> Result<Module, BoxError> {
     Ok(Module {
         name: module_name.clone(),
         manifest,
-        profile,
+        profile: profile.into(),
         conf_dir,
+        constraints: read_constraints(&registry, module_name).await,
     })
 }
 
@@ -209,10 +304,112 @@ impl Module {
         Ok(variables)
     }
 
+    /// Checks a value against the constraints the manifest declares on its
+    /// variable, describing what was expected if it doesn't conform.
+    pub fn check(&self, key: &str, value: &str) -> Result<(), String> {
+        match self.constraints.get(key) {
+            Some(constraint) => constraint.check(value),
+            None => Ok(()),
+        }
+    }
+
     pub fn var_file(&self, key: &str) -> PathBuf {
         self.conf_dir.join(key)
     }
 
+    /// The value stored for a variable, if any, regardless of whether the
+    /// environment overrides it.
+    pub async fn stored(&self, key: &str) -> tokio::io::Result<Option<String>> {
+        match tokio::fs::read_to_string(self.var_file(key)).await {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Names where a value is stored, binding encrypted values to it.
+    pub fn secret_context(&self, key: &str) -> String {
+        format!("{}/{}/{key}", self.profile, self.name)
+    }
+
+    /// Stores a value, encrypting it if it is a secret.
+    pub async fn store(&self, var: &ConfigurationVariable, value: &str) -> Result<(), BoxError> {
+        let value = self.seal(var, value)?;
+        tokio::fs::write(self.var_file(&var.name), value).await?;
+        Ok(())
+    }
+
+    /// Stores new values and unsets others, where `None` unsets a variable.
+    /// Every new value is written aside before any is moved into place, so
+    /// that a failed write leaves the stored configuration as it was.
+    pub async fn apply(
+        &self,
+        changes: &[(&ConfigurationVariable, Option<&str>)],
+    ) -> Result<(), BoxError> {
+        self.create_conf_dir().await?;
+        self.set_permissions().await?;
+
+        let mut pending: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for (var, value) in changes {
+            let Some(value) = value else {
+                continue;
+            };
+            let temp_path = self.conf_dir.join(format!(".{}.new", var.name));
+            let written = match self.seal(var, value) {
+                Ok(value) => tokio::fs::write(&temp_path, value).await.map_err(|e| {
+                    tracing::error!("failed to write `{}`: {e}", temp_path.display());
+                    e.into()
+                }),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                tokio::fs::remove_file(&temp_path).await.ok();
+                for (temp_path, _) in pending {
+                    tokio::fs::remove_file(temp_path).await.ok();
+                }
+                return Err(e);
+            }
+            pending.push((temp_path, self.var_file(&var.name)));
+        }
+
+        for (temp_path, path) in pending {
+            tokio::fs::rename(&temp_path, &path)
+                .await
+                .inspect_err(|e| {
+                    tracing::error!("failed to replace `{}`: {e}", path.display());
+                })?;
+        }
+
+        for (var, value) in changes {
+            if value.is_some() {
+                continue;
+            }
+            match tokio::fs::remove_file(self.var_file(&var.name)).await {
+                Err(e) if e.kind() != tokio::io::ErrorKind::NotFound => {
+                    tracing::error!("failed to unset configuration variable `{}`: {e}", var.name);
+                    return Err(e.into());
+                },
+                _ => (),
+            }
+        }
+
+        self.set_permissions().await?;
+        Ok(())
+    }
+
+    /// Prepares a value for storage, encrypting it if it is a secret.
+    fn seal(&self, var: &ConfigurationVariable, value: &str) -> Result<String, BoxError> {
+        if var.secret {
+            return crate::secrets::conceal(&self.secret_context(&var.name), value);
+        }
+        Ok(value.to_string())
+    }
+
+    /// Decrypts a value read from storage, if it is encrypted.
+    pub fn reveal(&self, key: &str, value: String) -> Result<String, BoxError> {
+        crate::secrets::reveal(&self.secret_context(key), value)
+    }
+
     /// Where the effective value of a variable comes from, in the same
     /// precedence the SDK resolves them: environment, then stored, then default.
     pub async fn source(&self, var: &ConfigurationVariable) -> Source {
@@ -333,3 +530,28 @@ pub(super) fn prompt_for_value(prompt: String, secret: bool) -> Result<String, B
         EX_IOERR.into()
     })
 }
+
+/// Reads the passphrase for secrets in exported configuration from the
+/// environment, or else prompts for it, twice when choosing a new one.
+pub(super) fn transfer_passphrase(new: bool) -> Result<String, BoxError> {
+    if let Ok(passphrase) = std::env::var(TRANSFER_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        ceprintln!("<s,r>error:</> a passphrase for the exported secrets is required");
+        ceprintln!(
+            "<s,dim>hint:</> Provide it in the environment variable <s>{TRANSFER_PASSPHRASE_ENV}</>"
+        );
+        return Err(EX_UNAVAILABLE.into());
+    }
+
+    let mut prompt = dialoguer::Password::new().with_prompt("Passphrase for the exported secrets");
+    if new {
+        prompt = prompt.with_confirmation("Repeat the passphrase", "The passphrases don't match.");
+    }
+    prompt.interact().map_err(|e| {
+        tracing::error!("failed to read the passphrase from the terminal: {e}");
+        EX_IOERR.into()
+    })
+}
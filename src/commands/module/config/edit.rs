@@ -0,0 +1,290 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{Module, open};
+use crate::BoxError;
+use asimov_module::{ConfigurationVariable, ModuleName};
+use clientele::{StandardOptions, SysexitsError::*};
+use color_print::{ceprintln, cprintln};
+use std::{collections::BTreeMap, io::IsTerminal, path::Path};
+
+/// Edits the stored values of a module's variables, other than secrets, in a
+/// YAML document opened in `$VISUAL` or `$EDITOR`. Nothing changes unless the
+/// whole edited document is valid.
+pub async fn edit(
+    module_name: &ModuleName,
+    profile: &str,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let module = open(module_name, profile).await?;
+    let variables: Vec<&ConfigurationVariable> = module
+        .require_variables()?
+        .iter()
+        .filter(|var| !var.secret)
+        .collect();
+
+    if variables.is_empty() {
+        ceprintln!(
+            "<s,dim>note:</> module <s>{}</> has only secret configuration variables",
+            module.name
+        );
+        ceprintln!(
+            "<s,dim>hint:</> Set them with: <s>asimov module config set {} --stdin KEY</>",
+            module.name
+        );
+        return Ok(());
+    }
+
+    let mut stored = BTreeMap::new();
+    for var in &variables {
+        if let Ok(value) = tokio::fs::read_to_string(module.var_file(&var.name)).await {
+            stored.insert(var.name.clone(), value);
+        }
+    }
+
+    let document = render(&module, &variables, &stored);
+    let path = std::env::temp_dir().join(format!(
+        "asimov-{}-config-{}.yaml",
+        module.name,
+        std::process::id()
+    ));
+    write_draft(&path, &document).await?;
+    let edited = edit_until_valid(&module, &path).await;
+    tokio::fs::remove_file(&path).await.ok();
+
+    let Some(wanted) = edited? else {
+        if flags.verbose > 0 {
+            ceprintln!(
+                "<s,dim>note:</> The document wasn't changed, so neither was the configuration."
+            );
+        }
+        return Ok(());
+    };
+
+    let changes: Vec<(&ConfigurationVariable, Option<&str>)> = variables
+        .iter()
+        .map(|var| (*var, wanted.get(&var.name).and_then(Option::as_deref)))
+        .filter(|(var, value)| stored.get(&var.name).map(String::as_str) != *value)
+        .collect();
+
+    if changes.is_empty() {
+        if flags.verbose > 0 {
+            ceprintln!("<s,dim>note:</> The configuration is unchanged.");
+        }
+        return Ok(());
+    }
+
+    module.apply(&changes).await?;
+
+    if flags.verbose > 0 {
+        cprintln!(
+            "<s,g>✓</> Changed {} configuration value(s) for module <s>{}</>.",
+            changes.len(),
+            module.name
+        );
+    }
+
+    Ok(())
+}
+
+/// Renders the document to edit: stored values, commented with the
+/// variables' descriptions; empty placeholders for unset required variables;
+/// and the other unset variables commented out.
+fn render(
+    module: &Module,
+    variables: &[&ConfigurationVariable],
+    stored: &BTreeMap<String, String>,
+) -> String {
+    let mut document = format!(
+        "# Configuration of module `{}` in profile `{}`.\n\
+         #\n\
+         # Remove a variable, or leave it empty, to unset it. Secrets aren't\n\
+         # listed; set them with `asimov module config set {} --stdin KEY`.\n",
+        module.name, module.profile, module.name
+    );
+
+    for var in variables {
+        document.push('\n');
+        if let Some(description) = &var.description {
+            for line in description.lines() {
+                document.push_str(format!("# {line}").trim_end());
+                document.push('\n');
+            }
+        }
+
+        match stored.get(&var.name) {
+            Some(value) => {
+                let value = serde_json::to_string(value).expect("strings should serialize");
+                document.push_str(&format!("{}: {value}\n", var.name));
+            },
+            None if var.is_required() => {
+                document.push_str(&format!("# (required)\n{}:\n", var.name));
+            },
+            None => {
+                if let Some(default_value) = &var.default_value {
+                    document.push_str(&format!("# (optional, default: `{default_value}`)\n"));
+                }
+                document.push_str(&format!("# {}:\n", var.name));
+            },
+        }
+    }
+
+    document
+}
+
+/// Opens the document in the editor until it is saved with valid values,
+/// which are returned, or as it was drafted. Invalid values are reported,
+/// and on a terminal the document can be edited again.
+async fn edit_until_valid(
+    module: &Module,
+    path: &Path,
+) -> Result<Option<BTreeMap<String, Option<String>>>, BoxError> {
+    // a rejected edit left as it is stays rejected, so only the draft itself
+    // counts as no change
+    let draft = tokio::fs::read_to_string(path).await?;
+    loop {
+        run_editor(path)?;
+
+        let edited = tokio::fs::read_to_string(path).await.inspect_err(|e| {
+            tracing::error!("failed to read `{}`: {e}", path.display());
+        })?;
+        if edited == draft {
+            return Ok(None);
+        }
+
+        match parse(module, &edited) {
+            Ok(wanted) => return Ok(Some(wanted)),
+            Err(errors) => {
+                for error in errors {
+                    ceprintln!("<s,r>error:</> {error}");
+                }
+            },
+        }
+
+        let again = std::io::stdin().is_terminal()
+            && dialoguer::Confirm::new()
+                .with_prompt("Edit the document again?")
+                .default(true)
+                .interact()
+                .unwrap_or(false);
+        if !again {
+            return Err(EX_DATAERR.into());
+        }
+    }
+}
+
+/// Parses the edited document into the wanted value of each variable it
+/// lists, where `None` leaves a variable unset, collecting every problem.
+fn parse(module: &Module, document: &str) -> Result<BTreeMap<String, Option<String>>, Vec<String>> {
+    let entries = match serde_yml::from_str(document) {
+        // a document of only comments unsets everything
+        Ok(serde_json::Value::Null) => serde_json::Map::new(),
+        Ok(serde_json::Value::Object(entries)) => entries,
+        Ok(_) => return Err(vec!["the document is not a YAML mapping".into()]),
+        Err(e) => return Err(vec![format!("the document is not valid YAML: {e}")]),
+    };
+
+    let mut wanted = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (key, value) in entries {
+        let Some(var) = module.variables().iter().find(|var| var.name == key) else {
+            errors.push(format!(
+                "`{key}` is not the name of a configuration variable for module `{}`",
+                module.name
+            ));
+            continue;
+        };
+        if var.secret {
+            errors.push(format!(
+                "`{key}` is a secret, so set it with `asimov module config set {} --stdin {key}`",
+                module.name
+            ));
+            continue;
+        }
+
+        let value = match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(value) => Some(value),
+            value @ (serde_json::Value::Bool(_) | serde_json::Value::Number(_)) => {
+                Some(value.to_string())
+            },
+            _ => {
+                errors.push(format!("the value of `{key}` is not a single value"));
+                continue;
+            },
+        };
+        if let Some(value) = &value
+            && let Err(expected) = module.check(&key, value)
+        {
+            errors.push(format!("invalid value for `{key}`: {expected}"));
+            continue;
+        }
+        wanted.insert(key, value);
+    }
+
+    if errors.is_empty() {
+        Ok(wanted)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Creates the document only readable by the user, since it holds the
+/// stored values.
+async fn write_draft(path: &Path, document: &str) -> Result<(), BoxError> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let result = async {
+        use tokio::io::AsyncWriteExt;
+        let mut file = options.open(path).await?;
+        file.write_all(document.as_bytes()).await?;
+        file.flush().await
+    }
+    .await;
+
+    result.map_err(|e| {
+        tracing::error!("failed to write `{}`: {e}", path.display());
+        e.into()
+    })
+}
+
+/// Runs the user's editor on a file, the way Git does: `$VISUAL`, else
+/// `$EDITOR`, interpreted by the shell so that it may include arguments.
+fn run_editor(path: &Path) -> Result<(), BoxError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.into());
+
+    #[cfg(unix)]
+    let mut command = {
+        let mut command = std::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("{editor} \"$@\""))
+            .arg(&editor)
+            .arg(path);
+        command
+    };
+    #[cfg(not(unix))]
+    let mut command = {
+        let mut words = editor.split_whitespace();
+        let mut command = std::process::Command::new(words.next().unwrap_or("notepad"));
+        command.args(words).arg(path);
+        command
+    };
+
+    let status = command.status().map_err(|e| {
+        ceprintln!("<s,r>error:</> failed to run the editor `{editor}`: {e}");
+        EX_UNAVAILABLE
+    })?;
+    if !status.success() {
+        ceprintln!("<s,r>error:</> the editor `{editor}` failed: {status}");
+        ceprintln!("<s,dim>note:</> The configuration is unchanged.");
+        return Err(EX_SOFTWARE.into());
+    }
+    Ok(())
+}
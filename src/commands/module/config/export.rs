@@ -0,0 +1,99 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{open, transfer_passphrase};
+use crate::{BoxError, secrets::PassphraseKey};
+use asimov_module::ModuleName;
+use clientele::StandardOptions;
+use color_print::ceprintln;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The format version of configuration documents written by this version of
+/// the CLI.
+pub(super) const CONFIG_VERSION: u32 = 1;
+
+/// A module's stored configuration, as written by `asimov module config
+/// export` and read by `asimov module config import`.
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct ConfigDocument {
+    pub version: u32,
+    pub module: String,
+    /// The stored values of non-secret configuration variables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<ExportedSecrets>,
+}
+
+/// The stored values of secret configuration variables, encrypted with a
+/// key derived from a passphrase.
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct ExportedSecrets {
+    #[serde(flatten)]
+    pub key: PassphraseKey,
+    pub values: BTreeMap<String, String>,
+}
+
+/// Names an exported secret, binding its encrypted value to it.
+pub(super) fn exported_context(module_name: &str, key: &str) -> String {
+    format!("{module_name}/{key}")
+}
+
+/// Prints the stored configuration of a module as a YAML or JSON document.
+/// Secrets are left out unless requested, and then encrypted.
+pub async fn export(
+    module_name: &ModuleName,
+    output: &str,
+    include_secrets: bool,
+    profile: &str,
+    _flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let module = open(module_name, profile).await?;
+
+    let mut config = BTreeMap::new();
+    let mut secrets = BTreeMap::new();
+    for var in module.variables() {
+        // stored values are exported even where the environment overrides them
+        let Some(value) = module.stored(&var.name).await? else {
+            continue;
+        };
+        if var.secret {
+            secrets.insert(var.name.clone(), value);
+        } else {
+            config.insert(var.name.clone(), value);
+        }
+    }
+
+    let secrets = if include_secrets && !secrets.is_empty() {
+        let (key, keyring) = PassphraseKey::new(&transfer_passphrase(true)?);
+        let mut values = BTreeMap::new();
+        for (name, value) in secrets {
+            let value = module.reveal(&name, value)?;
+            let context = exported_context(module.name.as_str(), &name);
+            values.insert(name, keyring.encrypt(&context, &value));
+        }
+        Some(ExportedSecrets { key, values })
+    } else {
+        if !include_secrets && !secrets.is_empty() {
+            ceprintln!(
+                "<s,dim>note:</> Left out {} stored secret(s); include them, encrypted to a passphrase, with: <s>--include-secrets</>",
+                secrets.len()
+            );
+        }
+        None
+    };
+
+    let document = ConfigDocument {
+        version: CONFIG_VERSION,
+        module: module.name.to_string(),
+        config,
+        secrets,
+    };
+
+    match output {
+        "json" => println!("{}", serde_json::to_string_pretty(&document)?),
+        _ => print!("{}", serde_yml::to_string(&document)?),
+    }
+
+    Ok(())
+}
@@ -12,16 +12,17 @@ pub async fn get(
     module_name: &ModuleName,
     key: &str,
     stored: bool,
+    profile: &str,
     _flags: &StandardOptions,
 ) -> Result<(), BoxError> {
-    let module = open(module_name).await?;
+    let module = open(module_name, profile).await?;
     module.require_variables()?;
     module.variable(key)?;
 
     if stored {
         return match tokio::fs::read_to_string(module.var_file(key)).await {
             Ok(value) => {
-                println!("{}", value.trim());
+                println!("{}", module.reveal(key, value)?.trim());
                 Ok(())
             },
             Err(e) if e.kind() == tokio::io::ErrorKind::NotFound => {
@@ -35,9 +36,9 @@ pub async fn get(
         };
     }
 
-    match module.manifest.variable(key, Some(module.profile)) {
+    match module.manifest.variable(key, Some(&module.profile)) {
         Ok(value) => {
-            println!("{}", value.trim());
+            println!("{}", module.reveal(key, value)?.trim());
             Ok(())
         },
         Err(e @ asimov_module::ReadVarError::UnconfiguredVar(_)) => {
@@ -0,0 +1,138 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    Source,
+    export::{CONFIG_VERSION, ConfigDocument, exported_context},
+    open, transfer_passphrase,
+};
+use crate::{BoxError, commands::module::read_document};
+use asimov_module::{ConfigurationVariable, ModuleName};
+use clientele::{StandardOptions, SysexitsError::*};
+use color_print::{ceprintln, cprintln};
+use std::{collections::BTreeMap, path::Path};
+
+/// Stores the configuration in a document written by `asimov module config
+/// export`. With `replace`, stored values the document doesn't hold are
+/// unset as well; secrets only if the document includes secrets. Nothing
+/// changes unless every value in the document is valid.
+pub async fn import(
+    path: &Path,
+    replace: bool,
+    profile: &str,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let document = read_document(
+        path,
+        "configuration document",
+        CONFIG_VERSION,
+        |document: &ConfigDocument| document.version,
+    )?;
+    let module_name = ModuleName::try_from(document.module.clone()).map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> `{}` names an invalid module: {e}",
+            path.display()
+        );
+        EX_DATAERR
+    })?;
+    let module = open(&module_name, profile).await?;
+
+    let mut wanted: BTreeMap<String, String> = document.config;
+    let mut invalid = false;
+    for key in wanted.keys() {
+        match module.variables().iter().find(|var| var.name == *key) {
+            Some(var) if var.secret => {
+                ceprintln!("<s,r>error:</> secret `{key}` is not encrypted in the document");
+                invalid = true;
+            },
+            Some(_) => (),
+            None => {
+                ceprintln!(
+                    "<s,r>error:</> `{key}` is not the name of a configuration variable for <s>{module_name}</> module"
+                );
+                invalid = true;
+            },
+        }
+    }
+
+    let include_secrets = document.secrets.is_some();
+    if let Some(secrets) = document.secrets {
+        let keyring = secrets
+            .key
+            .open(&transfer_passphrase(false)?)
+            .ok_or_else(|| {
+                ceprintln!("<s,r>error:</> wrong passphrase for the secrets in the document");
+                EX_NOPERM
+            })?;
+
+        for (key, value) in secrets.values {
+            if !module
+                .variables()
+                .iter()
+                .any(|var| var.name == key && var.secret)
+            {
+                ceprintln!(
+                    "<s,r>error:</> `{key}` is not the name of a secret configuration variable for <s>{module_name}</> module"
+                );
+                invalid = true;
+                continue;
+            }
+            let context = exported_context(module_name.as_str(), &key);
+            let Some(value) = keyring.try_decrypt(&context, &value) else {
+                ceprintln!("<s,r>error:</> secret `{key}` in the document can't be decrypted");
+                invalid = true;
+                continue;
+            };
+            wanted.insert(key, value);
+        }
+    }
+
+    for (key, value) in &wanted {
+        if let Err(expected) = module.check(key, value) {
+            ceprintln!("<s,r>error:</> invalid value for `{key}`: {expected}");
+            invalid = true;
+        }
+    }
+    if invalid {
+        return Err(EX_DATAERR.into());
+    }
+
+    let mut changes: Vec<(&ConfigurationVariable, Option<&str>)> = Vec::new();
+    for var in module.variables() {
+        let stored = match tokio::fs::read_to_string(module.var_file(&var.name)).await {
+            Ok(value) => Some(module.reveal(&var.name, value)?),
+            Err(_) => None,
+        };
+        let value = match wanted.get(&var.name) {
+            Some(value) => Some(value.as_str()),
+            None if replace && (include_secrets || !var.secret) => None,
+            None => continue,
+        };
+        if stored.as_deref() != value {
+            changes.push((var, value));
+        }
+    }
+
+    if !changes.is_empty() {
+        module.apply(&changes).await?;
+    }
+
+    // stored values still yield to the environment
+    for (var, value) in &changes {
+        if value.is_some() && module.source(var).await == Source::Environment {
+            ceprintln!(
+                "<s,y>warn:</> The imported value of `{}` is overridden by the environment variable <s>{}</>.",
+                var.name,
+                var.environment.as_deref().unwrap_or_default()
+            );
+        }
+    }
+
+    if flags.verbose > 0 {
+        cprintln!(
+            "<s,g>✓</> Changed {} configuration value(s) for module <s>{module_name}</>.",
+            changes.len()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::open;
+use crate::BoxError;
+use asimov_env::paths::asimov_root;
+use asimov_module::ModuleName;
+use clientele::StandardOptions;
+use color_print::{ceprintln, cprintln};
+
+/// Lists the profiles that hold stored configuration for a module, marking
+/// the one in effect.
+pub async fn profiles(
+    module_name: &ModuleName,
+    profile: &str,
+    _flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let module = open(module_name, profile).await?;
+
+    let mut profiles = Vec::new();
+    let configs_dir = asimov_root().join("configs");
+    match tokio::fs::read_dir(&configs_dir).await {
+        Ok(mut entries) => {
+            while let Some(entry) = entries.next_entry().await? {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if tokio::fs::metadata(entry.path().join(module.name.as_str()))
+                    .await
+                    .is_ok_and(|metadata| metadata.is_dir())
+                {
+                    profiles.push(name);
+                }
+            }
+        },
+        Err(e) if e.kind() == tokio::io::ErrorKind::NotFound => (),
+        Err(e) => {
+            tracing::error!("failed to read `{}`: {e}", configs_dir.display());
+            return Err(e.into());
+        },
+    }
+    profiles.sort();
+
+    if profiles.is_empty() {
+        ceprintln!(
+            "<s,dim>note:</> module <s>{}</> has no stored configuration in any profile",
+            module.name
+        );
+    }
+
+    for name in &profiles {
+        if *name == module.profile {
+            cprintln!("<s,g>*</> <s>{name}</>");
+        } else {
+            println!("  {name}");
+        }
+    }
+
+    if !profiles.contains(&module.profile) {
+        ceprintln!(
+            "<s,dim>hint:</> Store values in profile <s>{}</> with: <s>asimov module config set --profile {} {} KEY=VALUE</>",
+            module.profile,
+            module.profile,
+            module.name
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{
+    BoxError,
+    secrets::{NewKey, NewKeyring, is_encrypted, reveal},
+};
+use asimov_env::paths::asimov_root;
+use asimov_module::ModuleManifest;
+use clientele::{StandardOptions, SysexitsError::*};
+use color_print::{ceprintln, cprintln};
+use std::{collections::BTreeMap, io::IsTerminal, path::PathBuf};
+
+struct StoredSecret {
+    path: PathBuf,
+    context: String,
+    value: String,
+    encrypted: bool,
+}
+
+/// Re-encrypts the stored secrets of every module in every profile with a
+/// new key, and encrypts those still stored as plaintext. The previous key
+/// is only retired once every secret has been replaced, so that an
+/// interrupted rekey leaves them all readable.
+pub async fn rekey(
+    passphrase: bool,
+    key_file: Option<PathBuf>,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let manifests: BTreeMap<String, ModuleManifest> = registry
+        .installed_modules()
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to read installed modules: {e}");
+            EX_UNAVAILABLE
+        })?
+        .into_iter()
+        .map(|module| (module.manifest.name.clone(), module.manifest))
+        .collect();
+
+    let mut secrets = find_secrets(&manifests).await?;
+
+    // decrypt everything first, so that nothing changes unless all of it can
+    for secret in &mut secrets {
+        secret.value = reveal(&secret.context, std::mem::take(&mut secret.value))?;
+    }
+
+    let new_key = if passphrase {
+        NewKey::Passphrase(prompt_for_passphrase()?)
+    } else if let Some(path) = key_file {
+        NewKey::KeyFile(path)
+    } else {
+        NewKey::Generate
+    };
+    let new = NewKeyring::new(new_key)?;
+
+    let mut pending = Vec::with_capacity(secrets.len());
+    for secret in &secrets {
+        let file_name = secret
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let temp_path = secret.path.with_file_name(format!(".{file_name}.rekey"));
+        let sealed = new.keyring().encrypt(&secret.context, &secret.value);
+        tokio::fs::write(&temp_path, sealed)
+            .await
+            .inspect_err(|e| {
+                tracing::error!("failed to write `{}`: {e}", temp_path.display());
+            })?;
+        pending.push((temp_path, &secret.path));
+    }
+
+    new.commit()?;
+
+    for (temp_path, path) in pending {
+        tokio::fs::rename(&temp_path, path).await.inspect_err(|e| {
+            tracing::error!("failed to replace `{}`: {e}", path.display());
+            ceprintln!(
+                "<s,dim>hint:</> The previous key is kept for the secrets not yet replaced; finish with: <s>asimov module config rekey</>"
+            );
+        })?;
+    }
+
+    new.retire_previous()?;
+
+    if flags.verbose > 0 {
+        let migrated = secrets.iter().filter(|secret| !secret.encrypted).count();
+        cprintln!(
+            "<s,g>✓</> Encrypted {} stored secret(s) with the new key, {migrated} of them previously unencrypted.",
+            secrets.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Finds the stored secrets of every profile: values already encrypted, and
+/// plaintext values of variables that installed modules mark secret.
+async fn find_secrets(
+    manifests: &BTreeMap<String, ModuleManifest>,
+) -> Result<Vec<StoredSecret>, BoxError> {
+    let configs_dir = asimov_root().join("configs");
+    let mut secrets = Vec::new();
+
+    for profile_dir in read_dirs(configs_dir).await? {
+        for module_dir in read_dirs(profile_dir.clone()).await? {
+            let mut entries = tokio::fs::read_dir(&module_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') || !entry.file_type().await?.is_file() {
+                    continue;
+                }
+
+                let profile = profile_dir
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let module = module_dir.file_name().unwrap_or_default().to_string_lossy();
+                let value = tokio::fs::read_to_string(entry.path()).await?;
+                let encrypted = is_encrypted(&value);
+                let secret = manifests.get(module.as_ref()).is_some_and(|manifest| {
+                    manifest
+                        .config
+                        .iter()
+                        .flat_map(|config| config.variables.iter())
+                        .find(|var| var.name == name)
+                        .is_some_and(|var| var.secret)
+                });
+
+                if encrypted || secret {
+                    secrets.push(StoredSecret {
+                        path: entry.path(),
+                        context: format!("{profile}/{module}/{name}"),
+                        value,
+                        encrypted,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(secrets)
+}
+
+/// Lists the subdirectories of a directory, except hidden ones.
+async fn read_dirs(dir: PathBuf) -> Result<Vec<PathBuf>, BoxError> {
+    let mut entries = match tokio::fs::read_dir(&dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == tokio::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            tracing::error!("failed to read `{}`: {e}", dir.display());
+            return Err(e.into());
+        },
+    };
+
+    let mut dirs = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_name().to_string_lossy().starts_with('.')
+            && entry.file_type().await?.is_dir()
+        {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
+fn prompt_for_passphrase() -> Result<String, BoxError> {
+    if !std::io::stdin().is_terminal() {
+        ceprintln!("<s,r>error:</> choosing a passphrase requires a terminal");
+        ceprintln!(
+            "<s,dim>hint:</> Use a key file instead with: <s>asimov module config rekey --key-file <<path></>"
+        );
+        return Err(EX_UNAVAILABLE.into());
+    }
+
+    dialoguer::Password::new()
+        .with_prompt("New passphrase for stored secrets")
+        .with_confirmation("Repeat the passphrase", "The passphrases don't match.")
+        .interact()
+        .map_err(|e| {
+            tracing::error!("failed to read the passphrase from the terminal: {e}");
+            EX_IOERR.into()
+        })
+}
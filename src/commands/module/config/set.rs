@@ -13,9 +13,10 @@ pub async fn set(
     assignments: &[String],
     stdin: bool,
     from_json: bool,
+    profile: &str,
     _flags: &StandardOptions,
 ) -> Result<(), BoxError> {
-    let module = open(module_name).await?;
+    let module = open(module_name, profile).await?;
     module.require_variables()?;
 
     // collect and validate everything first so a typo doesn't apply half the batch
@@ -71,15 +72,23 @@ pub async fn set(
             .collect::<Result<_, BoxError>>()?
     };
 
-    for (key, _) in &pairs {
+    let mut invalid = false;
+    for (key, value) in &pairs {
         module.variable(key)?;
+        if let Err(expected) = module.check(key, value) {
+            ceprintln!("<s,r>error:</> invalid value for `{key}`: {expected}");
+            invalid = true;
+        }
+    }
+    if invalid {
+        return Err(EX_DATAERR.into());
     }
 
     module.create_conf_dir().await?;
     module.set_permissions().await?;
 
     for (key, value) in &pairs {
-        tokio::fs::write(module.var_file(key), value).await?;
+        module.store(module.variable(key)?, value).await?;
     }
 
     module.set_permissions().await?;
@@ -1,7 +1,7 @@
 // This is free and unencumbered software released into the public domain.
 
 use super::{MASK, open, prompt_for_value};
-use crate::BoxError;
+use crate::{BoxError, logs::Invocation, shared};
 use asimov_env::paths::asimov_root;
 use asimov_module::ModuleName;
 use clientele::{
@@ -13,8 +13,12 @@ use std::io::{IsTerminal, Write};
 
 /// Configures a module interactively: prompts for each declared variable, then
 /// hands over to the module's own configurator, if it provides one.
-pub async fn setup(module_name: &ModuleName, _flags: &StandardOptions) -> Result<(), BoxError> {
-    let module = open(module_name).await?;
+pub async fn setup(
+    module_name: &ModuleName,
+    profile: &str,
+    _flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let module = open(module_name, profile).await?;
     let variables = module.variables();
 
     let configurator_name = format!("asimov-{}-configurator", module.name);
@@ -75,23 +79,34 @@ pub async fn setup(module_name: &ModuleName, _flags: &StandardOptions) -> Result
                 }
             }
 
-            let value = prompt_for_value(
-                format!("Enter value for `{}` {info_text}", var.name),
-                var.secret,
-            )?;
-
-            let value = value.trim();
+            // ask again until the value conforms, or is left empty
+            let value = loop {
+                let value = prompt_for_value(
+                    format!("Enter value for `{}` {info_text}", var.name),
+                    var.secret,
+                )?;
+                let value = value.trim().to_string();
+                match module.check(&var.name, &value) {
+                    Err(expected) if !value.is_empty() => {
+                        ceprintln!(
+                            "<s,r>error:</> invalid value for `{}`: {expected}",
+                            var.name
+                        );
+                    },
+                    _ => break value,
+                }
+            };
             if value.is_empty() {
                 continue;
             }
 
-            tokio::fs::write(&var_file, value).await?;
+            module.store(var, &value).await?;
         }
 
         let mut stdout = std::io::stdout().lock();
         writeln!(&mut stdout, "Configuration:")?;
         for var in variables {
-            match module.manifest.variable(&var.name, Some(module.profile)) {
+            match module.manifest.variable(&var.name, Some(&module.profile)) {
                 Ok(_) if var.secret => writeln!(&mut stdout, "\t{}: {MASK}", var.name)?,
                 Ok(val) => writeln!(&mut stdout, "\t{}: {}", var.name, val)?,
                 Err(e @ asimov_module::ReadVarError::UnconfiguredVar(_)) => {
@@ -115,12 +130,22 @@ pub async fn setup(module_name: &ModuleName, _flags: &StandardOptions) -> Result
             return Err(EX_UNAVAILABLE.into());
         }
 
-        let status = std::process::Command::new(&conf_bin)
+        let mut command = std::process::Command::new(&conf_bin);
+        command
             .stdin(std::process::Stdio::inherit())
             .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .inspect_err(|e| tracing::error!("failed to execute configurator: {e}"))?;
+            .stderr(std::process::Stdio::inherit());
+        shared::configure_program(&mut command, &module.manifest, &module.profile)?;
+
+        // its stderr is the terminal that it prompts on, so isn't logged
+        let invocation = Invocation::start(&module.name, &command);
+        let status = command.status();
+        invocation.finish(
+            status.as_ref().map(|s| s.code()).map_err(|e| e.to_string()),
+            None,
+        );
+        let status =
+            status.inspect_err(|e| tracing::error!("failed to execute configurator: {e}"))?;
 
         if !status.success() {
             ceprintln!("<s,r>error:</> configurator `{configurator_name}` failed: {status}");
@@ -11,9 +11,10 @@ use color_print::{cformat, cprintln};
 pub async fn show(
     module_name: &ModuleName,
     output: &str,
+    profile: &str,
     _flags: &StandardOptions,
 ) -> Result<(), BoxError> {
-    let module = open(module_name).await?;
+    let module = open(module_name, profile).await?;
     let variables = module.variables();
 
     let mut rows = Vec::with_capacity(variables.len());
@@ -23,7 +24,7 @@ pub async fn show(
             Source::Unset => None,
             _ => module
                 .manifest
-                .variable(&var.name, Some(module.profile))
+                .variable(&var.name, Some(&module.profile))
                 .ok(),
         };
         rows.push((var, source, value));
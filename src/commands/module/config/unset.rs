@@ -11,9 +11,10 @@ pub async fn unset(
     module_name: &ModuleName,
     keys: &[String],
     all: bool,
+    profile: &str,
     _flags: &StandardOptions,
 ) -> Result<(), BoxError> {
-    let module = open(module_name).await?;
+    let module = open(module_name, profile).await?;
     let variables = module.require_variables()?;
 
     let keys: Vec<&str> = if all {
@@ -0,0 +1,233 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::BoxError;
+use asimov_module::ModuleName;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The file next to an installed module's manifest that records the
+/// constraints on its configuration variables.
+pub const CONSTRAINTS_FILE_NAME: &str = "constraints.json";
+
+/// The constraint fields that a manifest may declare on a configuration
+/// variable, besides those the SDK knows about.
+const CONSTRAINT_FIELDS: [&str; 6] = ["type", "choices", "enum", "pattern", "min", "max"];
+
+/// What a manifest declares that a configuration variable's values must be.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(super) struct Constraint {
+    #[serde(default, rename = "type")]
+    pub kind: Option<ValueType>,
+    #[serde(default, alias = "enum")]
+    pub choices: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum ValueType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Url,
+}
+
+impl Constraint {
+    /// Checks a value, describing what was expected if it doesn't conform.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let kind = match self.kind {
+            None if self.min.is_some() || self.max.is_some() => ValueType::Number,
+            kind => kind.unwrap_or(ValueType::String),
+        };
+
+        match kind {
+            ValueType::String => (),
+            ValueType::Integer => {
+                let number = value
+                    .parse::<i64>()
+                    .map_err(|_| format!("expected an integer{}", self.range()))?;
+                self.check_range(number as f64, "an integer")?;
+            },
+            ValueType::Number => {
+                let number = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| format!("expected a number{}", self.range()))?;
+                self.check_range(number, "a number")?;
+            },
+            ValueType::Boolean => {
+                if !matches!(value, "true" | "false") {
+                    return Err("expected `true` or `false`".into());
+                }
+            },
+            ValueType::Url => {
+                let url = url::Url::parse(value).map_err(|e| format!("expected a URL ({e})"))?;
+                if url.cannot_be_a_base() {
+                    return Err("expected a URL such as `https://example.org/`".into());
+                }
+            },
+        }
+
+        if !self.choices.is_empty() {
+            let choices: Vec<String> = self.choices.iter().map(choice_to_string).collect();
+            if !choices.iter().any(|choice| choice == value) {
+                return Err(format!("expected one of: {}", choices.join(", ")));
+            }
+        }
+
+        if let Some(pattern) = &self.pattern {
+            match regex::Regex::new(&format!("^(?:{pattern})$")) {
+                Ok(regex) if !regex.is_match(value) => {
+                    return Err(format!("expected a value matching `{pattern}`"));
+                },
+                Ok(_) => (),
+                Err(e) => tracing::warn!("ignoring the invalid pattern `{pattern}`: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_range(&self, number: f64, what: &str) -> Result<(), String> {
+        let below = self.min.is_some_and(|min| number < min);
+        let above = self.max.is_some_and(|max| number > max);
+        if below || above {
+            return Err(format!("expected {what}{}", self.range()));
+        }
+        Ok(())
+    }
+
+    fn range(&self) -> String {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => format!(" from {min} to {max}"),
+            (Some(min), None) => format!(" of at least {min}"),
+            (None, Some(max)) => format!(" of at most {max}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+fn choice_to_string(choice: &serde_json::Value) -> String {
+    match choice {
+        serde_json::Value::String(choice) => choice.clone(),
+        choice => choice.to_string(),
+    }
+}
+
+/// The constraint fields that a manifest declares on each of its
+/// configuration variables, by variable name.
+pub(super) type DeclaredConstraints = BTreeMap<String, serde_json::Map<String, serde_json::Value>>;
+
+/// Reads the constraints that an installed module's manifest declares on
+/// its configuration variables. Constraints that can't be understood are
+/// left out.
+pub(super) async fn read_constraints(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> BTreeMap<String, Constraint> {
+    let path = registry.module_dir(module_name).join(CONSTRAINTS_FILE_NAME);
+    let Ok(content) = tokio::fs::read(&path).await else {
+        return BTreeMap::new();
+    };
+    let Ok(declared) = serde_json::from_slice::<DeclaredConstraints>(&content) else {
+        tracing::warn!("ignoring the malformed `{}`", path.display());
+        return BTreeMap::new();
+    };
+
+    declared
+        .into_iter()
+        .filter_map(|(name, fields)| {
+            let constraint = serde_json::from_value(fields.into())
+                .inspect_err(|e| {
+                    tracing::warn!("ignoring the constraints of variable `{name}`: {e}")
+                })
+                .ok()?;
+            Some((name, constraint))
+        })
+        .collect()
+}
+
+/// Picks out the constraints declared in the text of a module's manifest,
+/// which the SDK's manifest leaves out.
+pub(super) fn declared_constraints(source: &str) -> Result<DeclaredConstraints, BoxError> {
+    let source: serde_json::Value = serde_yml::from_str(source)?;
+    Ok(variables(&source)
+        .filter_map(|var| {
+            let name = var.get("name")?.as_str()?;
+            let fields: serde_json::Map<_, _> = CONSTRAINT_FIELDS
+                .into_iter()
+                .filter_map(|field| Some((field.to_string(), var.get(field)?.clone())))
+                .collect();
+            (!fields.is_empty()).then(|| (name.to_string(), fields))
+        })
+        .collect())
+}
+
+fn variables(manifest: &serde_json::Value) -> impl Iterator<Item = &serde_json::Value> {
+    manifest
+        .pointer("/config/variables")
+        .and_then(|variables| variables.as_array())
+        .into_iter()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraint(json: serde_json::Value) -> Constraint {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn checks_types_and_ranges() {
+        let port = constraint(serde_json::json!({ "type": "integer", "min": 1, "max": 65535 }));
+        assert!(port.check("8080").is_ok());
+        assert_eq!(
+            port.check("80800").unwrap_err(),
+            "expected an integer from 1 to 65535"
+        );
+        assert!(port.check("http").is_err());
+
+        let url = constraint(serde_json::json!({ "type": "url" }));
+        assert!(url.check("https://example.org/api").is_ok());
+        assert!(url.check("example.org").is_err());
+    }
+
+    #[test]
+    fn checks_choices_and_patterns() {
+        let region = constraint(serde_json::json!({ "enum": ["eu", "us"] }));
+        assert!(region.check("eu").is_ok());
+        assert_eq!(region.check("EU").unwrap_err(), "expected one of: eu, us");
+
+        let key = constraint(serde_json::json!({ "pattern": "[a-f0-9]{4}" }));
+        assert!(key.check("beef").is_ok());
+        assert!(key.check("beef1").is_err(), "the pattern must match fully");
+    }
+
+    #[test]
+    fn picks_out_declared_constraints() {
+        let declared = declared_constraints(indoc::indoc! {"
+            name: demo
+            config:
+              variables:
+                - name: port
+                  type: integer
+                  min: 1
+                  description: The port to listen on
+                - name: host
+        "})
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&declared).unwrap(),
+            serde_json::json!({ "port": { "type": "integer", "min": 1 } })
+        );
+    }
+}
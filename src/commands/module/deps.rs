@@ -0,0 +1,282 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{ModuleSpec, VersionPin, resolve_version};
+use crate::{
+    BoxError,
+    SysexitsError::{self, *},
+    registry::http::download_client,
+};
+use asimov_module::{ModuleManifest, ModuleName};
+use color_print::ceprintln;
+use std::collections::{BTreeMap, VecDeque};
+
+/// One module to install, in the order that installs dependencies first.
+#[derive(Clone, Debug)]
+pub(super) struct PlannedModule {
+    pub name: ModuleName,
+    /// The version requested on the command line, if any.
+    pub pin: Option<VersionPin>,
+    /// The release to install, or `None` if the module is already installed.
+    pub version: Option<String>,
+    /// The module that pulled this one in, if it wasn't requested by name.
+    pub required_by: Option<ModuleName>,
+    /// The modules that this one requires.
+    pub requires: Vec<ModuleName>,
+}
+
+/// Resolves the modules that the requested ones require, transitively,
+/// and orders everything to be installed so that dependencies come first.
+///
+/// Modules that are already installed are not descended into, since their
+/// dependencies were resolved when they were installed. With `no_deps`,
+/// only the requested modules are planned.
+pub(super) async fn plan(
+    registry: &asimov_registry::Registry,
+    installer: &asimov_installer::Installer,
+    modules: Vec<ModuleSpec>,
+    default_version: Option<&str>,
+    no_deps: bool,
+) -> Result<Vec<PlannedModule>, BoxError> {
+    let roots: Vec<ModuleName> = modules.iter().map(|spec| spec.name.clone()).collect();
+
+    let mut nodes: BTreeMap<ModuleName, PlannedModule> = BTreeMap::new();
+    let mut edges: BTreeMap<ModuleName, Vec<ModuleName>> = BTreeMap::new();
+
+    let mut queue: VecDeque<(ModuleSpec, Option<ModuleName>)> =
+        modules.into_iter().map(|spec| (spec, None)).collect();
+
+    while let Some((spec, required_by)) = queue.pop_front() {
+        if nodes.contains_key(&spec.name) {
+            continue;
+        }
+        let ModuleSpec {
+            name: module_name,
+            version: pin,
+        } = spec;
+
+        let mut requires = Vec::new();
+        let version = if registry
+            .is_module_installed(&module_name)
+            .await
+            .unwrap_or(false)
+        {
+            None
+        } else {
+            // only the requested modules get the `--version` fallback
+            let default = required_by.is_none().then_some(default_version).flatten();
+            let version = resolve_version(installer, &module_name, pin.as_ref(), default).await?;
+
+            if no_deps {
+                nodes.insert(
+                    module_name.clone(),
+                    PlannedModule {
+                        name: module_name.clone(),
+                        pin,
+                        version: Some(version),
+                        required_by,
+                        requires: Vec::new(),
+                    },
+                );
+                edges.insert(module_name, Vec::new());
+                continue;
+            }
+
+            let manifest = fetch_module_manifest(&module_name, &version)
+                .await
+                .map_err(|e| {
+                    tracing::error!(
+                        "unable to fetch the manifest of module `{module_name}` version `{version}`: {e}"
+                    );
+                    EX_UNAVAILABLE
+                })?;
+
+            for dependency in manifest.requires.modules {
+                let dependency = ModuleName::try_from(dependency).map_err(|e| {
+                    ceprintln!(
+                        "<s,r>error:</> module <s>{module_name}</> requires an invalid module name: {e}"
+                    );
+                    EX_DATAERR
+                })?;
+                queue.push_back((
+                    ModuleSpec {
+                        name: dependency.clone(),
+                        version: None,
+                    },
+                    Some(module_name.clone()),
+                ));
+                requires.push(dependency);
+            }
+
+            Some(version)
+        };
+
+        edges.insert(module_name.clone(), requires.clone());
+        nodes.insert(
+            module_name.clone(),
+            PlannedModule {
+                name: module_name,
+                pin,
+                version,
+                required_by,
+                requires,
+            },
+        );
+    }
+
+    let order = install_order(&roots, &edges).map_err(report_cycle)?;
+
+    Ok(order
+        .into_iter()
+        .filter_map(|name| nodes.remove(&name))
+        .collect())
+}
+
+/// Explains a dependency cycle found by [`install_order`].
+pub(super) fn report_cycle(cycle: Vec<ModuleName>) -> SysexitsError {
+    ceprintln!(
+        "<s,r>error:</> modules require each other in a cycle: <s>{}</>",
+        cycle
+            .iter()
+            .map(ModuleName::as_str)
+            .collect::<Vec<_>>()
+            .join(" → ")
+    );
+    EX_DATAERR
+}
+
+/// Orders modules so that each comes after everything it requires, or
+/// returns the first cycle found, starting and ending with the same module.
+pub(super) fn install_order(
+    roots: &[ModuleName],
+    edges: &BTreeMap<ModuleName, Vec<ModuleName>>,
+) -> Result<Vec<ModuleName>, Vec<ModuleName>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        name: &ModuleName,
+        edges: &BTreeMap<ModuleName, Vec<ModuleName>>,
+        states: &mut BTreeMap<ModuleName, State>,
+        path: &mut Vec<ModuleName>,
+        order: &mut Vec<ModuleName>,
+    ) -> Result<(), Vec<ModuleName>> {
+        match states.get(name) {
+            Some(State::Done) => return Ok(()),
+            Some(State::Visiting) => {
+                let start = path.iter().position(|n| n == name).unwrap_or_default();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name.clone());
+                return Err(cycle);
+            },
+            None => (),
+        }
+
+        states.insert(name.clone(), State::Visiting);
+        path.push(name.clone());
+        for dependency in edges.get(name).into_iter().flatten() {
+            visit(dependency, edges, states, path, order)?;
+        }
+        path.pop();
+        states.insert(name.clone(), State::Done);
+        order.push(name.clone());
+
+        Ok(())
+    }
+
+    let mut states = BTreeMap::new();
+    let mut order = Vec::new();
+    for root in roots {
+        visit(root, edges, &mut states, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Fetches the manifest that a given release of a module was published with.
+pub async fn fetch_module_manifest(
+    module_name: &ModuleName,
+    version: &str,
+) -> Result<ModuleManifest, BoxError> {
+    let content = fetch_module_manifest_source(module_name, version).await?;
+
+    let manifest = serde_yml::from_str(&content)
+        .inspect_err(|err| tracing::debug!(?err, ?content))
+        .map_err(|e| format!("unable to deserialize module manifest: {e}"))?;
+
+    Ok(manifest)
+}
+
+/// Fetches the text of a module's manifest as published for a release.
+pub async fn fetch_module_manifest_source(
+    module_name: &ModuleName,
+    version: &str,
+) -> Result<String, BoxError> {
+    let url = format!(
+        "https://raw.githubusercontent.com/asimov-modules/asimov-{module_name}-module/{version}/.asimov/module.yaml"
+    );
+
+    let client = download_client();
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("request failed: {e}"))?;
+
+    if !response.status().is_success() {
+        Err(format!(
+            "HTTP status code was not successful: {0}",
+            response.status()
+        ))?;
+    }
+
+    let content = response
+        .text()
+        .await
+        .inspect_err(|err| tracing::debug!(?err))?;
+
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> ModuleName {
+        ModuleName::try_from(name.to_string()).unwrap()
+    }
+
+    fn graph(edges: &[(&str, &[&str])]) -> BTreeMap<ModuleName, Vec<ModuleName>> {
+        edges
+            .iter()
+            .map(|(from, to)| (name(from), to.iter().map(|to| name(to)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let edges = graph(&[
+            ("app", &["http", "json"]),
+            ("http", &["json"]),
+            ("json", &[]),
+        ]);
+        let order = install_order(&[name("app")], &edges).unwrap();
+        assert_eq!(order, [name("json"), name("http"), name("app")]);
+    }
+
+    #[test]
+    fn lists_shared_dependencies_once() {
+        let edges = graph(&[("a", &["common"]), ("b", &["common"]), ("common", &[])]);
+        let order = install_order(&[name("a"), name("b")], &edges).unwrap();
+        assert_eq!(order, [name("common"), name("a"), name("b")]);
+    }
+
+    #[test]
+    fn reports_a_cycle() {
+        let edges = graph(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"])]);
+        let cycle = install_order(&[name("a")], &edges).unwrap_err();
+        assert_eq!(cycle, [name("a"), name("b"), name("c"), name("a")]);
+    }
+}
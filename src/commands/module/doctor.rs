@@ -0,0 +1,387 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::variable_states;
+use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
+use asimov_module::{InstalledModuleManifest, ModuleName};
+use color_print::{ceprintln, cprintln};
+use serde::Serialize;
+use std::{collections::BTreeMap, path::Path, process::Stdio, time::Duration};
+
+/// How long a program may take to print its `--help`.
+const HELP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+struct ModuleReport {
+    name: String,
+    version: Option<String>,
+    enabled: bool,
+    config: Vec<VariableStatus>,
+    programs: Vec<ProgramStatus>,
+    problems: Vec<Problem>,
+}
+
+#[derive(Debug, Serialize)]
+struct VariableStatus {
+    name: String,
+    required: bool,
+    set: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ProgramStatus {
+    name: String,
+    present: bool,
+    executable: bool,
+    /// How `--help` went: `ok`, `failed`, `timeout`, or `skipped`.
+    help: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Problem {
+    severity: Severity,
+    message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Serialize)]
+struct Overlap {
+    kind: &'static str,
+    handle: String,
+    modules: Vec<String>,
+}
+
+/// Checks every installed module: its configuration, that its programs are
+/// present, executable, and answer `--help`, and that the enabled modules
+/// don't claim the same URLs.
+pub async fn doctor(output: String, _flags: &StandardOptions) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let profile = shared::profile(None)?;
+
+    let installed = registry.installed_modules().await.map_err(|e| {
+        tracing::error!("failed to read installed modules: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    let mut reports = Vec::with_capacity(installed.len());
+    let mut enabled = Vec::new();
+    for module in installed {
+        let module_name = ModuleName::try_from(module.manifest.name.clone())?;
+        let is_enabled = registry
+            .is_module_enabled(&module_name)
+            .await
+            .map_err(|e| {
+                tracing::error!("failed to check if module is enabled: {e}");
+                EX_UNAVAILABLE
+            })?;
+
+        reports.push(check_module(&registry, &module_name, &module, is_enabled, &profile).await);
+        if is_enabled {
+            enabled.push(module);
+        }
+    }
+
+    let overlaps = find_overlaps(&enabled);
+
+    let errors = reports
+        .iter()
+        .flat_map(|report| &report.problems)
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+    let warnings = reports
+        .iter()
+        .flat_map(|report| &report.problems)
+        .filter(|problem| problem.severity == Severity::Warning)
+        .count()
+        + overlaps.len();
+
+    match output.as_str() {
+        "json" => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "modules": reports,
+                    "overlaps": overlaps,
+                    "summary": {
+                        "modules": reports.len(),
+                        "errors": errors,
+                        "warnings": warnings,
+                    },
+                }))?
+            );
+        },
+        _ => {
+            for report in &reports {
+                let state = if report.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                if report
+                    .problems
+                    .iter()
+                    .any(|problem| problem.severity == Severity::Error)
+                {
+                    cprintln!("<s,r>✗</> <s>{}</> ({state})", report.name);
+                } else {
+                    cprintln!("<s,g>✓</> <s>{}</> ({state})", report.name);
+                }
+                for problem in &report.problems {
+                    match problem.severity {
+                        Severity::Error => cprintln!("    <s,r>error:</> {}", problem.message),
+                        Severity::Warning => cprintln!("    <s,y>warn:</> {}", problem.message),
+                    }
+                }
+            }
+
+            if !overlaps.is_empty() {
+                cprintln!("<s>Overlapping handles:</>");
+                for overlap in &overlaps {
+                    cprintln!(
+                        "    <s,y>warn:</> {} <s>{}</> is handled by {}",
+                        overlap.kind.replace('_', " "),
+                        overlap.handle,
+                        overlap.modules.join(", ")
+                    );
+                }
+            }
+
+            cprintln!(
+                "<s>Checked {} module(s):</> {errors} error(s), {warnings} warning(s).",
+                reports.len()
+            );
+        },
+    }
+
+    if errors > 0 {
+        ceprintln!(
+            "<s,dim>hint:</> Inspect a module for details with: <s>asimov module inspect <<name></>"
+        );
+        return Err(EX_CONFIG.into());
+    }
+
+    Ok(())
+}
+
+async fn check_module(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    module: &InstalledModuleManifest,
+    is_enabled: bool,
+    profile: &str,
+) -> ModuleReport {
+    let manifest = &module.manifest;
+    let mut problems = Vec::new();
+
+    let mut config = Vec::new();
+    for state in variable_states(manifest, profile) {
+        let (var, set) = (state.var, state.set);
+        if let Some(e) = &state.error {
+            problems.push(Problem {
+                severity: Severity::Error,
+                message: format!("unable to read variable `{}`: {e}", var.name),
+            });
+        }
+
+        if set
+            && var.secret
+            && let Ok(value) = tokio::fs::read_to_string(
+                asimov_env::paths::asimov_root()
+                    .join("configs")
+                    .join(profile)
+                    .join(module_name.as_str())
+                    .join(&var.name),
+            )
+            .await
+            && !crate::secrets::is_encrypted(&value)
+        {
+            problems.push(Problem {
+                severity: Severity::Warning,
+                message: format!(
+                    "secret `{}` is stored unencrypted; encrypt it with `asimov module config rekey`",
+                    var.name
+                ),
+            });
+        }
+
+        if state.is_missing() && is_enabled {
+            problems.push(Problem {
+                severity: Severity::Error,
+                message: format!(
+                    "enabled, but the required variable `{}` is not set",
+                    var.name
+                ),
+            });
+        }
+
+        config.push(VariableStatus {
+            name: var.name.clone(),
+            required: var.is_required(),
+            set,
+        });
+    }
+
+    let bin_dir = registry
+        .module_dir(module_name)
+        .join(asimov_registry::BIN_DIR_NAME);
+    let mut programs = Vec::new();
+    for program in &manifest.provides.programs {
+        let status = check_program(&bin_dir.join(program), program).await;
+        match status.help {
+            _ if !status.present => problems.push(Problem {
+                severity: Severity::Error,
+                message: format!("program `{program}` is missing"),
+            }),
+            _ if !status.executable => problems.push(Problem {
+                severity: Severity::Error,
+                message: format!("program `{program}` is not executable"),
+            }),
+            "timeout" => problems.push(Problem {
+                severity: Severity::Error,
+                message: format!(
+                    "program `{program}` did not answer `--help` within {} seconds",
+                    HELP_TIMEOUT.as_secs()
+                ),
+            }),
+            "failed" => problems.push(Problem {
+                severity: Severity::Warning,
+                message: format!("program `{program}` failed to run with `--help`"),
+            }),
+            _ => (),
+        }
+        programs.push(status);
+    }
+
+    ModuleReport {
+        name: module_name.to_string(),
+        version: module.version.clone(),
+        enabled: is_enabled,
+        config,
+        programs,
+        problems,
+    }
+}
+
+async fn check_program(path: &Path, program: &str) -> ProgramStatus {
+    let metadata = tokio::fs::metadata(path).await.ok();
+    let present = metadata.as_ref().is_some_and(|m| m.is_file());
+
+    #[cfg(unix)]
+    let executable = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata
+            .as_ref()
+            .is_some_and(|m| m.permissions().mode() & 0o111 != 0)
+    };
+    #[cfg(not(unix))]
+    let executable = present;
+
+    let help = if !present || !executable {
+        "skipped"
+    } else {
+        let child = tokio::process::Command::new(path)
+            .arg("--help")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+
+        match child {
+            Err(_) => "failed",
+            Ok(mut child) => match tokio::time::timeout(HELP_TIMEOUT, child.wait()).await {
+                Ok(Ok(status)) if status.success() => "ok",
+                Ok(_) => "failed",
+                Err(_) => "timeout",
+            },
+        }
+    };
+
+    ProgramStatus {
+        name: program.into(),
+        present,
+        executable,
+        help,
+    }
+}
+
+/// Finds URL prefixes that several enabled modules claim, including one
+/// prefix contained in another, and identical URL patterns.
+fn find_overlaps(modules: &[InstalledModuleManifest]) -> Vec<Overlap> {
+    let mut prefixes: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut patterns: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for module in modules {
+        let name = module.manifest.name.as_str();
+        for prefix in &module.manifest.handles.url_prefixes {
+            prefixes.entry(prefix).or_default().push(name);
+        }
+        for pattern in &module.manifest.handles.url_patterns {
+            patterns.entry(pattern).or_default().push(name);
+        }
+    }
+
+    let mut overlaps = Vec::new();
+    for (prefix, owners) in &prefixes {
+        let mut claimants: Vec<String> = owners.iter().map(|s| s.to_string()).collect();
+        for (other, other_owners) in &prefixes {
+            if other != prefix && prefix.starts_with(other) {
+                claimants.extend(other_owners.iter().map(|s| s.to_string()));
+            }
+        }
+        claimants.sort();
+        claimants.dedup();
+        if claimants.len() > 1 {
+            overlaps.push(Overlap {
+                kind: "url_prefix",
+                handle: prefix.to_string(),
+                modules: claimants,
+            });
+        }
+    }
+
+    for (pattern, owners) in patterns {
+        let mut claimants: Vec<String> = owners.iter().map(|s| s.to_string()).collect();
+        claimants.sort();
+        claimants.dedup();
+        if claimants.len() > 1 {
+            overlaps.push(Overlap {
+                kind: "url_pattern",
+                handle: pattern.to_string(),
+                modules: claimants,
+            });
+        }
+    }
+
+    overlaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, prefixes: &[&str]) -> InstalledModuleManifest {
+        let mut module = InstalledModuleManifest::default();
+        module.manifest.name = name.into();
+        module.manifest.handles.url_prefixes = prefixes.iter().map(|p| p.to_string()).collect();
+        module
+    }
+
+    #[test]
+    fn finds_nested_and_shared_prefixes() {
+        let modules = [
+            module("web", &["https://"]),
+            module("github", &["https://github.com/"]),
+            module("local", &["file:///"]),
+        ];
+
+        let overlaps = find_overlaps(&modules);
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].handle, "https://github.com/");
+        assert_eq!(overlaps[0].modules, ["github", "web"]);
+    }
+}
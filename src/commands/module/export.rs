@@ -0,0 +1,95 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::config::open;
+use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
+use asimov_module::ModuleName;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The format version of environment documents written by this version of
+/// the CLI.
+pub(super) const ENVIRONMENT_VERSION: u32 = 1;
+
+/// The installed modules, their state, and their configuration, as written
+/// by `asimov module export` and applied by `asimov module import`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Environment {
+    pub version: u32,
+    #[serde(default)]
+    pub modules: Vec<EnvironmentModule>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EnvironmentModule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub enabled: bool,
+    /// The stored values of non-secret configuration variables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub config: BTreeMap<String, String>,
+    /// The secret configuration variables that were set, without values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+}
+
+/// Prints the installed modules, their versions, whether they're enabled,
+/// and their stored non-secret configuration in the active profile as one
+/// YAML or JSON document.
+pub async fn export(output: String, _flags: &StandardOptions) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let profile = shared::profile(None)?;
+
+    let installed = registry.installed_modules().await.map_err(|e| {
+        tracing::error!("failed to read installed modules: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    let mut modules = Vec::with_capacity(installed.len());
+    for installed in installed {
+        let module_name = ModuleName::try_from(installed.manifest.name)?;
+        let enabled = registry
+            .is_module_enabled(&module_name)
+            .await
+            .map_err(|e| {
+                tracing::error!("failed to check if module is enabled: {e}");
+                EX_UNAVAILABLE
+            })?;
+
+        let module = open(&module_name, &profile).await?;
+        let mut config = BTreeMap::new();
+        let mut secrets = Vec::new();
+        for var in module.variables() {
+            // stored values are exported even where the environment overrides them
+            let Some(value) = module.stored(&var.name).await? else {
+                continue;
+            };
+            if var.secret {
+                secrets.push(var.name.clone());
+                continue;
+            }
+            config.insert(var.name.clone(), value);
+        }
+
+        modules.push(EnvironmentModule {
+            name: module_name.to_string(),
+            version: installed.version,
+            enabled,
+            config,
+            secrets,
+        });
+    }
+
+    let environment = Environment {
+        version: ENVIRONMENT_VERSION,
+        modules,
+    };
+
+    match output.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&environment)?),
+        _ => print!("{}", serde_yml::to_string(&environment)?),
+    }
+
+    Ok(())
+}
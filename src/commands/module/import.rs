@@ -0,0 +1,261 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    ENVIRONMENT_VERSION, Environment, EnvironmentModule, ModuleSpec, VersionPin,
+    config::{Module, Source, open, prompt_for_value},
+    enable_if_configured, install_plan, plan, summarize,
+};
+use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
+use asimov_module::ModuleName;
+use color_print::{ceprintln, cprintln};
+use std::{
+    io::{IsTerminal, Read},
+    path::Path,
+};
+
+/// Applies an environment written by `asimov module export`: installs the
+/// missing modules, stores the configuration values that differ in the
+/// active profile, prompts for missing secrets, and enables or disables each
+/// module to match. Applying the same environment again changes nothing.
+pub async fn import(path: &Path, jobs: usize, flags: &StandardOptions) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let installer = asimov_installer::Installer::default();
+
+    let profile = shared::profile(None)?;
+    let environment = read_document(
+        path,
+        "environment",
+        ENVIRONMENT_VERSION,
+        |environment: &Environment| environment.version,
+    )?;
+
+    let mut modules = Vec::with_capacity(environment.modules.len());
+    for module in environment.modules {
+        let module_name = ModuleName::try_from(module.name.clone()).map_err(|e| {
+            ceprintln!("<s,r>error:</> invalid module name `{}`: {e}", module.name);
+            EX_DATAERR
+        })?;
+        modules.push((module_name, module));
+    }
+
+    let specs = modules
+        .iter()
+        .map(|(module_name, module)| ModuleSpec {
+            name: module_name.clone(),
+            version: module.version.clone().map(VersionPin::Exact),
+        })
+        .collect();
+    let plan = plan(&registry, &installer, specs, None, false).await?;
+    let outcomes = install_plan(&plan, None, jobs, flags).await;
+
+    for (module_name, module) in &modules {
+        let failed = outcomes
+            .iter()
+            .any(|outcome| outcome.name == *module_name && outcome.result.is_err());
+        if failed {
+            continue;
+        }
+
+        let installed_version = registry.module_version(module_name).await.ok().flatten();
+        if let (Some(wanted), Some(installed)) = (&module.version, &installed_version)
+            && !VersionPin::Exact(wanted.clone()).matches(installed)
+        {
+            ceprintln!(
+                "<s,y>warn:</> Module <s>{module_name}</> is installed with version <s>{installed}</>, not <s>{wanted}</>."
+            );
+            ceprintln!(
+                "<s,dim>hint:</> Change the installed version with: <s>asimov module upgrade {module_name}@{wanted}</>"
+            );
+        }
+
+        apply_module(&registry, module_name, module, &profile, flags).await?;
+    }
+
+    summarize(&outcomes, flags)
+}
+
+/// Reads a document written by one of the `export` commands, as YAML or
+/// JSON, from a file or from stdin if `-`. The kind names the document in
+/// errors; documents of a later format version than `max_version` are
+/// rejected.
+pub(super) fn read_document<T: serde::de::DeserializeOwned + 'static>(
+    path: &Path,
+    kind: &str,
+    max_version: u32,
+    version: impl Fn(&T) -> u32,
+) -> Result<T, BoxError> {
+    let mut content = String::new();
+    let result = if path.as_os_str() == "-" {
+        std::io::stdin().lock().read_to_string(&mut content)
+    } else {
+        std::fs::File::open(path).and_then(|mut file| file.read_to_string(&mut content))
+    };
+    result.map_err(|e| {
+        tracing::error!("failed to read `{}`: {e}", path.display());
+        EX_NOINPUT
+    })?;
+
+    // YAML is a superset of JSON, so this reads either
+    let document: T = serde_yml::from_str(&content).map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> `{}` is not a valid {kind}: {e}",
+            path.display()
+        );
+        EX_DATAERR
+    })?;
+
+    if version(&document) > max_version {
+        ceprintln!(
+            "<s,r>error:</> `{}` has format version {}, but this version of asimov reads up to version {max_version}",
+            path.display(),
+            version(&document)
+        );
+        ceprintln!("<s,dim>hint:</> Upgrade asimov to import it.");
+        return Err(EX_DATAERR.into());
+    }
+
+    Ok(document)
+}
+
+/// Brings one installed module's configuration and state in line with the
+/// environment.
+async fn apply_module(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    wanted: &EnvironmentModule,
+    profile: &str,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let module = open(module_name, profile).await?;
+
+    // nothing of a module changes unless all of its values are valid
+    let mut invalid = false;
+    for (key, value) in &wanted.config {
+        if !module.variables().iter().any(|var| var.name == *key) {
+            ceprintln!(
+                "<s,r>error:</> `{key}` is not the name of a configuration variable for <s>{module_name}</> module"
+            );
+            invalid = true;
+        } else if let Err(expected) = module.check(key, value) {
+            ceprintln!(
+                "<s,r>error:</> invalid value for `{key}` of module <s>{module_name}</>: {expected}"
+            );
+            invalid = true;
+        }
+    }
+    if invalid {
+        return Err(EX_DATAERR.into());
+    }
+
+    let mut values = Vec::new();
+    for (key, value) in &wanted.config {
+        let stored = match module.stored(key).await? {
+            Some(stored) => Some(module.reveal(key, stored)?),
+            None => None,
+        };
+        if stored.as_deref() != Some(value) {
+            values.push((key.clone(), value.clone()));
+        }
+    }
+
+    values.extend(prompt_for_secrets(&module, &wanted.secrets).await?);
+
+    if !values.is_empty() {
+        let mut changes = Vec::with_capacity(values.len());
+        for (key, value) in &values {
+            changes.push((module.variable(key)?, Some(value.as_str())));
+        }
+        module.apply(&changes).await?;
+
+        if flags.verbose > 0 {
+            cprintln!(
+                "<s,g>✓</> Stored {} configuration value(s) for module <s>{module_name}</>.",
+                values.len()
+            );
+        }
+    }
+
+    let is_enabled = registry
+        .is_module_enabled(module_name)
+        .await
+        .unwrap_or(false);
+    match (wanted.enabled, is_enabled) {
+        (true, false) => enable_if_configured(registry, module_name).await?,
+        (false, true) => {
+            registry.disable_module(module_name).await.map_err(|e| {
+                tracing::error!("failed to disable module `{module_name}`: {e}");
+                EX_UNAVAILABLE
+            })?;
+            if flags.verbose > 0 {
+                cprintln!("<s,g>✓</> Disabled module <s>{module_name}</>.");
+            }
+        },
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Prompts for the secrets that are unset but required, or that were set
+/// where the environment was exported. Without a terminal to prompt on, it
+/// explains how to set them instead.
+async fn prompt_for_secrets(
+    module: &Module,
+    exported: &[String],
+) -> Result<Vec<(String, String)>, BoxError> {
+    let mut missing = Vec::new();
+    for var in module.variables() {
+        if var.secret
+            && (var.is_required() || exported.contains(&var.name))
+            && module.source(var).await == Source::Unset
+        {
+            missing.push(var);
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        for var in missing {
+            ceprintln!(
+                "<s,y>warn:</> Secret `{}` of module <s>{}</> is not set.",
+                var.name,
+                module.name
+            );
+            ceprintln!(
+                "<s,dim>hint:</> Set it with: <s>asimov module config set --profile {} {} --stdin {}</>",
+                module.profile,
+                module.name,
+                var.name
+            );
+        }
+        return Ok(Vec::new());
+    }
+
+    let mut values = Vec::new();
+    for var in missing {
+        let value = prompt_for_value(
+            format!(
+                "Enter value for `{}` of module `{}` (secret, input is hidden)",
+                var.name, module.name
+            ),
+            true,
+        )?;
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        if let Err(expected) = module.check(&var.name, value) {
+            ceprintln!(
+                "<s,y>warn:</> Skipping secret `{}` of module <s>{}</>, whose value is invalid: {expected}.",
+                var.name,
+                module.name
+            );
+        } else {
+            values.push((var.name.clone(), value.to_string()));
+        }
+    }
+    Ok(values)
+}
@@ -0,0 +1,204 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, SysexitsError::*, registry::http::download_client};
+use asimov_env::paths::asimov_root;
+use asimov_module::{INDEX_URL, Index};
+use color_print::ceprintln;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How long a cached index is used before checking for a newer one.
+const INDEX_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Whether the module index may come from the local cache, or the network.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) enum IndexSource {
+    /// The cached index while it is fresh, else revalidated or downloaded,
+    /// falling back to the cached index when the network is unavailable.
+    #[default]
+    Cached,
+    /// Only the cached index, however old.
+    Offline,
+    /// A newly downloaded index.
+    Refresh,
+}
+
+impl IndexSource {
+    pub fn new(offline: bool, refresh: bool) -> Self {
+        match (offline, refresh) {
+            (true, _) => Self::Offline,
+            (_, true) => Self::Refresh,
+            _ => Self::Cached,
+        }
+    }
+}
+
+/// What is recorded about the cached index, to revalidate it.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct IndexMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+    /// When the index was last downloaded or revalidated, in seconds since
+    /// the Unix epoch.
+    checked_at: u64,
+}
+
+fn cache_dir() -> PathBuf {
+    asimov_root().join("cache").join("modules")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Reads the index of available modules, from the local cache where allowed
+/// and otherwise from the network, caching it.
+pub(super) async fn module_index(source: IndexSource) -> Result<Index, BoxError> {
+    let index_path = cache_dir().join("index.jsonl");
+    let metadata_path = cache_dir().join("index.json");
+
+    let cached = match tokio::fs::read_to_string(&index_path).await {
+        Ok(content) => {
+            let metadata: IndexMetadata = tokio::fs::read(&metadata_path)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_slice(&content).ok())
+                .unwrap_or_default();
+            Some((content, metadata))
+        },
+        Err(_) => None,
+    };
+
+    let is_fresh =
+        |metadata: &IndexMetadata| now().saturating_sub(metadata.checked_at) < INDEX_TTL.as_secs();
+
+    match (source, cached) {
+        (IndexSource::Offline, None) => {
+            ceprintln!("<s,r>error:</> no module index is cached for offline use");
+            ceprintln!("<s,dim>hint:</> Download it by searching without <s>--offline</> once.");
+            Err(EX_UNAVAILABLE.into())
+        },
+        (IndexSource::Offline, Some((content, _))) => parse(&content),
+        (IndexSource::Cached, Some((content, metadata))) if is_fresh(&metadata) => parse(&content),
+        (source, cached) => {
+            let conditional = cached
+                .as_ref()
+                .filter(|_| source != IndexSource::Refresh)
+                .map(|(_, metadata)| metadata);
+
+            match download(conditional).await {
+                Ok(Downloaded::Unchanged(metadata)) => {
+                    let (content, _) = cached.expect("only a cached index is revalidated");
+                    write_cache(None, &metadata).await;
+                    parse(&content)
+                },
+                Ok(Downloaded::Changed(content, metadata)) => {
+                    let index = parse(&content)?;
+                    write_cache(Some(&content), &metadata).await;
+                    Ok(index)
+                },
+                Err(e) => {
+                    let Some((content, metadata)) = cached else {
+                        tracing::error!("failed to fetch the module index: {e}");
+                        return Err(EX_UNAVAILABLE.into());
+                    };
+                    let hours = now().saturating_sub(metadata.checked_at) / 3600;
+                    ceprintln!(
+                        "<s,y>warn:</> Using the cached module index, last checked {hours} hour(s) ago, since a newer one couldn't be fetched: {e}"
+                    );
+                    parse(&content)
+                },
+            }
+        },
+    }
+}
+
+enum Downloaded {
+    Unchanged(IndexMetadata),
+    Changed(String, IndexMetadata),
+}
+
+/// Downloads the index, unless it is unchanged since it was cached with the
+/// given metadata.
+async fn download(cached: Option<&IndexMetadata>) -> Result<Downloaded, BoxError> {
+    let client = download_client();
+
+    let mut request = client.get(INDEX_URL);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+    let metadata = IndexMetadata {
+        etag: header(reqwest::header::ETAG),
+        last_modified: header(reqwest::header::LAST_MODIFIED),
+        checked_at: now(),
+    };
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        return Ok(Downloaded::Unchanged(IndexMetadata {
+            etag: metadata.etag.or_else(|| cached.etag.clone()),
+            last_modified: metadata
+                .last_modified
+                .or_else(|| cached.last_modified.clone()),
+            ..metadata
+        }));
+    }
+    if !response.status().is_success() {
+        return Err(format!("HTTP status code was not successful: {}", response.status()).into());
+    }
+
+    Ok(Downloaded::Changed(response.text().await?, metadata))
+}
+
+fn parse(content: &str) -> Result<Index, BoxError> {
+    content.parse().map_err(|e| {
+        tracing::error!("failed to read the module index: {e}");
+        EX_DATAERR.into()
+    })
+}
+
+/// Replaces the cached index, or only its metadata. Failing to cache is not
+/// an error, since the index was read regardless.
+async fn write_cache(content: Option<&str>, metadata: &IndexMetadata) {
+    let result: Result<(), BoxError> = async {
+        let cache_dir = cache_dir();
+        tokio::fs::create_dir_all(&cache_dir).await?;
+        if let Some(content) = content {
+            let temp_path = cache_dir.join("index.jsonl.tmp");
+            tokio::fs::write(&temp_path, content).await?;
+            tokio::fs::rename(&temp_path, cache_dir.join("index.jsonl")).await?;
+        }
+        let temp_path = cache_dir.join("index.json.tmp");
+        tokio::fs::write(&temp_path, serde_json::to_vec_pretty(metadata)?).await?;
+        tokio::fs::rename(&temp_path, cache_dir.join("index.json")).await?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!("failed to cache the module index: {e}");
+    }
+}
@@ -0,0 +1,159 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    IndexSource, fetch_module_manifest, fetch_releases, module_index, print_capabilities,
+    print_configuration, print_description, variable_json,
+};
+use crate::{BoxError, StandardOptions, SysexitsError::*, registry::http::download_client};
+use asimov_module::{ModuleManifest, ModuleName};
+use color_print::{ceprintln, cprintln};
+
+/// Shows what a module provides and needs before it is installed, from the
+/// module index or else from its latest release, along with its releases
+/// and README unless `offline`.
+pub async fn info(
+    module_name: ModuleName,
+    output: String,
+    offline: bool,
+    _flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let index = module_index(IndexSource::new(offline, false)).await?;
+    let indexed = index
+        .modules()
+        .iter()
+        .find(|module| module.name == module_name.as_str())
+        .cloned();
+
+    let versions = if offline {
+        None
+    } else {
+        fetch_releases(&module_name)
+            .await
+            .inspect_err(|e| tracing::warn!("unable to list releases for `{module_name}`: {e}"))
+            .ok()
+    };
+    // the release that `install` would install, which skips prereleases
+    let latest = if offline {
+        None
+    } else {
+        asimov_installer::Installer::default()
+            .fetch_latest_release(&module_name)
+            .await
+            .inspect_err(|e| {
+                tracing::warn!("unable to find latest release for `{module_name}`: {e}")
+            })
+            .ok()
+    };
+
+    let manifest: ModuleManifest = match (indexed, &latest) {
+        (Some(manifest), _) => manifest,
+        (None, Some(latest)) => fetch_module_manifest(&module_name, latest)
+            .await
+            .map_err(|e| {
+                tracing::error!("failed to fetch manifest for module `{module_name}`: {e}");
+                EX_UNAVAILABLE
+            })?,
+        (None, None) => {
+            ceprintln!("<s,r>error:</> no module named <s>{module_name}</> is available");
+            ceprintln!("<s,dim>hint:</> Search for modules with: <s>asimov module search</>");
+            return Err(EX_UNAVAILABLE.into());
+        },
+    };
+
+    let readme = if offline {
+        None
+    } else {
+        fetch_readme(&module_name, latest.as_deref().unwrap_or("HEAD")).await
+    };
+
+    let installed = asimov_registry::Registry::default()
+        .read_manifest(&module_name)
+        .await
+        .ok()
+        .map(|installed| installed.version.unwrap_or_default());
+
+    let conf_vars = manifest
+        .config
+        .as_ref()
+        .map(|c| c.variables.as_slice())
+        .unwrap_or_default();
+
+    match output.as_str() {
+        "json" => {
+            let config: Vec<serde_json::Value> = conf_vars
+                .iter()
+                .map(|var| variable_json(var, None))
+                .collect();
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "manifest": serde_json::to_value(&manifest)?,
+                    "installed": installed,
+                    "latest": latest,
+                    "versions": versions,
+                    "config": config,
+                    "readme": readme,
+                }))?
+            );
+        },
+        _ => {
+            match &installed {
+                Some(version) if !version.is_empty() => {
+                    cprintln!("<s>{}</> (installed: {version})", manifest.name)
+                },
+                Some(_) => cprintln!("<s>{}</> (installed)", manifest.name),
+                None => cprintln!("<s>{}</> (not installed)", manifest.name),
+            }
+
+            print_description(&manifest);
+            if let Some(latest) = &latest {
+                cprintln!("<s>Latest version:</> {latest}");
+            }
+            if let Some(versions) = versions.as_ref().filter(|v| !v.is_empty()) {
+                cprintln!("<s>Versions:</> {}", versions.join(", "));
+            }
+
+            print_capabilities(&manifest);
+
+            let vars: Vec<_> = conf_vars.iter().map(|var| (var, None)).collect();
+            print_configuration(&vars);
+
+            if let Some(readme) = &readme {
+                println!();
+                cprintln!("<s>README:</>");
+                println!("{}", readme.trim_end());
+            } else if offline {
+                ceprintln!(
+                    "<s,dim>note:</> Versions and the README aren't shown with <s>--offline</>."
+                );
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Fetches a module's README as of a release, if it has one.
+pub(super) async fn fetch_readme(module_name: &ModuleName, version: &str) -> Option<String> {
+    let url = format!(
+        "https://raw.githubusercontent.com/asimov-modules/asimov-{module_name}-module/{version}/README.md"
+    );
+
+    let client = download_client();
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .inspect_err(|e| tracing::debug!("failed to fetch README for `{module_name}`: {e}"))
+        .ok()?;
+    if !response.status().is_success() {
+        tracing::debug!(
+            "no README for `{module_name}` at {version}: {}",
+            response.status()
+        );
+        return None;
+    }
+    response.text().await.ok()
+}
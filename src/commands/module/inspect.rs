@@ -1,7 +1,8 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions, SysexitsError::*};
-use asimov_module::ModuleName;
+use super::{print_capabilities, print_configuration, print_description, variable_json};
+use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
+use asimov_module::{ConfigurationVariable, ModuleManifest, ModuleName, ReadVarError};
 use color_print::{ceprintln, cprintln};
 
 pub async fn inspect(
@@ -30,40 +31,23 @@ pub async fn inspect(
         })?;
 
     let manifest = &installed.manifest;
-
-    let conf_vars = manifest
-        .config
-        .as_ref()
-        .map(|c| c.variables.as_slice())
-        .unwrap_or_default();
-
-    let conf_status: Vec<bool> = conf_vars
-        .iter()
-        .map(|var| match manifest.variable(&var.name, Some("default")) {
-            Ok(_) => true,
-            Err(asimov_module::ReadVarError::UnconfiguredVar(_)) => false,
-            Err(e) => {
-                tracing::warn!("failed to read configuration variable `{}`: {e}", var.name);
-                false
-            },
-        })
-        .collect();
+    let profile = shared::profile(None)?;
+
+    let conf_vars = variable_states(manifest, &profile);
+    for state in &conf_vars {
+        if let Some(e) = &state.error {
+            tracing::warn!(
+                "failed to read configuration variable `{}`: {e}",
+                state.var.name
+            );
+        }
+    }
 
     match output.as_str() {
         "json" => {
             let config: Vec<serde_json::Value> = conf_vars
                 .iter()
-                .zip(&conf_status)
-                .map(|(var, is_set)| {
-                    serde_json::json!({
-                        "name": var.name,
-                        "description": var.description,
-                        "default": var.default_value.as_deref().filter(|_| !var.secret),
-                        "secret": var.secret,
-                        "required": var.is_required(),
-                        "set": is_set,
-                    })
-                })
+                .map(|state| variable_json(state.var, Some(state.set)))
                 .collect();
 
             println!(
@@ -82,87 +66,24 @@ pub async fn inspect(
                 cprintln!("<s,r>✗</> <s>{}</> (disabled)", manifest.name);
             }
 
-            if let Some(label) = &manifest.label {
-                cprintln!("<s>Label:</> {label}");
-            }
-            if let Some(title) = &manifest.title {
-                cprintln!("<s>Title:</> {title}");
-            }
-            if let Some(summary) = &manifest.summary {
-                cprintln!("<s>Summary:</> {summary}");
-            }
+            print_description(manifest);
             if let Some(version) = &installed.version {
                 cprintln!("<s>Version:</> {version}");
             }
 
-            if !manifest.links.is_empty() {
-                let mut links = manifest.links.clone();
-                crate::sort_links(&manifest.name, &mut links);
-                cprintln!("<s>Links:</>");
-                for link in links {
-                    println!("  {link}");
-                }
-            }
-
-            if !manifest.provides.is_empty() {
-                cprintln!("<s>Programs:</>");
-                for program in &manifest.provides.programs {
-                    println!("  {program}");
-                }
-            }
-
-            if !manifest.handles.is_empty() {
-                cprintln!("<s>Handles:</>");
-                for (kind, values) in [
-                    ("URL protocols", &manifest.handles.url_protocols),
-                    ("URL prefixes", &manifest.handles.url_prefixes),
-                    ("URL patterns", &manifest.handles.url_patterns),
-                    ("file extensions", &manifest.handles.file_extensions),
-                    ("content types", &manifest.handles.content_types),
-                ] {
-                    if !values.is_empty() {
-                        println!("  {kind}: {}", values.join(", "));
-                    }
-                }
-            }
+            print_capabilities(manifest);
 
-            cprintln!("<s>Configuration:</>");
-            if conf_vars.is_empty() {
-                println!("  no configuration variables declared");
-            } else {
-                for (var, is_set) in conf_vars.iter().zip(&conf_status) {
-                    if *is_set {
-                        cprintln!("  <s,g>✓</> <s>{}</> (set)", var.name);
-                    } else if var.is_required() {
-                        cprintln!("  <s,r>✗</> <s>{}</> (required)", var.name);
-                    } else {
-                        cprintln!("  <dim>-</> <s>{}</> (unset)", var.name);
-                    }
-                    if let Some(description) = &var.description {
-                        println!("      {description}");
-                    }
-                    if let Some(default_value) = &var.default_value {
-                        if var.secret {
-                            println!("      default: ******");
-                        } else {
-                            println!("      default: {default_value}");
-                        }
-                    }
-                }
-            }
+            let vars: Vec<_> = conf_vars
+                .iter()
+                .map(|state| (state.var, Some(state.set)))
+                .collect();
+            print_configuration(&vars);
         },
     }
 
     // The report is the output; whether the module is ready to use is the
     // exit status, so that inspecting one doubles as checking it.
-    let missing: Vec<&str> = conf_vars
-        .iter()
-        .zip(&conf_status)
-        .filter(|(var, is_set)| var.is_required() && !**is_set)
-        .map(|(var, _)| var.name.as_str())
-        .collect();
-
-    if !missing.is_empty() {
+    if conf_vars.iter().any(VariableState::is_missing) {
         ceprintln!(
             "<s,dim>hint:</> Configure the missing variable(s) interactively with: <s>asimov module config setup {module_name}</>"
         );
@@ -171,3 +92,39 @@ pub async fn inspect(
 
     Ok(())
 }
+
+/// Whether a configuration variable of a module is set in a profile.
+pub(super) struct VariableState<'a> {
+    pub var: &'a ConfigurationVariable,
+    pub set: bool,
+    /// Why the variable couldn't be read, in which case it counts as unset.
+    pub error: Option<ReadVarError>,
+}
+
+impl VariableState<'_> {
+    /// Whether the module can't be used until the variable is set.
+    pub fn is_missing(&self) -> bool {
+        self.var.is_required() && !self.set
+    }
+}
+
+/// Reads whether each configuration variable that a module declares is set
+/// in a profile, which is what decides whether the module is ready to use.
+pub(super) fn variable_states<'a>(
+    manifest: &'a ModuleManifest,
+    profile: &str,
+) -> Vec<VariableState<'a>> {
+    manifest
+        .config
+        .iter()
+        .flat_map(|config| config.variables.iter())
+        .map(|var| {
+            let (set, error) = match manifest.variable(&var.name, Some(profile)) {
+                Ok(_) => (true, None),
+                Err(ReadVarError::UnconfiguredVar(_)) => (false, None),
+                Err(e) => (false, Some(e)),
+            };
+            VariableState { var, set, error }
+        })
+        .collect()
+}
@@ -1,25 +1,26 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions, SysexitsError::*};
-use asimov_installer::InstallOptions;
+use super::{
+    Job, JobOutcome, Lockfile, ModuleSpec, PlannedModule, describe, install_release, locked_module,
+    plan, run_jobs, summarize,
+};
+use crate::{BoxError, StandardOptions, SysexitsError::*, registry::http::download_client, shared};
 use asimov_module::{ModuleManifest, ModuleName, ReadVarError};
 use color_print::{ceprintln, cprintln};
+use std::{io::IsTerminal, path::Path};
 
 pub async fn install(
-    module_names: Vec<ModuleName>,
+    modules: Vec<ModuleSpec>,
     version: Option<String>,
     model_size: Option<String>,
+    no_deps: bool,
+    jobs: usize,
     flags: &StandardOptions,
 ) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
     let installer = asimov_installer::Installer::default();
 
-    let install_options = InstallOptions::builder()
-        .maybe_version(version.clone())
-        .maybe_model_size(model_size.clone())
-        .build();
-
-    let module_names = if module_names.len() == 1 && module_names[0].as_str() == "all" {
+    let modules = if modules.len() == 1 && modules[0].name.as_str() == "all" {
         fetch_all_module_names()
             .await
             .map_err(|e| {
@@ -27,132 +28,315 @@ pub async fn install(
                 EX_UNAVAILABLE
             })?
             .into_iter()
-            .map(ModuleName::try_from)
+            .map(|name| {
+                ModuleName::try_from(name).map(|name| ModuleSpec {
+                    name,
+                    version: None,
+                })
+            })
             .collect::<Result<Vec<_>, _>>()?
     } else {
-        module_names.to_vec()
+        modules
     };
 
-    for module_name in module_names {
-        if !registry
+    let plan = plan(&registry, &installer, modules, version.as_deref(), no_deps).await?;
+
+    let outcomes = install_plan(&plan, model_size, jobs, flags).await;
+
+    for PlannedModule {
+        name: module_name,
+        pin,
+        version: target_version,
+        ..
+    } in plan
+    {
+        if target_version.is_some() {
+            let installed = outcomes
+                .iter()
+                .any(|outcome| outcome.name == module_name && outcome.result.is_ok());
+            if !installed {
+                continue;
+            }
+        } else {
+            let installed_version = registry.module_version(&module_name).await.ok().flatten();
+
+            match (&pin, installed_version) {
+                (Some(pin), Some(installed)) if !pin.matches(&installed) => {
+                    ceprintln!(
+                        "<s,y>warn:</> Module <s>{module_name}</> is already installed with version <s>{installed}</>, which does not satisfy the requested version."
+                    );
+                    ceprintln!(
+                        "<s,dim>hint:</> Change the installed version with: <s>asimov module upgrade {}</>",
+                        ModuleSpec {
+                            name: module_name.clone(),
+                            version: Some(pin.clone()),
+                        }
+                    );
+                },
+                _ if flags.verbose > 0 => {
+                    cprintln!("<s,g>✓</> Module <s>{module_name}</> is already installed.");
+                },
+                _ => (),
+            }
+        }
+
+        enable_if_configured(&registry, &module_name).await?;
+    }
+
+    summarize(&outcomes, flags)
+}
+
+/// Installs the modules of a plan that aren't installed yet, the modules they
+/// require first, and returns what became of each. Only the modules in the
+/// plan are installed, so a plan made with `no_deps` installs none that
+/// they require.
+pub(super) async fn install_plan(
+    plan: &[PlannedModule],
+    model_size: Option<String>,
+    jobs: usize,
+    flags: &StandardOptions,
+) -> Vec<JobOutcome> {
+    let dependencies = plan
+        .iter()
+        .filter(|planned| planned.version.is_some() && planned.required_by.is_some());
+    if dependencies.clone().next().is_some() {
+        cprintln!("<s,c>»</> Installing required modules first:");
+        for planned in dependencies {
+            cprintln!(
+                "    <s>{}</> {} (required by <s>{}</>)",
+                planned.name,
+                planned.version.as_deref().unwrap_or_default(),
+                planned.required_by.as_ref().unwrap()
+            );
+        }
+    }
+
+    let queue = plan
+        .iter()
+        .filter_map(|planned| {
+            Some(Job {
+                name: planned.name.clone(),
+                requires: planned.requires.clone(),
+                data: planned.version.clone()?,
+            })
+        })
+        .collect();
+
+    let progress = flags.verbose > 0 || std::io::stdout().is_terminal();
+    run_jobs(
+        queue,
+        jobs,
+        "Installing",
+        progress,
+        |module_name, target_version| {
+            let model_size = model_size.clone();
+            async move {
+                let registry = asimov_registry::Registry::default();
+                let result = install_release(
+                    &registry,
+                    &module_name,
+                    &target_version,
+                    model_size.as_deref(),
+                )
+                .await
+                .map(|()| "installed")
+                .map_err(|e| format!("failed to install: {}", describe(e)));
+                (Some(target_version), result)
+            }
+        },
+    )
+    .await
+}
+
+/// Installs exactly the modules recorded in a lockfile, failing if any
+/// installed module differs from its record or isn't recorded at all.
+pub async fn install_locked(
+    lockfile: &Path,
+    model_size: Option<String>,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+
+    let lockfile = Lockfile::read(lockfile).await?;
+
+    let mut mismatches = 0;
+    for locked in &lockfile.modules {
+        let module_name = ModuleName::try_from(locked.name.clone())?;
+
+        if registry
             .is_module_installed(&module_name)
             .await
             .unwrap_or(false)
         {
-            let target_version = if let Some(ref want) = version {
-                want.clone()
-            } else {
-                installer
-                    .fetch_latest_release(&module_name)
-                    .await
-                    .map_err(|e| {
-                        tracing::error!(
-                            "unable to find latest release for module `{module_name}`: {e}"
-                        );
-                        EX_UNAVAILABLE
-                    })?
-            };
+            let version = registry.module_version(&module_name).await.map_err(|_| {
+                tracing::error!("failed to read installed version of `{module_name}`");
+                EX_UNAVAILABLE
+            })?;
+
+            if version.as_ref() != Some(&locked.version) {
+                ceprintln!(
+                    "<s,r>error:</> module <s>{module_name}</> has version <s>{}</> installed, but the lockfile requires <s>{}</>",
+                    version.as_deref().unwrap_or("unknown"),
+                    locked.version
+                );
+                mismatches += 1;
+                continue;
+            }
 
             if flags.verbose > 0 {
                 cprintln!(
-                    "<s,g>✓</> Found version <s>{target_version}</> for module <s>{module_name}</>."
+                    "<s,g>✓</> Module <s>{module_name}</> already has version <s>{}</> installed.",
+                    locked.version
                 );
             }
-
+        } else {
             if flags.verbose > 1 {
-                cprintln!("<s,c>»</> Installing module <s>{module_name}</>...");
+                cprintln!(
+                    "<s,c>»</> Installing module <s>{module_name}</> version <s>{}</>...",
+                    locked.version
+                );
             }
 
-            installer
-                .install_module(&module_name, &install_options)
-                .await
-                .map_err(|e| {
-                    tracing::error!("failed to install module `{module_name}`: {e}");
-                    EX_UNAVAILABLE
-                })?;
+            // the lockfile records every module, so none is installed twice
+            install_release(
+                &registry,
+                &module_name,
+                &locked.version,
+                model_size.as_deref(),
+            )
+            .await
+            .map_err(|e| {
+                tracing::error!("failed to install module `{module_name}`: {e}");
+                EX_UNAVAILABLE
+            })?;
 
             if flags.verbose > 0 {
-                cprintln!("<s,g>✓</> Installed module <s>{module_name}</>.");
+                cprintln!(
+                    "<s,g>✓</> Installed module <s>{module_name}</> version <s>{}</>.",
+                    locked.version
+                );
             }
-        } else if flags.verbose > 0 {
-            cprintln!("<s,g>✓</> Module <s>{module_name}</> is already installed.");
-        }
-
-        if registry
-            .is_module_enabled(&module_name)
-            .await
-            .unwrap_or(false)
-        {
-            continue;
         }
 
-        let manifest = registry.read_manifest(&module_name).await.map_err(|e| {
+        let installed = registry.read_manifest(&module_name).await.map_err(|e| {
             tracing::error!("failed to read module manifest for `{module_name}`: {e}");
             EX_UNAVAILABLE
         })?;
 
-        let variables = manifest
-            .manifest
-            .config
-            .iter()
-            .flat_map(|conf| conf.variables.iter());
+        let actual = locked_module(&registry, installed).await?;
+        if actual.checksum != locked.checksum {
+            ceprintln!(
+                "<s,r>error:</> the installed programs of module <s>{module_name}</> do not match the lockfile checksum"
+            );
+            ceprintln!("<s,dim>hint:</>   Expected: {}", locked.checksum);
+            ceprintln!("<s,dim>hint:</>   Actual:   {}", actual.checksum);
+            mismatches += 1;
+            continue;
+        }
+
+        enable_if_configured(&registry, &module_name).await?;
+    }
 
-        let mut missing_variables = Vec::new();
-        for var in variables {
-            if !var.is_required() {
-                continue;
-            }
-            match manifest.manifest.variable(&var.name, None) {
-                Ok(_) => (),
-                Err(ReadVarError::UnconfiguredVar(_)) => {
-                    missing_variables.push(var);
-                },
-                Err(e) => {
-                    tracing::error!(
-                        "failed to read configuration variable `{}` for module `{module_name}`: {e}",
-                        var.name
-                    );
-                    return Err(EX_UNAVAILABLE.into());
-                },
-            }
+    let installed = registry.installed_modules().await.map_err(|e| {
+        tracing::error!("failed to read installed modules: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    for module in installed {
+        let name = &module.manifest.name;
+        if !lockfile.modules.iter().any(|locked| &locked.name == name) {
+            ceprintln!("<s,r>error:</> module <s>{name}</> is installed but not in the lockfile");
+            ceprintln!("<s,dim>hint:</> Uninstall it with: <s>asimov module uninstall {name}</>");
+            mismatches += 1;
         }
+    }
 
-        if missing_variables.is_empty() {
-            registry.enable_module(&module_name).await.map_err(|e| {
-                tracing::error!("failed to enable installed module `{module_name}`: {e}");
-                EX_UNAVAILABLE
-            })?;
-        } else {
-            ceprintln!(
-                "<s,y>warn:</> Module <s>{module_name}</> wasn't enabled automatically due to missing configuration."
-            );
-            ceprintln!("<s,dim>hint:</> Module <s>{module_name}</> requires configuration:");
+    if mismatches > 0 {
+        return Err(EX_DATAERR.into());
+    }
 
-            for var in missing_variables {
-                let desc_suffix = if let Some(ref desc) = var.description {
-                    format!(" (Description: \"{desc}\")")
-                } else {
-                    String::new()
-                };
+    Ok(())
+}
 
-                ceprintln!(
-                    "<s,dim>hint:</>   Missing variable: <s>{}</s>{}",
-                    var.name,
-                    desc_suffix
+/// Enables a freshly installed module, unless it still lacks required
+/// configuration, in which case it explains how to configure and enable it.
+pub(super) async fn enable_if_configured(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> Result<(), BoxError> {
+    if registry
+        .is_module_enabled(module_name)
+        .await
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+
+    let manifest = registry.read_manifest(module_name).await.map_err(|e| {
+        tracing::error!("failed to read module manifest for `{module_name}`: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    let profile = shared::profile(None)?;
+    let variables = manifest
+        .manifest
+        .config
+        .iter()
+        .flat_map(|conf| conf.variables.iter());
+
+    let mut missing_variables = Vec::new();
+    for var in variables {
+        if !var.is_required() {
+            continue;
+        }
+        match manifest.manifest.variable(&var.name, Some(&profile)) {
+            Ok(_) => (),
+            Err(ReadVarError::UnconfiguredVar(_)) => {
+                missing_variables.push(var);
+            },
+            Err(e) => {
+                tracing::error!(
+                    "failed to read configuration variable `{}` for module `{module_name}`: {e}",
+                    var.name
                 );
+                return Err(EX_UNAVAILABLE.into());
+            },
+        }
+    }
 
-                if let Some(ref env) = var.environment {
-                    ceprintln!(
-                        "<s,dim>hint:</>   Alternative: set environment variable: <s>{env}</>"
-                    );
-                }
-            }
+    if missing_variables.is_empty() {
+        registry.enable_module(module_name).await.map_err(|e| {
+            tracing::error!("failed to enable installed module `{module_name}`: {e}");
+            EX_UNAVAILABLE
+        })?;
+    } else {
+        ceprintln!(
+            "<s,y>warn:</> Module <s>{module_name}</> wasn't enabled automatically due to missing configuration."
+        );
+        ceprintln!("<s,dim>hint:</> Module <s>{module_name}</> requires configuration:");
+
+        for var in missing_variables {
+            let desc_suffix = if let Some(ref desc) = var.description {
+                format!(" (Description: \"{desc}\")")
+            } else {
+                String::new()
+            };
 
             ceprintln!(
-                "<s,dim>hint:</>   To configure: <s>asimov module config setup {module_name}</s>"
+                "<s,dim>hint:</>   Missing variable: <s>{}</s>{}",
+                var.name,
+                desc_suffix
             );
-            ceprintln!("<s,dim>hint:</>   To enable: <s>asimov module enable {module_name}</s>");
+
+            if let Some(ref env) = var.environment {
+                ceprintln!("<s,dim>hint:</>   Alternative: set environment variable: <s>{env}</>");
+            }
         }
+
+        ceprintln!(
+            "<s,dim>hint:</>   To configure: <s>asimov module config setup {module_name}</s>"
+        );
+        ceprintln!("<s,dim>hint:</>   To enable: <s>asimov module enable {module_name}</s>");
     }
 
     Ok(())
@@ -161,12 +345,7 @@ pub async fn install(
 pub async fn fetch_all_module_names() -> Result<Vec<String>, Box<dyn core::error::Error>> {
     let url = "https://github.com/asimov-modules/asimov-modules/raw/master/all/.asimov/module.yaml";
 
-    let client = reqwest::Client::builder()
-        .user_agent("asimov-module-cli")
-        .connect_timeout(std::time::Duration::from_secs(10))
-        .read_timeout(std::time::Duration::from_secs(30))
-        .build()
-        .expect("Failed to build HTTP client");
+    let client = download_client();
 
     let response = client
         .get(url)
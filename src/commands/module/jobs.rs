@@ -0,0 +1,236 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, StandardOptions, SysexitsError};
+use asimov_module::ModuleName;
+use color_print::{ceprintln, cprintln};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    io::IsTerminal,
+};
+
+/// Work to do for one module, once the modules it requires are done.
+pub(super) struct Job<T> {
+    pub name: ModuleName,
+    pub requires: Vec<ModuleName>,
+    pub data: T,
+}
+
+/// What became of a module's job.
+#[derive(Clone, Debug)]
+pub(super) struct JobOutcome {
+    pub name: ModuleName,
+    pub version: Option<String>,
+    pub result: Result<&'static str, String>,
+}
+
+/// Runs module jobs with at most `jobs` of them at a time. A job starts only
+/// after the jobs of the modules it requires have succeeded, and fails
+/// without running if any of them failed. Outcomes are returned in the
+/// order the jobs were given.
+pub(super) async fn run_jobs<T, F, Fut>(
+    queue: Vec<Job<T>>,
+    jobs: usize,
+    action: &str,
+    progress: bool,
+    run: F,
+) -> Vec<JobOutcome>
+where
+    T: Send + 'static,
+    F: Fn(ModuleName, T) -> Fut,
+    Fut: Future<Output = (Option<String>, Result<&'static str, String>)> + Send + 'static,
+{
+    let order: Vec<ModuleName> = queue.iter().map(|job| job.name.clone()).collect();
+    let total = queue.len();
+
+    let mut pending: Vec<Job<T>> = queue;
+    let mut outcomes: BTreeMap<ModuleName, JobOutcome> = BTreeMap::new();
+    let mut running = tokio::task::JoinSet::new();
+    let mut running_names = HashMap::new();
+
+    loop {
+        // fail the jobs whose requirements failed, and start the ready ones
+        let mut index = 0;
+        while index < pending.len() {
+            let job = &pending[index];
+            let waiting = job
+                .requires
+                .iter()
+                .filter(|name| order.contains(name))
+                .find_map(|name| match outcomes.get(name) {
+                    None => Some(None),
+                    Some(outcome) if outcome.result.is_err() => Some(Some(name.clone())),
+                    Some(_) => None,
+                });
+
+            match waiting {
+                Some(Some(failed)) => {
+                    let job = pending.remove(index);
+                    let outcome = JobOutcome {
+                        name: job.name,
+                        version: None,
+                        result: Err(format!("required module `{failed}` failed")),
+                    };
+                    report(&outcome, outcomes.len() + 1, total, progress);
+                    outcomes.insert(outcome.name.clone(), outcome);
+                },
+                Some(None) => index += 1,
+                None if running.len() < jobs.max(1) => {
+                    let job = pending.remove(index);
+                    if progress {
+                        cprintln!("<s,c>»</> {action} module <s>{}</>...", job.name);
+                    }
+                    let name = job.name.clone();
+                    let task = run(job.name, job.data);
+                    let handle = running.spawn(task);
+                    running_names.insert(handle.id(), name);
+                },
+                None => index += 1,
+            }
+        }
+
+        let Some(finished) = running.join_next_with_id().await else {
+            break;
+        };
+        let outcome = match finished {
+            Ok((id, (version, result))) => JobOutcome {
+                name: running_names.remove(&id).expect("should be running"),
+                version,
+                result,
+            },
+            Err(e) => JobOutcome {
+                name: running_names.remove(&e.id()).expect("should be running"),
+                version: None,
+                result: Err(e.to_string()),
+            },
+        };
+        report(&outcome, outcomes.len() + 1, total, progress);
+        outcomes.insert(outcome.name.clone(), outcome);
+    }
+
+    order
+        .into_iter()
+        .filter_map(|name| outcomes.remove(&name))
+        .collect()
+}
+
+fn report(outcome: &JobOutcome, done: usize, total: usize, progress: bool) {
+    if !progress {
+        return;
+    }
+    let version = outcome.version.as_deref().unwrap_or_default();
+    match &outcome.result {
+        Ok(status) => cprintln!(
+            "<s,g>✓</> [{done}/{total}] <s>{}</> {version} {status}",
+            outcome.name
+        ),
+        Err(_) => cprintln!("<s,r>✗</> [{done}/{total}] <s>{}</> failed", outcome.name),
+    }
+}
+
+/// Prints a table of the outcomes to stderr when there are several, with
+/// `-v` or on a terminal, then reports each failure, returning an error if
+/// there were any.
+pub(super) fn summarize(outcomes: &[JobOutcome], flags: &StandardOptions) -> Result<(), BoxError> {
+    if outcomes.len() > 1 && (flags.verbose > 0 || std::io::stderr().is_terminal()) {
+        let name_width = outcomes
+            .iter()
+            .map(|o| o.name.as_str().len())
+            .max()
+            .unwrap_or(0)
+            .max("MODULE".len());
+        let version_width = outcomes
+            .iter()
+            .map(|o| o.version.as_deref().unwrap_or("-").len())
+            .max()
+            .unwrap_or(0)
+            .max("VERSION".len());
+
+        ceprintln!(
+            "<s>{:name_width$}  {:version_width$}  STATUS</>",
+            "MODULE",
+            "VERSION"
+        );
+        for outcome in outcomes {
+            let version = outcome.version.as_deref().unwrap_or("-");
+            match &outcome.result {
+                Ok(status) => ceprintln!(
+                    "{:name_width$}  {version:version_width$}  <g>{status}</>",
+                    outcome.name.as_str()
+                ),
+                Err(_) => ceprintln!(
+                    "{:name_width$}  {version:version_width$}  <r>failed</>",
+                    outcome.name.as_str()
+                ),
+            }
+        }
+    }
+
+    let mut failures = 0;
+    for outcome in outcomes {
+        if let Err(error) = &outcome.result {
+            ceprintln!("<s,r>error:</> module <s>{}</>: {error}", outcome.name);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        return Err(SysexitsError::EX_UNAVAILABLE.into());
+    }
+    Ok(())
+}
+
+/// Describes an error for a job outcome. Errors that are only an exit code
+/// have already been explained where they arose.
+pub(super) fn describe(error: BoxError) -> String {
+    match error.downcast_ref::<SysexitsError>() {
+        Some(code) => code.summary().into(),
+        None => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> ModuleName {
+        ModuleName::try_from(name.to_string()).unwrap()
+    }
+
+    fn job(module: &str, requires: &[&str]) -> Job<()> {
+        Job {
+            name: name(module),
+            requires: requires.iter().map(|r| name(r)).collect(),
+            data: (),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_failure_skips_only_the_modules_that_require_it() {
+        let queue = vec![job("base", &[]), job("app", &["base"]), job("other", &[])];
+
+        let outcomes = run_jobs(
+            queue,
+            2,
+            "Installing",
+            false,
+            |module_name, ()| async move {
+                if module_name.as_str() == "base" {
+                    (None, Err("broken".to_string()))
+                } else {
+                    (Some("1.0.0".to_string()), Ok("installed"))
+                }
+            },
+        )
+        .await;
+
+        let names: Vec<_> = outcomes.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["base", "app", "other"]);
+        assert!(outcomes[0].result.is_err());
+        assert_eq!(
+            outcomes[1].result.as_ref().unwrap_err(),
+            "required module `base` failed"
+        );
+        assert_eq!(outcomes[2].result, Ok("installed"));
+    }
+}
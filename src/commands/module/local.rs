@@ -0,0 +1,702 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    CONSTRAINTS_FILE_NAME, ModuleSpec, VersionPin, declared_constraints, enable_if_configured,
+    install_order, parse_checksum_listing, parse_release, record_checksums, report_cycle,
+};
+use crate::{BoxError, StandardOptions, SysexitsError::*};
+use asimov_module::{InstalledModuleManifest, ModuleManifest, ModuleName};
+use color_print::{ceprintln, cprintln};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Where a module's source tree, or an archive of it, keeps its manifest.
+const SOURCE_MANIFEST_PATH: &str = ".asimov/module.yaml";
+
+/// The manifest a mirror keeps next to the archives of each release.
+const MIRROR_MANIFEST_NAME: &str = "module.yaml";
+
+/// The checksums a mirror keeps next to the archives of each release, in
+/// the format that `sha256sum` writes.
+const MIRROR_CHECKSUMS_NAME: &str = "SHA256SUMS";
+
+/// A module found on the local filesystem, ready to be installed.
+pub(super) struct LocalModule {
+    pub manifest: ModuleManifest,
+    /// The manifest as written, including what the SDK doesn't parse.
+    pub manifest_path: PathBuf,
+    pub version: Option<String>,
+    /// Where the module's programs are looked up.
+    pub root: PathBuf,
+    pub readme: Option<PathBuf>,
+}
+
+/// Installs modules without network access, from a release archive, from
+/// a module directory containing `.asimov/module.yaml` and its programs,
+/// or from a mirror of release archives laid out like GitHub releases, as
+/// `<mirror>/asimov-<name>-module/<version>/` holding `module.yaml`, the
+/// archives for each platform, and their `SHA256SUMS`. With `no_deps`, the
+/// modules that a mirrored module requires are not installed.
+pub async fn install_from(
+    path: &Path,
+    modules: Vec<ModuleSpec>,
+    version: Option<String>,
+    no_deps: bool,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+
+    let metadata = tokio::fs::metadata(path).await.map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> failed to read <s>{}</>: {e}",
+            path.display()
+        );
+        EX_NOINPUT
+    })?;
+
+    registry.create_file_tree().await.map_err(|e| {
+        tracing::error!("failed to create the module directories: {e}");
+        EX_CANTCREAT
+    })?;
+
+    // next to the installed modules, so that adding one is a rename
+    let work_dir = registry
+        .install_dir()
+        .with_file_name(format!(".local-{}", std::process::id()));
+
+    let result = if metadata.is_file()
+        || tokio::fs::try_exists(path.join(SOURCE_MANIFEST_PATH))
+            .await
+            .unwrap_or(false)
+    {
+        install_single(&registry, path, modules, version, &work_dir, flags).await
+    } else {
+        install_mirrored(&registry, path, modules, version, no_deps, &work_dir, flags).await
+    };
+
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result
+}
+
+/// Installs the one module contained in an archive or a module directory.
+async fn install_single(
+    registry: &asimov_registry::Registry,
+    path: &Path,
+    modules: Vec<ModuleSpec>,
+    version: Option<String>,
+    work_dir: &Path,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let spec = match modules.as_slice() {
+        [] => None,
+        [spec] => Some(spec),
+        _ => {
+            ceprintln!(
+                "<s,r>error:</> <s>{}</> contains a single module, but several were named",
+                path.display()
+            );
+            return Err(EX_USAGE.into());
+        },
+    };
+
+    let version = match spec.and_then(|spec| spec.version.as_ref()) {
+        Some(VersionPin::Exact(version)) => Some(version.clone()),
+        Some(VersionPin::Range(req)) => {
+            ceprintln!(
+                "<s,r>error:</> version range <s>{req}</> can only be chosen from a mirror, not from <s>{}</>",
+                path.display()
+            );
+            return Err(EX_USAGE.into());
+        },
+        None => version,
+    };
+
+    let root = if tokio::fs::metadata(path).await?.is_file() {
+        let root = work_dir.join("extract");
+        extract_archive(path, &root).await?;
+        root
+    } else {
+        path.to_path_buf()
+    };
+
+    let manifest_path = [
+        root.join(SOURCE_MANIFEST_PATH),
+        root.join(MIRROR_MANIFEST_NAME),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+    .ok_or_else(|| {
+        ceprintln!(
+            "<s,r>error:</> no module manifest found in <s>{}</>",
+            path.display()
+        );
+        ceprintln!("<s,dim>hint:</> Expected a manifest at <s>{SOURCE_MANIFEST_PATH}</>");
+        EX_DATAERR
+    })?;
+    let manifest = read_manifest(&manifest_path).await?;
+
+    if let Some(spec) = spec
+        && spec.name.as_str() != manifest.name
+    {
+        ceprintln!(
+            "<s,r>error:</> <s>{}</> contains module <s>{}</>, not <s>{}</>",
+            path.display(),
+            manifest.name,
+            spec.name
+        );
+        return Err(EX_USAGE.into());
+    }
+
+    for dependency in &manifest.requires.modules {
+        let installed = match ModuleName::try_from(dependency.clone()) {
+            Ok(dependency) => registry
+                .is_module_installed(&dependency)
+                .await
+                .unwrap_or(false),
+            Err(_) => false,
+        };
+        if !installed {
+            ceprintln!(
+                "<s,y>warn:</> Module <s>{}</> requires module <s>{dependency}</>, which isn't installed.",
+                manifest.name
+            );
+            ceprintln!(
+                "<s,dim>hint:</> Install it with: <s>asimov module install --from <<MIRROR> {dependency}</>"
+            );
+        }
+    }
+
+    let readme = find_readme(&[&root]);
+    install_local(
+        registry,
+        LocalModule {
+            manifest,
+            manifest_path,
+            version,
+            root,
+            readme,
+        },
+        work_dir,
+        flags,
+    )
+    .await
+}
+
+/// Installs modules, and the modules they require, from a mirror of
+/// release archives. Without names, installs every module in the mirror.
+async fn install_mirrored(
+    registry: &asimov_registry::Registry,
+    mirror: &Path,
+    modules: Vec<ModuleSpec>,
+    version: Option<String>,
+    no_deps: bool,
+    work_dir: &Path,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let modules = if modules.is_empty() {
+        mirrored_module_names(mirror)
+            .await
+            .map_err(|e| {
+                ceprintln!(
+                    "<s,r>error:</> failed to read mirror <s>{}</>: {e}",
+                    mirror.display()
+                );
+                EX_NOINPUT
+            })?
+            .into_iter()
+            .map(|name| ModuleSpec {
+                name,
+                version: None,
+            })
+            .collect()
+    } else {
+        modules
+    };
+
+    let roots: Vec<ModuleName> = modules.iter().map(|spec| spec.name.clone()).collect();
+
+    let mut releases: BTreeMap<ModuleName, (String, PathBuf, ModuleManifest, PathBuf)> =
+        BTreeMap::new();
+    let mut edges: BTreeMap<ModuleName, Vec<ModuleName>> = BTreeMap::new();
+    let mut queue: VecDeque<(ModuleSpec, bool)> =
+        modules.into_iter().map(|spec| (spec, true)).collect();
+
+    while let Some((spec, requested)) = queue.pop_front() {
+        if edges.contains_key(&spec.name) {
+            continue;
+        }
+
+        if registry
+            .is_module_installed(&spec.name)
+            .await
+            .unwrap_or(false)
+        {
+            edges.insert(spec.name, Vec::new());
+            continue;
+        }
+
+        // only the requested modules get the `--version` fallback
+        let default = requested.then_some(version.as_deref()).flatten();
+        let (release, release_dir) =
+            mirrored_release(mirror, &spec.name, spec.version.as_ref(), default).await?;
+        let manifest_path = release_dir.join(MIRROR_MANIFEST_NAME);
+        let manifest = read_manifest(&manifest_path).await?;
+
+        let mut requires = Vec::new();
+        for dependency in &manifest.requires.modules {
+            if no_deps {
+                break;
+            }
+            let dependency = ModuleName::try_from(dependency.clone()).map_err(|e| {
+                ceprintln!(
+                    "<s,r>error:</> module <s>{}</> requires an invalid module name: {e}",
+                    spec.name
+                );
+                EX_DATAERR
+            })?;
+            queue.push_back((
+                ModuleSpec {
+                    name: dependency.clone(),
+                    version: None,
+                },
+                false,
+            ));
+            requires.push(dependency);
+        }
+
+        edges.insert(spec.name.clone(), requires);
+        releases.insert(spec.name, (release, release_dir, manifest, manifest_path));
+    }
+
+    let order = install_order(&roots, &edges).map_err(report_cycle)?;
+
+    for module_name in order {
+        let Some((release, release_dir, manifest, manifest_path)) = releases.remove(&module_name)
+        else {
+            if roots.contains(&module_name) {
+                if flags.verbose > 0 {
+                    cprintln!("<s,g>✓</> Module <s>{module_name}</> is already installed.");
+                }
+                enable_if_configured(registry, &module_name).await?;
+            }
+            continue;
+        };
+
+        let archive = find_archive(&release_dir, &module_name).ok_or_else(|| {
+            ceprintln!(
+                "<s,r>error:</> no archive of module <s>{module_name}</> version <s>{release}</> for this platform in <s>{}</>",
+                release_dir.display()
+            );
+            ceprintln!(
+                "<s,dim>hint:</> Expected one of: {}",
+                asset_names(&module_name).join(", ")
+            );
+            EX_UNAVAILABLE
+        })?;
+
+        verify_archive(&release_dir, &archive).await?;
+
+        let root = work_dir.join(format!("{module_name}-extract"));
+        extract_archive(&archive, &root).await?;
+
+        let readme = find_readme(&[&root, &release_dir]);
+        install_local(
+            registry,
+            LocalModule {
+                manifest,
+                manifest_path,
+                version: Some(release),
+                root,
+                readme,
+            },
+            work_dir,
+            flags,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Places a module the same way the installer does, then enables it if it
+/// needs no further configuration.
+async fn install_local(
+    registry: &asimov_registry::Registry,
+    local: LocalModule,
+    work_dir: &Path,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let module_name = ModuleName::try_from(local.manifest.name.clone())?;
+
+    if registry
+        .is_module_installed(&module_name)
+        .await
+        .unwrap_or(false)
+    {
+        if flags.verbose > 0 {
+            cprintln!("<s,g>✓</> Module <s>{module_name}</> is already installed.");
+        }
+        return enable_if_configured(registry, &module_name).await;
+    }
+
+    if flags.verbose > 1 {
+        cprintln!(
+            "<s,c>»</> Installing module <s>{module_name}</> from <s>{}</>...",
+            local.root.display()
+        );
+    }
+
+    let manifest = place_module(registry, local, work_dir).await?;
+
+    if !manifest.manifest.requires.models.is_empty() {
+        ceprintln!(
+            "<s,y>warn:</> Module <s>{module_name}</> requires models, which aren't installed from local files."
+        );
+    }
+
+    if flags.verbose > 0 {
+        match &manifest.version {
+            Some(version) => {
+                cprintln!("<s,g>✓</> Installed module <s>{module_name}</> version <s>{version}</>.")
+            },
+            None => cprintln!("<s,g>✓</> Installed module <s>{module_name}</>."),
+        }
+    }
+
+    enable_if_configured(registry, &module_name).await
+}
+
+/// Places a module's programs, README, and manifest in a work directory the
+/// same way the installer does, then adds it to the installed modules.
+pub(super) async fn place_module(
+    registry: &asimov_registry::Registry,
+    local: LocalModule,
+    work_dir: &Path,
+) -> Result<InstalledModuleManifest, BoxError> {
+    let module_name = ModuleName::try_from(local.manifest.name.clone())?;
+
+    let module_dir = work_dir.join(module_name.as_str());
+    let bin_dir = module_dir.join(asimov_registry::BIN_DIR_NAME);
+    tokio::fs::create_dir_all(&bin_dir).await.map_err(|e| {
+        tracing::error!("failed to create `{}`: {e}", bin_dir.display());
+        EX_CANTCREAT
+    })?;
+
+    for program in &local.manifest.provides.programs {
+        let Some(src) = find_program(&local.root, program) else {
+            ceprintln!(
+                "<s,r>error:</> program <s>{program}</> of module <s>{module_name}</> not found in <s>{}</>",
+                local.root.display()
+            );
+            return Err(EX_DATAERR.into());
+        };
+
+        let dst = bin_dir.join(program);
+        tokio::fs::copy(&src, &dst).await.map_err(|e| {
+            tracing::error!("failed to copy `{}`: {e}", src.display());
+            EX_IOERR
+        })?;
+
+        #[cfg(unix)]
+        {
+            use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+            tokio::fs::set_permissions(&dst, Permissions::from_mode(0o755)).await?;
+        }
+    }
+
+    if let Some(readme) = &local.readme {
+        let readme_path = module_dir.join(asimov_registry::README_FILE_PATH);
+        if let Some(doc_dir) = readme_path.parent() {
+            tokio::fs::create_dir_all(doc_dir).await?;
+        }
+        tokio::fs::copy(readme, &readme_path).await?;
+    }
+
+    let installed = InstalledModuleManifest {
+        version: local.version,
+        manifest: local.manifest,
+    };
+    tokio::fs::write(
+        module_dir.join(asimov_registry::MANIFEST_FILE_NAME),
+        serde_json::to_vec_pretty(&installed)?,
+    )
+    .await?;
+    let constraints =
+        declared_constraints(&tokio::fs::read_to_string(&local.manifest_path).await?)?;
+    tokio::fs::write(
+        module_dir.join(CONSTRAINTS_FILE_NAME),
+        serde_json::to_vec_pretty(&constraints)?,
+    )
+    .await?;
+
+    registry
+        .add_module(&module_name, &module_dir)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to install module `{module_name}`: {e}");
+            EX_UNAVAILABLE
+        })?;
+    record_checksums(registry, &module_name).await;
+
+    Ok(installed)
+}
+
+/// Chooses a release of a module from a mirror: the pinned one, the one
+/// given with `--version`, or else the highest one available.
+async fn mirrored_release(
+    mirror: &Path,
+    module_name: &ModuleName,
+    pin: Option<&VersionPin>,
+    default: Option<&str>,
+) -> Result<(String, PathBuf), BoxError> {
+    let module_dir = mirror.join(format!("asimov-{module_name}-module"));
+
+    let exact = match pin {
+        Some(VersionPin::Exact(version)) => Some(version.as_str()),
+        Some(VersionPin::Range(_)) => None,
+        None => default,
+    };
+
+    if let Some(version) = exact {
+        let release_dir = module_dir.join(version);
+        if !release_dir.is_dir() {
+            ceprintln!(
+                "<s,r>error:</> version <s>{version}</> of module <s>{module_name}</> is not in mirror <s>{}</>",
+                mirror.display()
+            );
+            return Err(EX_UNAVAILABLE.into());
+        }
+        return Ok((version.into(), release_dir));
+    }
+
+    let mut releases = Vec::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(&module_dir).await {
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir()
+                && let Some(release) = entry.file_name().to_str()
+            {
+                releases.push(release.to_string());
+            }
+        }
+    }
+
+    releases
+        .iter()
+        .filter(|release| pin.is_none_or(|pin| pin.matches(release)))
+        .filter_map(|release| Some((parse_release(release)?, release)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| (release.clone(), module_dir.join(release)))
+        .ok_or_else(|| {
+            ceprintln!(
+                "<s,r>error:</> no suitable release of module <s>{module_name}</> in mirror <s>{}</>",
+                mirror.display()
+            );
+            if !releases.is_empty() {
+                releases.sort();
+                ceprintln!(
+                    "<s,dim>hint:</> Available releases: {}",
+                    releases.join(", ")
+                );
+            }
+            EX_UNAVAILABLE.into()
+        })
+}
+
+/// Lists the modules a mirror has releases of.
+async fn mirrored_module_names(mirror: &Path) -> io::Result<Vec<ModuleName>> {
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(mirror).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(name) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix("asimov-"))
+            .and_then(|name| name.strip_suffix("-module"))
+        else {
+            continue;
+        };
+        if let Ok(name) = ModuleName::try_from(name.to_string()) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+async fn read_manifest(path: &Path) -> Result<ModuleManifest, BoxError> {
+    let content = tokio::fs::read_to_string(path).await.map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> failed to read module manifest <s>{}</>: {e}",
+            path.display()
+        );
+        EX_NOINPUT
+    })?;
+
+    let manifest = serde_yml::from_str(&content).map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> failed to parse module manifest <s>{}</>: {e}",
+            path.display()
+        );
+        EX_DATAERR
+    })?;
+
+    Ok(manifest)
+}
+
+/// The names of the release archives that suit this platform, in order of
+/// preference, as published on GitHub.
+pub(super) fn asset_names(module_name: &ModuleName) -> Vec<String> {
+    let os = if cfg!(target_os = "macos") {
+        "macos"
+    } else if cfg!(target_os = "linux") {
+        "linux"
+    } else if cfg!(target_os = "windows") {
+        "windows"
+    } else {
+        "unknown"
+    };
+
+    let arch = if cfg!(any(target_arch = "aarch64", target_arch = "arm")) {
+        "arm"
+    } else if cfg!(all(target_arch = "x86_64", target_os = "windows")) {
+        "x64"
+    } else if cfg!(target_arch = "x86_64") {
+        "x86"
+    } else {
+        "unknown"
+    };
+
+    let libc = if cfg!(target_env = "musl") {
+        Some("musl")
+    } else if cfg!(target_env = "gnu") {
+        Some("gnu")
+    } else {
+        None
+    };
+
+    let mut platforms = Vec::new();
+    if let Some(libc) = libc {
+        platforms.push(format!("{os}-{arch}-{libc}"));
+    }
+    platforms.push(format!("{os}-{arch}"));
+
+    platforms
+        .iter()
+        .flat_map(|platform| {
+            ["tar.gz", "zip"]
+                .map(|extension| format!("asimov-{module_name}-module-{platform}.{extension}"))
+        })
+        .collect()
+}
+
+fn find_archive(release_dir: &Path, module_name: &ModuleName) -> Option<PathBuf> {
+    asset_names(module_name)
+        .into_iter()
+        .map(|name| release_dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Verifies a mirrored archive against the checksums of its release.
+async fn verify_archive(release_dir: &Path, archive: &Path) -> Result<(), BoxError> {
+    let checksums_path = release_dir.join(MIRROR_CHECKSUMS_NAME);
+    let checksums = tokio::fs::read_to_string(&checksums_path)
+        .await
+        .map_err(|e| {
+            ceprintln!(
+                "<s,r>error:</> failed to read checksums <s>{}</>: {e}",
+                checksums_path.display()
+            );
+            ceprintln!(
+                "<s,dim>hint:</> Mirrors list the checksums of their archives in <s>{MIRROR_CHECKSUMS_NAME}</>, as written by <s>sha256sum</>"
+            );
+            EX_NOINPUT
+        })?;
+
+    let name = archive
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let Some(expected) = parse_checksum_listing(&checksums).remove(name) else {
+        ceprintln!(
+            "<s,r>error:</> <s>{name}</> is not listed in <s>{}</>",
+            checksums_path.display()
+        );
+        return Err(EX_DATAERR.into());
+    };
+
+    let content = tokio::fs::read(archive).await?;
+    let actual = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&content));
+    if !actual.eq_ignore_ascii_case(&expected) {
+        ceprintln!(
+            "<s,r>error:</> the checksum of <s>{}</> does not match <s>{MIRROR_CHECKSUMS_NAME}</>",
+            archive.display()
+        );
+        ceprintln!("<s,dim>hint:</>   Expected: {expected}");
+        ceprintln!("<s,dim>hint:</>   Actual:   {actual}");
+        return Err(EX_DATAERR.into());
+    }
+
+    Ok(())
+}
+
+fn find_program(root: &Path, program: &str) -> Option<PathBuf> {
+    [root.to_path_buf(), root.join(asimov_registry::BIN_DIR_NAME)]
+        .into_iter()
+        .map(|dir| dir.join(program))
+        // on Windows the binaries carry the .exe extension, after any dots
+        // that are part of the program's name
+        .map(|path| {
+            if cfg!(windows) {
+                let mut path = path.into_os_string();
+                path.push(".exe");
+                PathBuf::from(path)
+            } else {
+                path
+            }
+        })
+        .find(|path| path.is_file())
+}
+
+pub(super) fn find_readme(dirs: &[&Path]) -> Option<PathBuf> {
+    dirs.iter()
+        .map(|dir| dir.join("README.md"))
+        .find(|path| path.is_file())
+}
+
+pub(super) async fn extract_archive(archive: &Path, dst: &Path) -> Result<(), BoxError> {
+    let file_name = archive
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let (src, dst) = (archive.to_path_buf(), dst.to_path_buf());
+    let result = tokio::task::spawn_blocking(move || -> io::Result<()> {
+        std::fs::create_dir_all(&dst)?;
+        let file = std::fs::File::open(&src)?;
+        if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(&dst)
+        } else if file_name.ends_with(".zip") {
+            zip::ZipArchive::new(file)
+                .and_then(|mut archive| archive.extract(&dst))
+                .map_err(io::Error::other)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "expected a .tar.gz or .zip archive",
+            ))
+        }
+    })
+    .await?;
+
+    result.map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> failed to extract <s>{}</>: {e}",
+            archive.display()
+        );
+        EX_DATAERR.into()
+    })
+}
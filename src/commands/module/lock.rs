@@ -0,0 +1,158 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{checksum_listing, link_ecosystems, program_checksums};
+use crate::{BoxError, StandardOptions, SysexitsError::*};
+use asimov_module::{InstalledModuleManifest, ModuleName};
+use color_print::{ceprintln, cprintln};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The lockfile written when no path is given.
+pub const LOCKFILE_NAME: &str = "asimov.lock";
+
+/// The format version of lockfiles written by this version of the CLI.
+const LOCKFILE_VERSION: u32 = 1;
+
+/// A record of the exact set of installed modules, for reproducing it
+/// elsewhere with `asimov module install --locked`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Lockfile {
+    pub version: u32,
+    pub modules: Vec<LockedModule>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct LockedModule {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecosystem: Option<String>,
+    pub checksum: String,
+}
+
+impl Lockfile {
+    pub async fn read(path: &Path) -> Result<Self, BoxError> {
+        let content = tokio::fs::read_to_string(path).await.map_err(|e| {
+            ceprintln!(
+                "<s,r>error:</> failed to read lockfile <s>{}</>: {e}",
+                path.display()
+            );
+            EX_NOINPUT
+        })?;
+
+        let lockfile: Lockfile = serde_json::from_str(&content).map_err(|e| {
+            ceprintln!(
+                "<s,r>error:</> failed to parse lockfile <s>{}</>: {e}",
+                path.display()
+            );
+            EX_DATAERR
+        })?;
+
+        if lockfile.version != LOCKFILE_VERSION {
+            ceprintln!(
+                "<s,r>error:</> unsupported lockfile version <s>{}</> in <s>{}</>",
+                lockfile.version,
+                path.display()
+            );
+            return Err(EX_DATAERR.into());
+        }
+
+        Ok(lockfile)
+    }
+}
+
+/// Writes a lockfile listing every installed module with its version,
+/// ecosystem, and a checksum of its installed programs.
+pub async fn lock(path: Option<PathBuf>, flags: &StandardOptions) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let path = path.unwrap_or_else(|| LOCKFILE_NAME.into());
+
+    let installed = registry.installed_modules().await.map_err(|e| {
+        tracing::error!("failed to read installed modules: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    let mut modules = Vec::with_capacity(installed.len());
+    for module in installed {
+        modules.push(locked_module(&registry, module).await?);
+    }
+
+    let lockfile = Lockfile {
+        version: LOCKFILE_VERSION,
+        modules,
+    };
+
+    let mut content = serde_json::to_string_pretty(&lockfile)?;
+    content.push('\n');
+    tokio::fs::write(&path, content).await.map_err(|e| {
+        ceprintln!(
+            "<s,r>error:</> failed to write lockfile <s>{}</>: {e}",
+            path.display()
+        );
+        EX_CANTCREAT
+    })?;
+
+    if flags.verbose > 0 {
+        cprintln!(
+            "<s,g>✓</> Locked <s>{}</> module(s) to <s>{}</>.",
+            lockfile.modules.len(),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Describes an installed module the way a lockfile records it.
+pub(super) async fn locked_module(
+    registry: &asimov_registry::Registry,
+    module: InstalledModuleManifest,
+) -> Result<LockedModule, BoxError> {
+    let module_name = ModuleName::try_from(module.manifest.name.clone())?;
+
+    let Some(version) = module.version else {
+        ceprintln!(
+            "<s,r>error:</> module <s>{module_name}</> was installed without a version and cannot be locked"
+        );
+        ceprintln!(
+            "<s,dim>hint:</> Reinstall it with: <s>asimov module uninstall {module_name} && asimov module install {module_name}</>"
+        );
+        return Err(EX_DATAERR.into());
+    };
+
+    let checksum = artifact_checksum(registry, &module_name, &module.manifest.provides.programs)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to checksum the programs of module `{module_name}`: {e}");
+            EX_IOERR
+        })?;
+
+    Ok(LockedModule {
+        name: module_name.to_string(),
+        version,
+        ecosystem: link_ecosystems(&module.manifest.links)
+            .first()
+            .map(|t| t.to_string()),
+        checksum,
+    })
+}
+
+/// A single checksum over all the installed programs of a module, computed
+/// over their sorted `sha256sum`-style listing.
+pub(super) async fn artifact_checksum(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    programs: &[String],
+) -> std::io::Result<String> {
+    let checksums = program_checksums(registry, module_name, programs).await?;
+    if let Some(missing) = programs.iter().find(|p| !checksums.contains_key(*p)) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("program `{missing}` is missing"),
+        ));
+    }
+    let listing = checksum_listing(&checksums);
+
+    let hash = <sha2::Sha256 as sha2::Digest>::digest(listing.as_bytes());
+    Ok(format!("sha256:{}", hex::encode(hash)))
+}
@@ -0,0 +1,176 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{
+    BoxError, StandardOptions,
+    SysexitsError::*,
+    logs::{LogEntry, log_paths},
+};
+use asimov_module::ModuleName;
+use color_print::{ceprintln, cprintln};
+use jiff::{Span, Timestamp, Zoned, civil::Date, tz::TimeZone};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+/// How often a followed log is checked for new entries.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shows the logged invocations of a module's programs, oldest first.
+pub async fn logs(
+    module_name: ModuleName,
+    follow: bool,
+    since: Option<String>,
+    output: String,
+    _flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let since = since.as_deref().map(parse_since).transpose()?;
+    // whether the entry was recent enough to show
+    let show = |entry: &LogEntry| -> Result<bool, BoxError> {
+        let recent = match (since, entry.timestamp()) {
+            (Some(since), Some(time)) => time >= since,
+            _ => true,
+        };
+        if recent {
+            print_entry(entry, &output)?;
+        }
+        Ok(recent)
+    };
+
+    let paths = log_paths(module_name.as_str());
+    let current = paths.last().expect("there is a current log").clone();
+
+    let mut shown = 0;
+    for path in &paths {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        for entry in parse(&content, path) {
+            if show(&entry)? {
+                shown += 1;
+            }
+        }
+    }
+
+    if !follow {
+        if shown == 0 && output != "jsonl" {
+            ceprintln!("<s,dim>note:</> No invocations of module <s>{module_name}</> are logged.");
+        }
+        return Ok(());
+    }
+
+    let mut position = std::fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+    let mut pending = String::new();
+    loop {
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+
+        let Ok(mut file) = std::fs::File::open(&current) else {
+            position = 0;
+            continue;
+        };
+        let size = file.metadata()?.len();
+        if size < position {
+            // the log was rotated, and this is a new one
+            position = 0;
+            pending.clear();
+        }
+        if size == position {
+            continue;
+        }
+
+        file.seek(SeekFrom::Start(position))?;
+        let mut added = String::new();
+        position += file.read_to_string(&mut added)? as u64;
+        pending.push_str(&added);
+
+        // an entry may be only partly written yet
+        let Some(end) = pending.rfind('\n') else {
+            continue;
+        };
+        let complete: String = pending.drain(..=end).collect();
+        for entry in parse(&complete, &current) {
+            show(&entry)?;
+        }
+    }
+}
+
+fn parse<'a>(content: &'a str, path: &'a Path) -> impl Iterator<Item = LogEntry> + 'a {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(move |line| {
+            serde_json::from_str(line)
+                .inspect_err(|e| {
+                    tracing::warn!("skipping a malformed entry in `{}`: {e}", path.display())
+                })
+                .ok()
+        })
+}
+
+/// Parses a time to show entries since: either a span of time before now,
+/// such as `2h` or `3 days`, a date, or a timestamp.
+fn parse_since(since: &str) -> Result<Timestamp, BoxError> {
+    if let Ok(span) = since.parse::<Span>() {
+        return Zoned::now()
+            .checked_sub(span.abs())
+            .map(|zoned| zoned.timestamp())
+            .map_err(|e| {
+                ceprintln!("<s,r>error:</> invalid time `{since}`: {e}");
+                EX_USAGE.into()
+            });
+    }
+    if let Ok(timestamp) = since.parse::<Timestamp>() {
+        return Ok(timestamp);
+    }
+    if let Ok(date) = since.parse::<Date>()
+        && let Ok(zoned) = date.to_zoned(TimeZone::system())
+    {
+        return Ok(zoned.timestamp());
+    }
+
+    ceprintln!("<s,r>error:</> invalid time `{since}`");
+    ceprintln!(
+        "<s,dim>hint:</> Give a span of time such as <s>30m</> or <s>2 days</>, a date such as <s>2025-06-01</>, or a timestamp such as <s>2025-06-01T12:00:00Z</>."
+    );
+    Err(EX_USAGE.into())
+}
+
+fn print_entry(entry: &LogEntry, output: &str) -> Result<(), BoxError> {
+    if output == "jsonl" {
+        println!("{}", serde_json::to_string(entry)?);
+        return Ok(());
+    }
+
+    let time = entry
+        .timestamp()
+        .map(|time| {
+            time.to_zoned(TimeZone::system())
+                .strftime("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| entry.time.clone());
+    let command = std::iter::once(entry.program.as_str())
+        .chain(entry.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let duration = format!("{:.1}s", entry.duration_ms as f64 / 1000.0);
+
+    if let Some(error) = &entry.error {
+        cprintln!("<dim>{time}</> <s,r>✗</> {command} <dim>({duration})</>: {error}");
+    } else if entry.succeeded() {
+        cprintln!("<dim>{time}</> <s,g>✓</> {command} <dim>({duration})</>");
+    } else {
+        let status = entry
+            .status
+            .map(|code| format!("exit status {code}"))
+            .unwrap_or_else(|| "killed by a signal".into());
+        cprintln!("<dim>{time}</> <s,r>✗</> {command} <dim>({duration}, {status})</>");
+    }
+    if let Some(stderr) = &entry.stderr {
+        for line in stderr.lines() {
+            cprintln!("    <dim>{line}</>");
+        }
+    }
+    Ok(())
+}
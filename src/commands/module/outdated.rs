@@ -0,0 +1,187 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::parse_release;
+use crate::{BoxError, StandardOptions, SysexitsError::*};
+use asimov_module::ModuleName;
+use color_print::{ceprintln, cformat, cprintln};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct OutdatedModule {
+    name: String,
+    installed: Option<String>,
+    latest: Option<String>,
+    outdated: bool,
+}
+
+/// Compares each installed module with its latest release, exiting with
+/// `EX_TEMPFAIL` when any of them can be upgraded.
+pub async fn outdated(output: String, flags: &StandardOptions) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let installer = asimov_installer::Installer::default();
+
+    let installed = registry.installed_modules().await.map_err(|e| {
+        tracing::error!("failed to read installed modules: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    let mut lookups = tokio::task::JoinSet::new();
+    for module in installed {
+        let module_name = ModuleName::try_from(module.manifest.name)?;
+        let installer = installer.clone();
+        lookups.spawn(async move {
+            let latest = installer.fetch_latest_release(&module_name).await;
+            (module_name, module.version, latest)
+        });
+    }
+
+    let mut modules = Vec::with_capacity(lookups.len());
+    let mut failures = 0;
+    while let Some(lookup) = lookups.join_next().await {
+        let (module_name, installed, latest) = lookup?;
+        let latest = match latest {
+            Ok(latest) => Some(latest),
+            Err(e) => {
+                ceprintln!(
+                    "<s,y>warn:</> unable to find latest release for module <s>{module_name}</>: {e}"
+                );
+                failures += 1;
+                None
+            },
+        };
+        modules.push(OutdatedModule {
+            name: module_name.to_string(),
+            outdated: is_outdated(installed.as_deref(), latest.as_deref()),
+            installed,
+            latest,
+        });
+    }
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let lines = report(&modules, &output)?;
+    let table = !matches!(output.as_str(), "json" | "jsonl");
+    if table && lines.is_empty() && flags.verbose > 0 && failures == 0 {
+        cprintln!("<s,g>✓</> All installed modules are up to date.");
+    }
+    for line in lines {
+        println!("{line}");
+    }
+
+    if modules.iter().any(|m| m.outdated) {
+        return Err(EX_TEMPFAIL.into());
+    }
+    if failures > 0 {
+        return Err(EX_UNAVAILABLE.into());
+    }
+
+    Ok(())
+}
+
+/// The lines of the report in the given format. The table lists only the
+/// outdated modules, and is empty if there are none.
+fn report(modules: &[OutdatedModule], output: &str) -> serde_json::Result<Vec<String>> {
+    match output {
+        "json" => Ok(vec![serde_json::to_string_pretty(modules)?]),
+        "jsonl" => modules.iter().map(serde_json::to_string).collect(),
+        _ => {
+            let outdated: Vec<_> = modules.iter().filter(|m| m.outdated).collect();
+            if outdated.is_empty() {
+                return Ok(Vec::new());
+            }
+            let width = outdated.iter().map(|m| m.name.len()).max().unwrap_or(0);
+            let installed_width = outdated
+                .iter()
+                .map(|m| m.installed.as_deref().unwrap_or("unknown").len())
+                .max()
+                .unwrap_or(0)
+                .max("INSTALLED".len());
+
+            let mut lines = vec![cformat!(
+                "<s>{:width$}  {:installed_width$}  LATEST</>",
+                "NAME",
+                "INSTALLED"
+            )];
+            for module in outdated {
+                lines.push(cformat!(
+                    "{:width$}  {:installed_width$}  <s,g>{}</>",
+                    module.name,
+                    module.installed.as_deref().unwrap_or("unknown"),
+                    module.latest.as_deref().unwrap_or_default()
+                ));
+            }
+            Ok(lines)
+        },
+    }
+}
+
+/// Whether the latest release is newer than the installed one. A module
+/// installed without a recorded version counts as outdated.
+fn is_outdated(installed: Option<&str>, latest: Option<&str>) -> bool {
+    let Some(latest) = latest else {
+        return false;
+    };
+    let Some(installed) = installed else {
+        return true;
+    };
+    match (parse_release(installed), parse_release(latest)) {
+        (Some(installed), Some(latest)) => latest > installed,
+        _ => installed != latest,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_releases_as_semver() {
+        assert!(is_outdated(Some("0.9.0"), Some("0.10.0")));
+        assert!(!is_outdated(Some("v1.2.0"), Some("1.2.0")));
+        assert!(!is_outdated(Some("1.3.0"), Some("1.2.0")));
+    }
+
+    #[test]
+    fn reports_a_module_with_a_newer_release() {
+        let modules = [
+            OutdatedModule {
+                name: "demo".into(),
+                installed: Some("1.0.0".into()),
+                latest: Some("1.1.0".into()),
+                outdated: true,
+            },
+            OutdatedModule {
+                name: "current".into(),
+                installed: Some("2.0.0".into()),
+                latest: Some("2.0.0".into()),
+                outdated: false,
+            },
+        ];
+
+        let table = report(&modules, "cli").unwrap();
+        assert_eq!(table.len(), 2);
+        let row: Vec<&str> = table[1].split_whitespace().collect();
+        assert_eq!(row[0], "demo");
+        assert_eq!(row[1], "1.0.0");
+        assert!(row[2].contains("1.1.0"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&report(&modules, "json").unwrap().concat()).unwrap();
+        assert_eq!(
+            json[0],
+            serde_json::json!({
+                "name": "demo",
+                "installed": "1.0.0",
+                "latest": "1.1.0",
+                "outdated": true,
+            })
+        );
+        assert_eq!(json[1]["outdated"], false);
+    }
+
+    #[test]
+    fn treats_unknown_versions_conservatively() {
+        assert!(is_outdated(None, Some("1.0.0")));
+        assert!(!is_outdated(Some("1.0.0"), None));
+        assert!(is_outdated(Some("nightly-1"), Some("nightly-2")));
+    }
+}
@@ -0,0 +1,85 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, StandardOptions, SysexitsError::*, preferences::Preferences};
+use asimov_module::ModuleName;
+use color_print::{ceprintln, cprintln};
+
+/// Prefers a module for the URLs that a pattern matches, or lists the
+/// preferences without a pattern.
+pub async fn prefer(
+    pattern: Option<String>,
+    module_name: Option<ModuleName>,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let mut preferences = Preferences::load()?;
+
+    let (Some(pattern), Some(module_name)) = (pattern, module_name) else {
+        if preferences.preferences.is_empty() {
+            ceprintln!("<s,dim>note:</> No modules are preferred.");
+            ceprintln!(
+                "<s,dim>hint:</> Prefer a module with: <s>asimov module prefer <<pattern>> <<module>></>"
+            );
+        }
+        for preference in &preferences.preferences {
+            cprintln!(
+                "<s>{}</> {}",
+                preference.pattern,
+                preference.modules.join(", ")
+            );
+        }
+        return Ok(());
+    };
+
+    if pattern.trim().is_empty() || pattern.contains(char::is_whitespace) {
+        ceprintln!("<s,r>error:</> invalid pattern `{pattern}`");
+        ceprintln!(
+            "<s,dim>hint:</> Use a host such as `linkedin.com`, optionally with a path, or a URL prefix such as `https://example.org/api/`."
+        );
+        return Err(EX_USAGE.into());
+    }
+
+    let registry = asimov_registry::Registry::default();
+    if !registry
+        .is_module_installed(&module_name)
+        .await
+        .unwrap_or(false)
+    {
+        ceprintln!("<s,y>warn:</> Module <s>{module_name}</> is not installed.");
+    }
+
+    preferences.prefer(&pattern, &module_name);
+    preferences.save()?;
+
+    if flags.verbose > 0 {
+        cprintln!("<s,g>✓</> Preferred module <s>{module_name}</> for <s>{pattern}</>.");
+    }
+
+    Ok(())
+}
+
+/// Stops preferring a module, or else any module, for a pattern.
+pub async fn unprefer(
+    pattern: String,
+    module_name: Option<ModuleName>,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let mut preferences = Preferences::load()?;
+
+    if !preferences.unprefer(&pattern, module_name.as_deref()) {
+        match module_name {
+            Some(module_name) => ceprintln!(
+                "<s,r>error:</> module <s>{module_name}</> is not preferred for <s>{pattern}</>"
+            ),
+            None => ceprintln!("<s,r>error:</> no module is preferred for <s>{pattern}</>"),
+        }
+        ceprintln!("<s,dim>hint:</> List the preferences with: <s>asimov module prefer</>");
+        return Err(EX_USAGE.into());
+    }
+    preferences.save()?;
+
+    if flags.verbose > 0 {
+        cprintln!("<s,g>✓</> Updated the preferences for <s>{pattern}</>.");
+    }
+
+    Ok(())
+}
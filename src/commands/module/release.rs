@@ -0,0 +1,197 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::{
+    LocalModule, asset_names, extract_archive, fetch_module_manifest_source, fetch_readme,
+    find_readme, place_module,
+};
+use crate::{BoxError, registry::http::download_client};
+use asimov_module::{ModuleManifest, ModuleName, RequiredModel};
+use std::path::{Path, PathBuf};
+
+/// Installs a release of a module as published on GitHub, replacing the
+/// installed version, if any, once the release is ready to take its place.
+/// The modules it requires are left to the caller, which plans them.
+/// Every command that installs a release does so through here.
+pub(super) async fn install_release(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    version: &str,
+    model_size: Option<&str>,
+) -> Result<(), BoxError> {
+    registry.create_file_tree().await?;
+
+    // next to the installed modules, so that adding one is a rename
+    let work_dir = registry
+        .install_dir()
+        .with_file_name(format!(".release-{module_name}-{}", std::process::id()));
+
+    let result = install_release_in(registry, module_name, version, model_size, &work_dir).await;
+
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result
+}
+
+async fn install_release_in(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    version: &str,
+    model_size: Option<&str>,
+    work_dir: &Path,
+) -> Result<(), BoxError> {
+    tokio::fs::create_dir_all(work_dir).await?;
+
+    // kept as written, for what the SDK doesn't parse
+    let source = fetch_module_manifest_source(module_name, version)
+        .await
+        .map_err(|e| format!("unable to fetch the manifest: {e}"))?;
+    let manifest: ModuleManifest = serde_yml::from_str(&source)
+        .map_err(|e| format!("unable to deserialize the manifest: {e}"))?;
+    let manifest_path = work_dir.join("module.yaml");
+    tokio::fs::write(&manifest_path, &source).await?;
+
+    let archive = download_archive(module_name, version, work_dir).await?;
+    let root = work_dir.join("extract");
+    extract_archive(&archive, &root).await?;
+
+    install_models(module_name, &manifest, model_size).await?;
+
+    let readme = match find_readme(&[&root]) {
+        Some(readme) => Some(readme),
+        None => match fetch_readme(module_name, version).await {
+            Some(content) => {
+                let readme = work_dir.join("README.md");
+                tokio::fs::write(&readme, content).await?;
+                Some(readme)
+            },
+            None => None,
+        },
+    };
+
+    let was_enabled = registry
+        .is_module_enabled(module_name)
+        .await
+        .unwrap_or(false);
+    if registry
+        .is_module_installed(module_name)
+        .await
+        .unwrap_or(false)
+    {
+        asimov_installer::Installer::default()
+            .uninstall_module(module_name)
+            .await?;
+    }
+
+    place_module(
+        registry,
+        LocalModule {
+            manifest,
+            manifest_path,
+            version: Some(version.into()),
+            root,
+            readme,
+        },
+        work_dir,
+    )
+    .await?;
+
+    if was_enabled {
+        registry.enable_module(module_name).await?;
+    }
+
+    Ok(())
+}
+
+/// Downloads the release archive that suits this platform, verifying it
+/// against its published checksum if there is one.
+async fn download_archive(
+    module_name: &ModuleName,
+    version: &str,
+    work_dir: &Path,
+) -> Result<PathBuf, BoxError> {
+    let client = download_client();
+
+    let names = asset_names(module_name);
+    for name in &names {
+        let url = format!(
+            "https://github.com/asimov-modules/asimov-{module_name}-module/releases/download/{version}/{name}"
+        );
+
+        let response = client.get(&url).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            continue;
+        }
+        let content = response.error_for_status()?.bytes().await?;
+
+        // not every release publishes a checksum
+        let response = client.get(format!("{url}.sha256")).send().await?;
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            let checksum = response.error_for_status()?.text().await?;
+            let expected = checksum.split_whitespace().next().unwrap_or_default();
+            let actual = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&content));
+            if !actual.eq_ignore_ascii_case(expected) {
+                Err(format!(
+                    "checksum of `{name}` does not match: expected {expected}, got {actual}"
+                ))?;
+            }
+        }
+
+        let path = work_dir.join(name);
+        tokio::fs::write(&path, &content).await?;
+        return Ok(path);
+    }
+
+    Err(format!(
+        "no release archive for this platform, expected one of: {}",
+        names.join(", ")
+    ))?
+}
+
+/// Downloads the models that a module requires, choosing among sizes: the
+/// one asked for, else any already downloaded, else the first.
+async fn install_models(
+    module_name: &ModuleName,
+    manifest: &ModuleManifest,
+    model_size: Option<&str>,
+) -> Result<(), BoxError> {
+    for (name, model) in &manifest.requires.models {
+        let Some(repo) = name.strip_prefix("hf:") else {
+            tracing::debug!(
+                ?name,
+                "unexpected format for required model, only `hf:<user>/<repo>` is supported"
+            );
+            continue;
+        };
+
+        let filename = match (model, model_size) {
+            (RequiredModel::Url(url), _) => url.clone(),
+            (RequiredModel::Choices(choices), None) => {
+                // without a size asked for, any choice already downloaded will do
+                if choices
+                    .iter()
+                    .any(|(_, file)| asimov_huggingface::file_exists(repo, file).is_some())
+                {
+                    continue;
+                }
+                let Some((_, file)) = choices.first() else {
+                    tracing::warn!(
+                        ?module_name,
+                        "manifest defines required models with no choices"
+                    );
+                    continue;
+                };
+                file.clone()
+            },
+            (RequiredModel::Choices(choices), Some(size)) => choices
+                .iter()
+                .find(|(choice, _)| choice == size)
+                .map(|(_, file)| file.clone())
+                .ok_or_else(|| format!("no model of size `{size}` for `{name}`"))?,
+        };
+
+        let repo = repo.to_string();
+        tokio::task::spawn_blocking(move || asimov_huggingface::ensure_file(&repo, &filename))
+            .await??;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+// This is free and unencumbered software released into the public domain.
+
+use asimov_module::{ConfigurationVariable, ModuleManifest};
+use color_print::cprintln;
+
+/// Prints a module's label, title, and summary, whichever it has.
+pub(super) fn print_description(manifest: &ModuleManifest) {
+    if let Some(label) = &manifest.label {
+        cprintln!("<s>Label:</> {label}");
+    }
+    if let Some(title) = &manifest.title {
+        cprintln!("<s>Title:</> {title}");
+    }
+    if let Some(summary) = &manifest.summary {
+        cprintln!("<s>Summary:</> {summary}");
+    }
+}
+
+/// Prints a module's links, the programs it provides, and what it handles.
+pub(super) fn print_capabilities(manifest: &ModuleManifest) {
+    if !manifest.links.is_empty() {
+        let mut links = manifest.links.clone();
+        crate::sort_links(&manifest.name, &mut links);
+        cprintln!("<s>Links:</>");
+        for link in links {
+            println!("  {link}");
+        }
+    }
+
+    if !manifest.provides.is_empty() {
+        cprintln!("<s>Programs:</>");
+        for program in &manifest.provides.programs {
+            println!("  {program}");
+        }
+    }
+
+    if !manifest.handles.is_empty() {
+        cprintln!("<s>Handles:</>");
+        for (kind, values) in [
+            ("URL protocols", &manifest.handles.url_protocols),
+            ("URL prefixes", &manifest.handles.url_prefixes),
+            ("URL patterns", &manifest.handles.url_patterns),
+            ("file extensions", &manifest.handles.file_extensions),
+            ("content types", &manifest.handles.content_types),
+        ] {
+            if !values.is_empty() {
+                println!("  {kind}: {}", values.join(", "));
+            }
+        }
+    }
+}
+
+/// Prints a module's configuration variables, each with whether it is set
+/// if that is known, and otherwise with whether it is required.
+pub(super) fn print_configuration(vars: &[(&ConfigurationVariable, Option<bool>)]) {
+    cprintln!("<s>Configuration:</>");
+    if vars.is_empty() {
+        println!("  no configuration variables declared");
+        return;
+    }
+
+    for (var, set) in vars {
+        match set {
+            Some(true) => cprintln!("  <s,g>✓</> <s>{}</> (set)", var.name),
+            Some(false) if var.is_required() => {
+                cprintln!("  <s,r>✗</> <s>{}</> (required)", var.name)
+            },
+            Some(false) => cprintln!("  <dim>-</> <s>{}</> (unset)", var.name),
+            None if var.is_required() => cprintln!("  <s>{}</> (required)", var.name),
+            None => cprintln!("  <s>{}</> (optional)", var.name),
+        }
+        if let Some(description) = &var.description {
+            println!("      {description}");
+        }
+        if let Some(default_value) = &var.default_value {
+            if var.secret {
+                println!("      default: ******");
+            } else {
+                println!("      default: {default_value}");
+            }
+        }
+    }
+}
+
+/// A configuration variable as `-o json` shows it, with whether it is set
+/// if that is known. Secret defaults are left out.
+pub(super) fn variable_json(var: &ConfigurationVariable, set: Option<bool>) -> serde_json::Value {
+    let mut json = serde_json::json!({
+        "name": var.name,
+        "description": var.description,
+        "default": var.default_value.as_deref().filter(|_| !var.secret),
+        "secret": var.secret,
+        "required": var.is_required(),
+    });
+    if let Some(set) = set {
+        json["set"] = set.into();
+    }
+    json
+}
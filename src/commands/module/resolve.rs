@@ -1,10 +1,67 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions, SysexitsError::*};
-use asimov_module::{normalization::normalize_url, resolve::Resolver};
-use color_print::cprintln;
+use crate::{BoxError, StandardOptions, SysexitsError::*, preferences::Preferences, shared};
+use asimov_module::{
+    ModuleManifest,
+    normalization::normalize_url,
+    resolve::{Module, Resolver},
+};
+use color_print::{ceprintln, cprintln};
+use serde::Serialize;
+use std::{collections::BTreeMap, rc::Rc};
+
+/// The commands that pick a module for a URL, and the kind of program that
+/// each of them runs.
+const OPERATIONS: [(&str, &str); 3] = [
+    ("fetch", "fetcher"),
+    ("read", "reader"),
+    ("list", "cataloger"),
+];
+
+#[derive(Debug, Serialize)]
+struct Explanation {
+    url: String,
+    candidates: Vec<Candidate>,
+    /// The module each of `fetch`, `read`, and `list` would use.
+    chosen: BTreeMap<&'static str, Option<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Candidate {
+    name: String,
+    /// The rank among the candidates, 1 being tried first.
+    priority: usize,
+    enabled: bool,
+    /// The preference pattern that puts this module ahead of the others.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preferred_for: Option<String>,
+    rules: Vec<Rule>,
+    programs: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Rule {
+    kind: &'static str,
+    rule: String,
+}
+
+pub async fn resolve(
+    url: impl AsRef<str>,
+    explain: bool,
+    output: String,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let input = url.as_ref().to_string();
+    let url = normalize_url(&input)
+        .inspect_err(|e| {
+            tracing::error!("proceeding with given unmodified URL, normalization failed: {e}, ")
+        })
+        .unwrap_or_else(|_| input.clone());
+
+    if explain || output == "json" {
+        return explain_resolution(input, url, output, flags).await;
+    }
 
-pub async fn resolve(url: impl AsRef<str>, _flags: &StandardOptions) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
 
     let manifests = registry
@@ -20,21 +77,241 @@ pub async fn resolve(url: impl AsRef<str>, _flags: &StandardOptions) -> Result<(
     let resolver = Resolver::try_from_iter(manifests)
         .inspect_err(|e| tracing::error!("failed to create resolver: {e}"))?;
 
-    let url = url.as_ref().to_string();
-    let url = normalize_url(&url)
-        .inspect_err(|e| {
-            tracing::error!("proceeding with given unmodified URL, normalization failed: {e}, ")
-        })
-        .unwrap_or(url);
-
     let modules = resolver
         .resolve(&url)
         .inspect_err(|e| tracing::error!("failed to resolve modules for URL <s>{url}</>: {e}"))
         .map_err(|_| EX_USAGE)?;
 
-    for module in modules {
+    for module in Preferences::load()?.order(&url, &modules) {
         cprintln!("{}", module.name);
     }
 
     Ok(())
 }
+
+/// Shows every installed module that handles a URL, whether enabled or not,
+/// with the rules that matched it, and which of them each command would use.
+/// The input is the URL as given, which `read` also resolves by the content
+/// type of a local file.
+async fn explain_resolution(
+    input: String,
+    url: String,
+    output: String,
+    flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+
+    let manifests: BTreeMap<String, ModuleManifest> = registry
+        .installed_modules()
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to get installed modules: {e}");
+            EX_UNAVAILABLE
+        })?
+        .into_iter()
+        .map(|module| (module.manifest.name.clone(), module.manifest))
+        .collect();
+
+    let resolver = Resolver::try_from_iter(manifests.values())
+        .inspect_err(|e| tracing::error!("failed to create resolver: {e}"))?;
+
+    let resolved = resolver
+        .resolve(&url)
+        .inspect_err(|e| tracing::error!("failed to resolve modules for URL <s>{url}</>: {e}"))
+        .map_err(|_| EX_USAGE)?;
+
+    // as `source read` resolves its inputs, which may add modules by
+    // content type
+    let readable = shared::read_candidates(&resolver, &input, flags);
+    let content_type = shared::content_type(&input, flags);
+
+    let preferences = Preferences::load()?;
+    let preference = preferences.matching(&url);
+    let modules = preferences.order(&url, &readable);
+
+    let mut candidates: Vec<Candidate> = Vec::with_capacity(modules.len());
+    for module in &modules {
+        // a module that matches in several ways is listed for each of them
+        if candidates
+            .iter()
+            .any(|candidate| candidate.name == module.name)
+        {
+            continue;
+        }
+        let Some(manifest) = manifests.get(&module.name) else {
+            continue;
+        };
+        let enabled = match module.name.parse() {
+            Ok(module_name) => registry
+                .is_module_enabled(&module_name)
+                .await
+                .unwrap_or(false),
+            Err(_) => false,
+        };
+        candidates.push(Candidate {
+            name: module.name.clone(),
+            priority: candidates.len() + 1,
+            enabled,
+            preferred_for: preference
+                .filter(|preference| preference.modules.contains(&module.name))
+                .map(|preference| preference.pattern.clone()),
+            rules: matching_rules(manifest, &url, content_type.as_deref()),
+            programs: OPERATIONS
+                .iter()
+                .map(|(_, program)| program.to_string())
+                .filter(|program| provides(manifest, program))
+                .collect(),
+        });
+    }
+
+    // as `source fetch`, `read`, and `list` pick theirs, among the modules
+    // that provide their program
+    let mut chosen = BTreeMap::new();
+    for (operation, program) in OPERATIONS {
+        let (url, modules) = match operation {
+            "read" => (&input, &readable),
+            _ => (&url, &resolved),
+        };
+        let providing: Vec<Rc<Module>> = modules
+            .iter()
+            .filter(|module| {
+                manifests
+                    .get(&module.name)
+                    .is_some_and(|manifest| provides(manifest, program))
+            })
+            .cloned()
+            .collect();
+        let picked = shared::enabled_candidates(&registry, url, &providing).await?;
+        chosen.insert(operation, picked.first().map(|module| module.name.clone()));
+    }
+
+    let explanation = Explanation {
+        url,
+        candidates,
+        chosen,
+    };
+
+    match output.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&explanation)?),
+        _ => print_explanation(&explanation),
+    }
+
+    Ok(())
+}
+
+fn print_explanation(explanation: &Explanation) {
+    if explanation.candidates.is_empty() {
+        ceprintln!(
+            "<s,dim>note:</> No installed module handles the URL <s>{}</>.",
+            explanation.url
+        );
+        return;
+    }
+
+    cprintln!(
+        "Modules that handle <s>{}</>, in order of priority:",
+        explanation.url
+    );
+    for candidate in &explanation.candidates {
+        let state = if candidate.enabled {
+            color_print::cformat!("<g>enabled</>")
+        } else {
+            color_print::cformat!("<dim>disabled</>")
+        };
+        cprintln!(
+            "{:>4}. <s>{}</> ({state})",
+            candidate.priority,
+            candidate.name
+        );
+        if let Some(pattern) = &candidate.preferred_for {
+            cprintln!("        preferred for: {pattern}");
+        }
+        for rule in &candidate.rules {
+            cprintln!("        matches {}: {}", rule.kind, rule.rule);
+        }
+        if !candidate.programs.is_empty() {
+            cprintln!("        provides: {}", candidate.programs.join(", "));
+        }
+    }
+
+    println!();
+    for (operation, module) in &explanation.chosen {
+        match module {
+            Some(module) => cprintln!("{operation:<5} uses <s>{module}</>"),
+            None => cprintln!("{operation:<5} <dim>has no enabled module to use</>"),
+        }
+    }
+}
+
+/// Finds the rules of a module's manifest that match a URL, or the content
+/// type of the local file it names, by trying each rule on its own.
+fn matching_rules(manifest: &ModuleManifest, url: &str, content_type: Option<&str>) -> Vec<Rule> {
+    let handles = &manifest.handles;
+    let rules = handles
+        .url_protocols
+        .iter()
+        .map(|rule| ("url_protocols", rule))
+        .chain(
+            handles
+                .url_prefixes
+                .iter()
+                .map(|rule| ("url_prefixes", rule)),
+        )
+        .chain(
+            handles
+                .url_patterns
+                .iter()
+                .map(|rule| ("url_patterns", rule)),
+        )
+        .chain(
+            handles
+                .file_extensions
+                .iter()
+                .map(|rule| ("file_extensions", rule)),
+        )
+        .chain(
+            handles
+                .content_types
+                .iter()
+                .map(|rule| ("content_types", rule)),
+        );
+
+    rules
+        .filter(|(kind, rule)| {
+            let mut resolver = Resolver::new();
+            if *kind == "content_types" {
+                let (Ok(rule), Some(Ok(content_type))) =
+                    (rule.parse(), content_type.map(str::parse))
+                else {
+                    return false;
+                };
+                let _ = resolver.insert_content_type(&manifest.name, rule);
+                return !resolver.resolve_content_type(&content_type).is_empty();
+            }
+            let inserted = match *kind {
+                "url_protocols" => resolver.insert_protocol(&manifest.name, rule).is_ok(),
+                "url_prefixes" => resolver.insert_prefix(&manifest.name, rule).is_ok(),
+                "url_patterns" => resolver.insert_pattern(&manifest.name, rule).is_ok(),
+                _ => resolver.insert_file_extension(&manifest.name, rule).is_ok(),
+            };
+            inserted
+                && resolver
+                    .resolve(url)
+                    .is_ok_and(|modules| !modules.is_empty())
+        })
+        .map(|(kind, rule)| Rule {
+            kind,
+            rule: rule.clone(),
+        })
+        .collect()
+}
+
+/// Whether a module provides a program of the given kind, as
+/// `shared::installed_modules` tells them apart.
+fn provides(manifest: &ModuleManifest, program: &str) -> bool {
+    manifest
+        .provides
+        .programs
+        .iter()
+        .any(|name| name.split('-').next_back() == Some(program))
+}
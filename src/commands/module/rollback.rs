@@ -0,0 +1,259 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, StandardOptions, SysexitsError::*};
+use asimov_module::{InstalledModuleManifest, ModuleName};
+use color_print::{ceprintln, cprintln};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// How many previously installed versions are kept for each module.
+pub const RETAINED_VERSIONS: usize = 3;
+
+/// How a module installed without a version is named.
+const UNKNOWN_VERSION: &str = "unknown";
+
+/// The file in each kept version that records when it was kept, as a
+/// number that grows with every version kept of the module.
+const ORDER_FILE_NAME: &str = ".order";
+
+/// Reinstalls the most recent previously installed version of a module,
+/// keeping the current version so that the rollback can itself be undone.
+pub async fn rollback(module_name: ModuleName, flags: &StandardOptions) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+
+    let current = registry.module_version(&module_name).await.map_err(|e| {
+        tracing::error!("failed to read installed version of `{module_name}`: {e}");
+        ceprintln!(
+            "<s,dim>hint:</> Check if the module is installed with: <s>asimov module list</>"
+        );
+        EX_UNAVAILABLE
+    })?;
+    let saved = saved_versions(&registry, &module_name).await.map_err(|e| {
+        tracing::error!("failed to read previous versions of `{module_name}`: {e}");
+        EX_IOERR
+    })?;
+
+    // versions without a version number can't be told apart, so any of them
+    // counts as a previous version
+    let Some((previous, previous_dir)) = saved
+        .into_iter()
+        .find(|(version, _)| version.is_none() || *version != current)
+    else {
+        ceprintln!("<s,r>error:</> no previous version of module <s>{module_name}</> is available");
+        return Err(EX_UNAVAILABLE.into());
+    };
+
+    let current = current.as_deref().unwrap_or(UNKNOWN_VERSION);
+    let previous = previous.as_deref().unwrap_or(UNKNOWN_VERSION);
+    if flags.verbose > 1 {
+        cprintln!(
+            "<s,c>»</> Rolling back module <s>{module_name}</> from version <s>{current}</> to <s>{previous}</>..."
+        );
+    }
+
+    let was_enabled = registry
+        .is_module_enabled(&module_name)
+        .await
+        .unwrap_or(false);
+
+    save_version(&registry, &module_name).await.map_err(|e| {
+        tracing::error!("failed to keep version `{current}` of `{module_name}`: {e}");
+        EX_IOERR
+    })?;
+
+    restore_version(&registry, &module_name, &previous_dir, was_enabled).await?;
+
+    tokio::fs::remove_dir_all(&previous_dir)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to remove `{}`: {e}", previous_dir.display());
+            EX_IOERR
+        })?;
+
+    if flags.verbose > 0 {
+        cprintln!("<s,g>✓</> Rolled back module <s>{module_name}</> to version <s>{previous}</>.");
+    }
+
+    Ok(())
+}
+
+/// Keeps a copy of the installed version of a module aside, so that it can
+/// be restored later, and discards the oldest copies beyond
+/// [`RETAINED_VERSIONS`]. A copy of the same version kept before is
+/// replaced; versions without a version number are each kept apart.
+pub(super) async fn save_version(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> io::Result<PathBuf> {
+    let version = registry.module_version(module_name).await.ok().flatten();
+
+    let order = saved_entries(registry, module_name)
+        .await?
+        .iter()
+        .map(|entry| entry.order)
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    let dir_name = version.unwrap_or_else(|| format!("{UNKNOWN_VERSION}-{order}"));
+    let saved_dir = history_dir(registry, module_name).join(dir_name);
+    if tokio::fs::try_exists(&saved_dir).await? {
+        tokio::fs::remove_dir_all(&saved_dir).await?;
+    }
+
+    let module_dir = registry.module_dir(module_name);
+    let copy_to = saved_dir.clone();
+    tokio::task::spawn_blocking(move || copy_dir(&module_dir, &copy_to)).await??;
+    tokio::fs::write(saved_dir.join(ORDER_FILE_NAME), order.to_string()).await?;
+
+    let saved = saved_versions(registry, module_name).await?;
+    for (_, dir) in saved.iter().skip(RETAINED_VERSIONS) {
+        tokio::fs::remove_dir_all(dir).await?;
+    }
+
+    Ok(saved_dir)
+}
+
+/// Replaces whatever is installed for a module, possibly a half-finished
+/// upgrade, with a copy kept aside by [`save_version`].
+pub(super) async fn restore_version(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    saved_dir: &Path,
+    enable: bool,
+) -> Result<(), BoxError> {
+    let installer = asimov_installer::Installer::default();
+
+    // copy next to the installed modules, so that adding it is a rename
+    let work_dir = registry
+        .install_dir()
+        .with_file_name(format!(".{module_name}-restore"));
+    if tokio::fs::try_exists(&work_dir).await? {
+        tokio::fs::remove_dir_all(&work_dir).await?;
+    }
+    let (from, to) = (saved_dir.to_path_buf(), work_dir.clone());
+    tokio::task::spawn_blocking(move || copy_dir(&from, &to))
+        .await?
+        .map_err(|e| {
+            tracing::error!("failed to copy `{}`: {e}", saved_dir.display());
+            EX_IOERR
+        })?;
+    match tokio::fs::remove_file(work_dir.join(ORDER_FILE_NAME)).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => (),
+    }
+
+    if installer.uninstall_module(module_name).await.is_err() {
+        // whatever is left isn't a complete module, so remove it by hand
+        let _ = registry.disable_module(module_name).await;
+        match tokio::fs::remove_dir_all(registry.module_dir(module_name)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                tracing::error!("failed to remove module `{module_name}`: {e}");
+                return Err(EX_IOERR.into());
+            },
+            _ => (),
+        }
+    }
+
+    registry
+        .add_module(module_name, &work_dir)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to restore module `{module_name}`: {e}");
+            EX_UNAVAILABLE
+        })?;
+
+    if enable {
+        registry.enable_module(module_name).await.map_err(|e| {
+            tracing::error!("failed to enable module `{module_name}`: {e}");
+            EX_UNAVAILABLE
+        })?;
+    }
+
+    Ok(())
+}
+
+fn history_dir(registry: &asimov_registry::Registry, module_name: &ModuleName) -> PathBuf {
+    registry
+        .install_dir()
+        .with_file_name("history")
+        .join(module_name.as_str())
+}
+
+/// A version kept aside for a module.
+struct SavedEntry {
+    /// When it was kept, relative to the others; those kept before the
+    /// order was recorded come first.
+    order: u64,
+    version: Option<String>,
+    path: PathBuf,
+}
+
+/// Lists the versions kept aside for a module, with the version each is of,
+/// if it has one, most recently kept first.
+async fn saved_versions(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> io::Result<Vec<(Option<String>, PathBuf)>> {
+    let mut saved = saved_entries(registry, module_name).await?;
+    saved.sort_by(|a, b| (b.order, &b.path).cmp(&(a.order, &a.path)));
+
+    Ok(saved
+        .into_iter()
+        .map(|entry| (entry.version, entry.path))
+        .collect())
+}
+
+async fn saved_entries(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> io::Result<Vec<SavedEntry>> {
+    let mut entries = match tokio::fs::read_dir(history_dir(registry, module_name)).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut saved = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+
+        let order = tokio::fs::read_to_string(path.join(ORDER_FILE_NAME))
+            .await
+            .ok()
+            .and_then(|order| order.trim().parse().ok())
+            .unwrap_or(0);
+        let version = tokio::fs::read(path.join(asimov_registry::MANIFEST_FILE_NAME))
+            .await
+            .ok()
+            .and_then(|manifest| serde_json::from_slice::<InstalledModuleManifest>(&manifest).ok())
+            .and_then(|manifest| manifest.version);
+
+        saved.push(SavedEntry {
+            order,
+            version,
+            path,
+        });
+    }
+
+    Ok(saved)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
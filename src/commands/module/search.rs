@@ -1,35 +1,232 @@
 // This is free and unencumbered software released into the public domain.
 
+use super::{IndexSource, module_index};
 use crate::{BoxError, StandardOptions, SysexitsError::*};
+use asimov_module::ModuleManifest;
 use color_print::{ceprintln, cprintln};
 
+/// A search query: plain terms to rank modules by, and `field:value`
+/// filters that every module found must match.
+#[derive(Debug, Default)]
+struct Query {
+    terms: Vec<String>,
+    filters: Vec<(Field, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    /// The kinds of programs provided, as in `provides:fetcher`.
+    Provides,
+    /// The URLs, files, and content types handled, as in `handles:youtube.com`.
+    Handles,
+    /// The package ecosystem, as in `type:python`.
+    Type,
+    Tag,
+}
+
+impl Query {
+    fn parse(words: &[String]) -> Self {
+        let mut query = Self::default();
+        for word in words.iter().flat_map(|word| word.split_whitespace()) {
+            let word = word.to_lowercase();
+            let filter = word.split_once(':').and_then(|(field, value)| {
+                let field = match field {
+                    "provides" => Field::Provides,
+                    "handles" => Field::Handles,
+                    "type" => Field::Type,
+                    "tag" => Field::Tag,
+                    _ => return None,
+                };
+                Some((field, value.to_string()))
+            });
+            match filter {
+                Some(filter) => query.filters.push(filter),
+                None => query.terms.push(word),
+            }
+        }
+        query
+    }
+
+    fn accepts(&self, module: &ModuleManifest) -> bool {
+        self.filters.iter().all(|(field, value)| {
+            let value = value.as_str();
+            match field {
+                Field::Provides => module.provides.programs.iter().any(|program| {
+                    let program = program.to_lowercase();
+                    program.rsplit('-').next() == Some(value) || program.contains(value)
+                }),
+                Field::Handles => {
+                    let handles = &module.handles;
+                    [
+                        &handles.url_protocols,
+                        &handles.url_prefixes,
+                        &handles.url_patterns,
+                        &handles.file_extensions,
+                        &handles.content_types,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .any(|handle| handle.to_lowercase().contains(value))
+                },
+                Field::Type => module_types(module).contains(&value),
+                Field::Tag => module
+                    .tags
+                    .iter()
+                    .any(|tag| tag.eq_ignore_ascii_case(value)),
+            }
+        })
+    }
+
+    /// Scores how well a module matches the plain terms, by the most
+    /// significant field that each term occurs in, or `None` if none of
+    /// them occurs anywhere.
+    fn score(&self, module: &ModuleManifest) -> Option<u32> {
+        if self.terms.is_empty() {
+            return Some(0);
+        }
+
+        let name = module.name.to_lowercase();
+        let text = |field: &Option<String>| field.as_deref().unwrap_or_default().to_lowercase();
+        let (label, title, summary) = (
+            text(&module.label),
+            text(&module.title),
+            text(&module.summary),
+        );
+        let handles = &module.handles;
+        let lists = |lists: &[&Vec<String>], term: &str| {
+            lists
+                .iter()
+                .copied()
+                .flatten()
+                .any(|item| item.to_lowercase().contains(term))
+        };
+
+        let mut score = 0;
+        let mut matched = 0;
+        for term in &self.terms {
+            let term = term.as_str();
+            let term_score = if name == term {
+                100
+            } else if name.starts_with(term) {
+                40
+            } else if name.contains(term) {
+                25
+            } else if label.contains(term) || title.contains(term) {
+                15
+            } else if module.tags.iter().any(|tag| tag.eq_ignore_ascii_case(term)) {
+                12
+            } else if summary.contains(term) {
+                8
+            } else if lists(
+                &[
+                    &module.provides.programs,
+                    &handles.url_protocols,
+                    &handles.url_prefixes,
+                    &handles.url_patterns,
+                    &handles.file_extensions,
+                    &handles.content_types,
+                ],
+                term,
+            ) {
+                6
+            } else if lists(&[&module.links], term) {
+                2
+            } else {
+                0
+            };
+            if term_score > 0 {
+                matched += 1;
+                score += term_score;
+            }
+        }
+
+        // modules that match every term come before those that match some
+        (matched > 0).then(|| score + if matched == self.terms.len() { 50 } else { 0 })
+    }
+}
+
+/// The package registries that modules are published in, and the ecosystem
+/// each of them stands for, in the order that a module published in several
+/// is attributed to one of them.
+const ECOSYSTEMS: [(&str, &str); 4] = [
+    ("pypi.org", "python"),
+    ("rubygems.org", "ruby"),
+    ("crates.io", "rust"),
+    ("npmjs.com", "javascript"),
+];
+
+/// The package ecosystems that a module's links point into, by the host of
+/// each link, in the order of [`ECOSYSTEMS`].
+pub(super) fn link_ecosystems(links: &[String]) -> Vec<&'static str> {
+    let hosts: Vec<String> = links
+        .iter()
+        .filter_map(|link| reqwest::Url::parse(link).ok())
+        .filter_map(|url| url.host_str().map(str::to_string))
+        .collect();
+
+    ECOSYSTEMS
+        .into_iter()
+        .filter(|(registry, _)| {
+            hosts.iter().any(|host| {
+                host.strip_suffix(registry)
+                    .is_some_and(|subdomain| subdomain.is_empty() || subdomain.ends_with('.'))
+            })
+        })
+        .map(|(_, ecosystem)| ecosystem)
+        .collect()
+}
+
+/// The package ecosystems a module is published in, judging by its links and
+/// tags.
+fn module_types(module: &ModuleManifest) -> Vec<&'static str> {
+    let linked = link_ecosystems(&module.links);
+
+    ECOSYSTEMS
+        .into_iter()
+        .map(|(_, ecosystem)| ecosystem)
+        .filter(|ecosystem| {
+            linked.contains(ecosystem)
+                || module
+                    .tags
+                    .iter()
+                    .any(|tag| tag.eq_ignore_ascii_case(ecosystem))
+        })
+        .collect()
+}
+
 pub async fn search(
     query: Vec<String>,
     output: String,
+    offline: bool,
+    refresh: bool,
     _flags: &StandardOptions,
 ) -> Result<(), BoxError> {
-    let index = asimov_module::Index::fetch().await.map_err(|e| {
-        tracing::error!("failed to fetch the module index: {e}");
-        EX_UNAVAILABLE
-    })?;
+    let index = module_index(IndexSource::new(offline, refresh)).await?;
 
-    let query = query.join(" ");
-    let modules: Vec<_> = index.search(&query).collect();
+    let parsed = Query::parse(&query);
+    let mut modules: Vec<(u32, &ModuleManifest)> = index
+        .modules()
+        .iter()
+        .filter(|module| parsed.accepts(module))
+        .filter_map(|module| Some((parsed.score(module)?, module)))
+        .collect();
+    // the sort is stable, so equally relevant modules stay in index order
+    modules.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
 
     if modules.is_empty() {
         if output != "jsonl" {
-            ceprintln!("<s,y>!</> No modules matched <s>{query}</>.");
+            ceprintln!("<s,y>!</> No modules matched <s>{}</>.", query.join(" "));
         }
         return Ok(());
     }
 
     let width = modules
         .iter()
-        .map(|module| module.name.len())
+        .map(|(_, module)| module.name.len())
         .max()
         .unwrap_or_default();
 
-    for module in modules {
+    for (_, module) in modules {
         match output.as_str() {
             "jsonl" => {
                 let json = serde_json::to_string(module).map_err(|e| {
@@ -48,3 +245,73 @@ pub async fn search(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(json: serde_json::Value) -> ModuleManifest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn query(query: &str) -> Query {
+        Query::parse(&[query.to_string()])
+    }
+
+    #[test]
+    fn filters_by_field() {
+        let youtube = manifest(serde_json::json!({
+            "name": "youtube",
+            "links": ["https://pypi.org/project/asimov-youtube-module"],
+            "provides": { "programs": ["asimov-youtube-fetcher"] },
+            "handles": { "url_prefixes": ["https://youtube.com/"] }
+        }));
+
+        assert!(query("provides:fetcher handles:youtube.com type:python").accepts(&youtube));
+        assert!(!query("provides:reader").accepts(&youtube));
+        assert!(!query("type:rust").accepts(&youtube));
+        assert!(
+            query("https://youtube.com").filters.is_empty(),
+            "a URL isn't a filter"
+        );
+    }
+
+    #[test]
+    fn ranks_names_above_summaries() {
+        let web = manifest(serde_json::json!({ "name": "web", "summary": "Fetch web pages." }));
+        let other = manifest(serde_json::json!({
+            "name": "other",
+            "summary": "Not for the web, for other things."
+        }));
+
+        let query = query("web");
+        assert!(query.score(&web) > query.score(&other));
+        assert_eq!(
+            query.score(&manifest(serde_json::json!({ "name": "x" }))),
+            None
+        );
+    }
+
+    #[test]
+    fn tells_ecosystems_by_whole_link_hosts() {
+        let links = |links: &[&str]| {
+            links
+                .iter()
+                .map(|link| link.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            link_ecosystems(&links(&[
+                "https://crates.io/crates/asimov-web-module",
+                "https://pypi.org/project/asimov-web-module",
+            ])),
+            ["python", "rust"]
+        );
+        assert_eq!(
+            link_ecosystems(&links(&["https://www.npmjs.com/package/asimov"])),
+            ["javascript"]
+        );
+        assert!(link_ecosystems(&links(&["https://notcrates.io/crates.io"])).is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, SysexitsError::*, registry::http::download_client};
+use asimov_module::ModuleName;
+use color_print::ceprintln;
+use serde::Deserialize;
+
+/// A module name optionally pinned to a version, as in `serpapi@1.2.0` or
+/// `brightdata@^0.4`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleSpec {
+    pub name: ModuleName,
+    pub version: Option<VersionPin>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionPin {
+    /// An exact release, such as `1.2.0`.
+    Exact(String),
+    /// A semver range, such as `^0.4` or `>=1.0, <1.3`.
+    Range(semver::VersionReq),
+}
+
+impl core::str::FromStr for ModuleSpec {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match input.split_once('@') {
+            Some((name, version)) => (name, Some(version.parse()?)),
+            None => (input, None),
+        };
+
+        let name = ModuleName::try_from(name.to_string()).map_err(|e| e.to_string())?;
+
+        Ok(Self { name, version })
+    }
+}
+
+impl core::str::FromStr for VersionPin {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err("missing version after `@`".into());
+        }
+
+        if semver::Version::parse(input.strip_prefix('v').unwrap_or(input)).is_ok() {
+            return Ok(Self::Exact(input.into()));
+        }
+
+        semver::VersionReq::parse(input)
+            .map(Self::Range)
+            .map_err(|e| format!("invalid version requirement `{input}`: {e}"))
+    }
+}
+
+impl core::fmt::Display for ModuleSpec {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.version {
+            Some(VersionPin::Exact(version)) => write!(f, "{}@{version}", self.name),
+            Some(VersionPin::Range(req)) => write!(f, "{}@{req}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl VersionPin {
+    /// Whether a release satisfies this pin. Release names that aren't semver
+    /// only ever match an identical exact pin.
+    pub fn matches(&self, release: &str) -> bool {
+        match self {
+            VersionPin::Exact(version) => {
+                version == release || parse_release(version) == parse_release(release)
+            },
+            VersionPin::Range(req) => parse_release(release).is_some_and(|v| req.matches(&v)),
+        }
+    }
+}
+
+/// Determines the release to install for a module: the pinned one, the one
+/// given with `--version`, or else the latest release. A range resolves to
+/// the highest release that satisfies it.
+pub(super) async fn resolve_version(
+    installer: &asimov_installer::Installer,
+    module_name: &ModuleName,
+    pin: Option<&VersionPin>,
+    default: Option<&str>,
+) -> Result<String, BoxError> {
+    let fetch_latest = || async {
+        installer
+            .fetch_latest_release(module_name)
+            .await
+            .map_err(|e| {
+                tracing::error!("unable to find latest release for module `{module_name}`: {e}");
+                EX_UNAVAILABLE
+            })
+    };
+
+    let req = match pin {
+        Some(VersionPin::Exact(version)) => return Ok(version.clone()),
+        Some(VersionPin::Range(req)) => req,
+        None => {
+            return match default {
+                Some(version) => Ok(version.into()),
+                None => Ok(fetch_latest().await?),
+            };
+        },
+    };
+
+    // the latest release usually satisfies the range, which saves listing them all
+    let latest = fetch_latest().await?;
+    if VersionPin::Range(req.clone()).matches(&latest) {
+        return Ok(latest);
+    }
+
+    let releases = fetch_releases(module_name).await.map_err(|e| {
+        tracing::error!("unable to list releases for module `{module_name}`: {e}");
+        EX_UNAVAILABLE
+    })?;
+
+    highest_matching(&releases, req).ok_or_else(|| {
+        ceprintln!("<s,r>error:</> no release of module <s>{module_name}</> satisfies <s>{req}</>");
+        if !releases.is_empty() {
+            ceprintln!(
+                "<s,dim>hint:</> Available releases: {}",
+                releases.join(", ")
+            );
+        }
+        EX_UNAVAILABLE.into()
+    })
+}
+
+/// Lists the published releases of a module, by their tag names.
+pub async fn fetch_releases(module_name: &ModuleName) -> Result<Vec<String>, BoxError> {
+    #[derive(Deserialize)]
+    struct GitHubRelease {
+        tag_name: String,
+        #[serde(default)]
+        draft: bool,
+    }
+
+    let url = format!(
+        "https://api.github.com/repos/asimov-modules/asimov-{module_name}-module/releases?per_page=100"
+    );
+
+    let client = download_client();
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("request failed: {e}"))?;
+
+    if !response.status().is_success() {
+        Err(format!(
+            "HTTP status code was not successful: {0}",
+            response.status()
+        ))?;
+    }
+
+    let releases: Vec<GitHubRelease> = response
+        .json()
+        .await
+        .map_err(|e| format!("unable to deserialize GitHub response: {e}"))?;
+
+    Ok(releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .map(|release| release.tag_name)
+        .collect())
+}
+
+fn highest_matching(releases: &[String], req: &semver::VersionReq) -> Option<String> {
+    releases
+        .iter()
+        .filter_map(|release| Some((parse_release(release)?, release)))
+        .filter(|(version, _)| req.matches(version))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release.clone())
+}
+
+/// Parses a release name as a semver version, allowing a `v` prefix.
+pub(super) fn parse_release(release: &str) -> Option<semver::Version> {
+    semver::Version::parse(release.strip_prefix('v').unwrap_or(release)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_bare_name() {
+        let spec: ModuleSpec = "serpapi".parse().unwrap();
+        assert_eq!(spec.name.as_str(), "serpapi");
+        assert_eq!(spec.version, None);
+    }
+
+    #[test]
+    fn parses_an_exact_version() {
+        let spec: ModuleSpec = "serpapi@1.2.0".parse().unwrap();
+        assert_eq!(spec.version, Some(VersionPin::Exact("1.2.0".into())));
+    }
+
+    #[test]
+    fn parses_a_range() {
+        let spec: ModuleSpec = "brightdata@^0.4".parse().unwrap();
+        assert_eq!(
+            spec.version,
+            Some(VersionPin::Range("^0.4".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn rejects_an_empty_or_invalid_version() {
+        assert!("serpapi@".parse::<ModuleSpec>().is_err());
+        assert!("serpapi@not-a-version".parse::<ModuleSpec>().is_err());
+    }
+
+    #[test]
+    fn picks_the_highest_matching_release() {
+        let releases = ["0.3.9", "v0.4.0", "0.4.2", "0.5.0", "nightly"].map(String::from);
+        let req = "^0.4".parse().unwrap();
+        assert_eq!(highest_matching(&releases, &req).as_deref(), Some("0.4.2"));
+
+        let req = ">=1".parse().unwrap();
+        assert_eq!(highest_matching(&releases, &req), None);
+    }
+
+    #[test]
+    fn exact_pins_ignore_a_v_prefix() {
+        let pin = VersionPin::Exact("1.2.0".into());
+        assert!(pin.matches("v1.2.0"));
+        assert!(!pin.matches("1.2.1"));
+    }
+}
@@ -1,21 +1,26 @@
 // This is free and unencumbered software released into the public domain.
 
+use super::{
+    Job, ModuleSpec, VersionPin, describe, fetch_module_manifest, install_release, parse_release,
+    resolve_version, restore_version, run_jobs, save_version, summarize,
+};
 use crate::{BoxError, StandardOptions, SysexitsError::*};
-use asimov_installer::InstallOptions;
 use asimov_module::ModuleName;
-use color_print::cprintln;
+use color_print::ceprintln;
+use std::{collections::HashSet, io::IsTerminal, sync::Arc};
+use tokio::sync::Mutex;
 
 pub async fn upgrade(
-    module_names: Vec<ModuleName>,
+    modules: Vec<ModuleSpec>,
     version: Option<String>,
     model_size: Option<String>,
+    jobs: usize,
     flags: &StandardOptions,
 ) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
-    let installer = asimov_installer::Installer::default();
 
-    let module_names = if !module_names.is_empty() {
-        module_names.to_vec()
+    let modules = if !modules.is_empty() {
+        modules
     } else {
         registry
             .installed_modules()
@@ -25,67 +30,170 @@ pub async fn upgrade(
                 EX_UNAVAILABLE
             })?
             .into_iter()
-            .map(|manifest| ModuleName::try_from(manifest.manifest.name))
+            .map(|manifest| {
+                ModuleName::try_from(manifest.manifest.name).map(|name| ModuleSpec {
+                    name,
+                    version: None,
+                })
+            })
             .collect::<Result<Vec<_>, _>>()?
     };
 
-    let install_options = InstallOptions::builder()
-        .maybe_version(version.clone())
-        .maybe_model_size(model_size.clone())
-        .build();
-
-    for module_name in module_names {
-        let current = registry.module_version(&module_name).await.map_err(|_| {
-            tracing::error!("failed to read installed version of `{module_name}`");
-            EX_UNAVAILABLE
-        })?;
-
-        let target_version = if let Some(ref want) = version {
-            want.clone()
-        } else {
-            installer
-                .fetch_latest_release(&module_name)
-                .await
-                .map_err(|e| {
-                    tracing::error!(
-                        "unable to find latest release for module `{module_name}`: {e}"
-                    );
-                    EX_UNAVAILABLE
-                })?
-        };
-
-        if current.is_some_and(|current| current == target_version) {
-            if flags.verbose > 0 {
-                let vers_txt = if version.is_some() {
-                    "version"
-                } else {
-                    "latest version"
-                };
-
-                cprintln!(
-                    "<s,g>✓</> Module <s>{module_name}</> already has {vers_txt} <s>{target_version}</> installed."
-                );
+    let queue = modules
+        .into_iter()
+        .map(|spec| Job {
+            name: spec.name,
+            requires: Vec::new(),
+            data: spec.version,
+        })
+        .collect();
+
+    // held while installing a module that an upgrade newly requires, so that
+    // concurrent upgrades don't install the same module at once
+    let dependencies = Arc::new(Mutex::new(()));
+
+    let progress = flags.verbose > 0 || std::io::stdout().is_terminal();
+    let outcomes = run_jobs(queue, jobs, "Upgrading", progress, |module_name, pin| {
+        let version = version.clone();
+        let model_size = model_size.clone();
+        let dependencies = dependencies.clone();
+        async move {
+            match upgrade_module(&module_name, pin, version, model_size, &dependencies).await {
+                Ok((version, status)) => (Some(version), Ok(status)),
+                Err(e) => (None, Err(describe(e))),
             }
-            continue;
         }
+    })
+    .await;
+
+    summarize(&outcomes, flags)
+}
+
+/// Upgrades one module, restoring its previous version if the upgrade
+/// fails, and returns the version it ends up at.
+async fn upgrade_module(
+    module_name: &ModuleName,
+    pin: Option<VersionPin>,
+    version: Option<String>,
+    model_size: Option<String>,
+    dependencies: &Mutex<()>,
+) -> Result<(String, &'static str), BoxError> {
+    let registry = asimov_registry::Registry::default();
+    let installer = asimov_installer::Installer::default();
+
+    let current = registry.module_version(module_name).await.map_err(|_| {
+        tracing::error!("failed to read installed version of `{module_name}`");
+        EX_UNAVAILABLE
+    })?;
+
+    let target_version =
+        resolve_version(&installer, module_name, pin.as_ref(), version.as_deref()).await?;
+
+    // within a range, an installed release at or above the target is
+    // already as far as the range allows, so it isn't downgraded
+    let is_current = match (&current, &pin) {
+        (Some(current), Some(pin @ VersionPin::Range(_))) => {
+            pin.matches(current) && parse_release(current) >= parse_release(&target_version)
+        },
+        (Some(current), _) => *current == target_version,
+        (None, _) => false,
+    };
+
+    if is_current {
+        return Ok((current.unwrap_or(target_version), "up to date"));
+    }
 
-        if flags.verbose > 1 {
-            cprintln!("<s,c>»</> Upgrading module <s>{module_name}</>...");
+    let manifest = fetch_module_manifest(module_name, &target_version)
+        .await
+        .map_err(|e| format!("unable to fetch the manifest of version `{target_version}`: {e}"))?;
+
+    // installed first, one upgrade at a time, so that concurrent upgrades
+    // don't install the same module at once
+    if !manifest.requires.modules.is_empty() {
+        let _guard = dependencies.lock().await;
+        install_dependencies(
+            &registry,
+            &installer,
+            &manifest.requires.modules,
+            model_size.as_deref(),
+            &mut HashSet::from([module_name.clone()]),
+        )
+        .await?;
+    }
+
+    let was_enabled = registry
+        .is_module_enabled(module_name)
+        .await
+        .unwrap_or(false);
+
+    let saved_dir = save_version(&registry, module_name)
+        .await
+        .map_err(|e| format!("failed to keep the installed version: {e}"))?;
+
+    if let Err(e) = install_release(
+        &registry,
+        module_name,
+        &target_version,
+        model_size.as_deref(),
+    )
+    .await
+    {
+        restore_version(&registry, module_name, &saved_dir, was_enabled).await?;
+        ceprintln!(
+            "<s,y>warn:</> Restored version <s>{}</> of module <s>{module_name}</>.",
+            current.as_deref().unwrap_or("unknown")
+        );
+        return Err(format!("upgrade failed: {}", describe(e)).into());
+    }
+
+    Ok((target_version, "upgraded"))
+}
+
+/// Installs the required modules that aren't installed yet, the modules
+/// that they require first. Concurrent upgrades do this one at a time.
+/// Modules already seen, as in a cycle of requirements, are passed over.
+async fn install_dependencies(
+    registry: &asimov_registry::Registry,
+    installer: &asimov_installer::Installer,
+    requires: &[String],
+    model_size: Option<&str>,
+    seen: &mut HashSet<ModuleName>,
+) -> Result<(), BoxError> {
+    for dependency in requires {
+        let dependency = ModuleName::try_from(dependency.clone())?;
+        if !seen.insert(dependency.clone())
+            || registry
+                .is_module_installed(&dependency)
+                .await
+                .unwrap_or(false)
+        {
+            continue;
         }
 
-        installer
-            .upgrade_module(&module_name, &install_options)
+        let version = resolve_version(installer, &dependency, None, None).await?;
+        let manifest = fetch_module_manifest(&dependency, &version)
             .await
             .map_err(|e| {
-                tracing::error!("module upgrade failed for `{module_name}`: {e}");
-                EX_UNAVAILABLE
+                format!("unable to fetch the manifest of required module `{dependency}`: {e}")
             })?;
+        Box::pin(install_dependencies(
+            registry,
+            installer,
+            &manifest.requires.modules,
+            model_size,
+            seen,
+        ))
+        .await?;
 
-        if flags.verbose > 0 {
-            cprintln!(
-                "<s,g>✓</> Upgraded module <s>{module_name}</> to version <s>{target_version}</>."
-            );
-        }
+        install_release(registry, &dependency, &version, model_size)
+            .await
+            .map_err(|e| {
+                format!(
+                    "failed to install required module `{dependency}`: {}",
+                    describe(e)
+                )
+            })?;
     }
+
     Ok(())
 }
@@ -0,0 +1,225 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, StandardOptions, SysexitsError::*};
+use asimov_env::paths::asimov_root;
+use asimov_module::ModuleName;
+use color_print::{ceprintln, cprintln};
+use serde::Serialize;
+use std::{collections::BTreeMap, io};
+
+/// The checksum record kept in each installed module's directory, in the
+/// format of `sha256sum`.
+pub const CHECKSUMS_FILE_NAME: &str = "SHA256SUMS";
+
+#[derive(Debug, Default, Serialize)]
+struct Verification {
+    name: String,
+    ok: bool,
+    recorded: bool,
+    issues: Vec<String>,
+}
+
+/// Checks that the programs of installed modules are present, linked into
+/// libexec, and unchanged since they were installed.
+pub async fn verify(
+    module_names: Vec<ModuleName>,
+    output: String,
+    _flags: &StandardOptions,
+) -> Result<(), BoxError> {
+    let registry = asimov_registry::Registry::default();
+
+    let module_names = if !module_names.is_empty() {
+        module_names
+    } else {
+        registry
+            .installed_modules()
+            .await
+            .map_err(|e| {
+                tracing::error!("failed to read installed modules: {e}");
+                EX_UNAVAILABLE
+            })?
+            .into_iter()
+            .map(|module| ModuleName::try_from(module.manifest.name))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut failures = 0;
+    for module_name in module_names {
+        let result = verify_module(&registry, &module_name).await?;
+        if !result.ok {
+            failures += 1;
+        }
+
+        match output.as_str() {
+            "jsonl" => println!("{}", serde_json::to_string(&result)?),
+            _ => {
+                if result.ok {
+                    cprintln!("<s,g>✓</> {}", result.name);
+                } else {
+                    cprintln!("<s,r>✗</> {}", result.name);
+                }
+                for issue in &result.issues {
+                    cprintln!("    {issue}");
+                }
+                if !result.recorded {
+                    ceprintln!(
+                        "<s,y>warn:</> Module <s>{module_name}</> has no checksum record, so its programs can't be checked for changes."
+                    );
+                    ceprintln!(
+                        "<s,dim>hint:</> Reinstall it to create one: <s>asimov module uninstall {module_name} && asimov module install {module_name}</>"
+                    );
+                }
+            },
+        }
+    }
+
+    if failures > 0 {
+        return Err(EX_DATAERR.into());
+    }
+
+    Ok(())
+}
+
+async fn verify_module(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> Result<Verification, BoxError> {
+    let installed = registry.read_manifest(module_name).await.map_err(|e| {
+        tracing::error!("failed to read manifest for module `{module_name}`: {e}");
+        if let asimov_registry::error::ManifestError::NotInstalled = e {
+            ceprintln!(
+                "<s,dim>hint:</> Check if the module is installed with: <s>asimov module list</>"
+            );
+        }
+        EX_UNAVAILABLE
+    })?;
+
+    let recorded = read_checksums(registry, module_name).await.map_err(|e| {
+        tracing::error!("failed to read checksum record of module `{module_name}`: {e}");
+        EX_IOERR
+    })?;
+    let actual = program_checksums(registry, module_name, &installed.manifest.provides.programs)
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to checksum the programs of module `{module_name}`: {e}");
+            EX_IOERR
+        })?;
+
+    let libexec = asimov_root().join("libexec");
+    let mut issues = Vec::new();
+    for program in &installed.manifest.provides.programs {
+        let Some(hash) = actual.get(program) else {
+            issues.push(format!("{program}: missing"));
+            continue;
+        };
+
+        if !tokio::fs::try_exists(libexec.join(program))
+            .await
+            .unwrap_or(false)
+        {
+            issues.push(format!("{program}: not linked in libexec"));
+        }
+
+        match recorded.as_ref().map(|recorded| recorded.get(program)) {
+            Some(Some(expected)) if expected != hash => {
+                issues.push(format!("{program}: modified since installation"));
+            },
+            Some(None) => issues.push(format!("{program}: not in the checksum record")),
+            _ => (),
+        }
+    }
+
+    Ok(Verification {
+        name: module_name.to_string(),
+        ok: issues.is_empty(),
+        recorded: recorded.is_some(),
+        issues,
+    })
+}
+
+/// Writes the checksum record of a freshly installed module, for later
+/// checks by `asimov module verify`.
+pub(super) async fn record_checksums(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) {
+    let result = async {
+        let installed = registry
+            .read_manifest(module_name)
+            .await
+            .map_err(io::Error::other)?;
+        let checksums =
+            program_checksums(registry, module_name, &installed.manifest.provides.programs).await?;
+        tokio::fs::write(
+            registry.module_dir(module_name).join(CHECKSUMS_FILE_NAME),
+            checksum_listing(&checksums),
+        )
+        .await
+    }
+    .await;
+
+    if let Err(e) = result {
+        ceprintln!(
+            "<s,y>warn:</> Unable to record the checksums of module <s>{module_name}</>: {e}"
+        );
+    }
+}
+
+/// Reads the checksum record of a module, if it has one.
+async fn read_checksums(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+) -> io::Result<Option<BTreeMap<String, String>>> {
+    let path = registry.module_dir(module_name).join(CHECKSUMS_FILE_NAME);
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    Ok(Some(parse_checksum_listing(&content)))
+}
+
+/// Lists checksums by file name in the format of `sha256sum`, which is how
+/// both the checksum record and the lockfile's checksum are computed.
+pub(super) fn checksum_listing(checksums: &BTreeMap<String, String>) -> String {
+    checksums
+        .iter()
+        .map(|(file, hash)| format!("{hash}  {file}\n"))
+        .collect()
+}
+
+/// Reads checksums by file name from a listing in the format of `sha256sum`.
+pub(super) fn parse_checksum_listing(listing: &str) -> BTreeMap<String, String> {
+    listing
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        // binary mode marks the file name with `*`
+        .filter_map(|(hash, file)| Some((file.strip_prefix([' ', '*'])?, hash)))
+        .map(|(file, hash)| (file.to_string(), hash.to_string()))
+        .collect()
+}
+
+/// Hashes each installed program of a module with SHA-256, leaving out
+/// those that are missing.
+pub(super) async fn program_checksums(
+    registry: &asimov_registry::Registry,
+    module_name: &ModuleName,
+    programs: &[String],
+) -> io::Result<BTreeMap<String, String>> {
+    let bin_dir = registry
+        .module_dir(module_name)
+        .join(asimov_registry::BIN_DIR_NAME);
+
+    let mut checksums = BTreeMap::new();
+    for program in programs {
+        let content = match tokio::fs::read(bin_dir.join(program)).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let hash = <sha2::Sha256 as sha2::Digest>::digest(&content);
+        checksums.insert(program.clone(), hex::encode(hash));
+    }
+    Ok(checksums)
+}
@@ -3,6 +3,7 @@
 use crate::BoxError;
 use asimov_module::ModuleName;
 use clientele::{StandardOptions, crates::clap::Subcommand};
+use std::path::PathBuf;
 
 #[derive(Debug, Subcommand)]
 pub enum SourceCommand {
@@ -27,6 +28,11 @@ pub enum SourceCommand {
         #[arg(value_name = "FORMAT", short = 'o', long)]
         output: Option<String>,
 
+        /// Read more URLs from a file, one per line, or from stdin if `-`.
+        #[arg(value_name = "PATH", short = 'i', long)]
+        input_file: Option<PathBuf>,
+
+        /// The URLs, where `-` reads more from stdin, one per line.
         urls: Vec<String>,
     },
 
@@ -35,6 +41,19 @@ pub enum SourceCommand {
         #[clap(long, short = 'M')]
         module: Option<ModuleName>,
 
+        /// Fail once the first module that can read a URL fails, instead of
+        /// trying the next one.
+        #[arg(long)]
+        no_fallback: bool,
+
+        #[clap(flatten)]
+        target: OutputTargetArgs,
+
+        /// Read more URLs from a file, one per line, or from stdin if `-`.
+        #[arg(value_name = "PATH", short = 'i', long)]
+        input_file: Option<PathBuf>,
+
+        /// The URLs, where `-` reads more from stdin, one per line.
         urls: Vec<String>,
     },
 
@@ -67,10 +86,17 @@ impl SourceCommand {
                 module,
                 limit,
                 output,
+                input_file,
                 urls,
-            } => list(urls, module, limit, output, flags).await,
+            } => list(urls, input_file, module, limit, output, flags).await,
 
-            Read { module, urls } => read(urls, module, flags).await,
+            Read {
+                module,
+                no_fallback,
+                target,
+                input_file,
+                urls,
+            } => read(urls, input_file, module, !no_fallback, target, flags).await,
 
             #[cfg(feature = "source-snap")]
             Snap { command, args } => {
@@ -86,14 +112,23 @@ impl SourceCommand {
 #[cfg(false)]
 pub mod describe;
 
+mod fallback;
+use fallback::*;
+
 mod fetch;
 pub use fetch::*;
 
 mod list;
 pub use list::list;
 
+mod output;
+pub use output::*;
+
 mod read;
 pub use read::*;
 
 mod snap;
 pub use snap::*;
+
+mod urls;
+use urls::*;
@@ -0,0 +1,85 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{StandardOptions, logs};
+use asimov_runner::{Executor, ExecutorError};
+use color_print::ceprintln;
+use std::io::Cursor;
+
+/// What became of running the programs of the modules that can handle a
+/// URL, each in turn until one of them succeeded.
+#[derive(Debug, Default)]
+pub(super) struct Attempts {
+    /// The module that succeeded, and its output.
+    pub succeeded: Option<(String, Cursor<Vec<u8>>)>,
+    /// The modules that failed, in the order they were tried.
+    pub failed: Vec<(String, ExecutorError)>,
+}
+
+impl Attempts {
+    /// Runs each module's program in turn, until one of them succeeds.
+    pub async fn run(candidates: Vec<(String, Executor)>) -> Self {
+        let mut attempts = Self::default();
+        for (module, mut executor) in candidates {
+            match logs::execute(&module, &mut executor).await {
+                Ok(output) => {
+                    attempts.succeeded = Some((module, output));
+                    break;
+                },
+                Err(e) => attempts.failed.push((module, e)),
+            }
+        }
+        attempts
+    }
+
+    /// The module that succeeded, or else the last one tried.
+    pub fn module(&self) -> Option<&str> {
+        match &self.succeeded {
+            Some((module, _)) => Some(module),
+            None => self.failed.last().map(|(module, _)| module.as_str()),
+        }
+    }
+
+    /// The exit status of the program that succeeded, or else of the last
+    /// one tried, if it ran and wasn't ended by a signal.
+    pub fn status(&self) -> Option<i32> {
+        match (&self.succeeded, self.failed.last()) {
+            (Some(_), _) => Some(0),
+            (None, Some((_, ExecutorError::Failure(code, _)))) => code.code(),
+            (None, Some((_, ExecutorError::UnexpectedFailure(code, _)))) => *code,
+            (None, _) => None,
+        }
+    }
+
+    /// Reports the failures. Each module's error is reported only if every
+    /// module failed, since otherwise the URL was handled regardless.
+    pub fn report(&self, program: &str, url: &str, flags: &StandardOptions) {
+        match (&self.succeeded, self.failed.as_slice()) {
+            (None, [(_, e)]) => {
+                ceprintln!("<s,r>error:</> {program} execution failed for <s>{url}</>: {e}");
+            },
+            (None, failed) => {
+                ceprintln!(
+                    "<s,r>error:</> all {} modules failed to handle <s>{url}</>:",
+                    failed.len()
+                );
+                for (module, e) in failed {
+                    ceprintln!("<s,r>error:</> {program} of module <s>{module}</> failed: {e}");
+                }
+            },
+            (Some((used, _)), failed) => {
+                if flags.verbose > 0 {
+                    for (module, _) in failed {
+                        ceprintln!(
+                            "<s,dim>note:</> Module <s>{module}</> failed to handle <s>{url}</>, so the next module was tried."
+                        );
+                    }
+                    if !failed.is_empty() {
+                        ceprintln!(
+                            "<s,dim>note:</> Module <s>{used}</> handled <s>{url}</>. Failures are logged, see: <s>asimov module logs <<module>></>"
+                        );
+                    }
+                }
+            },
+        }
+    }
+}
@@ -1,11 +1,26 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
-use asimov_module::{ModuleName, normalization::normalize_url, resolve::Resolver};
-use asimov_runner::{FetcherOptions, GraphOutput};
+use super::{Attempts, InputUrls, OutputTarget, OutputTargetArgs};
+use crate::{
+    BoxError, StandardOptions, SysexitsError,
+    SysexitsError::*,
+    cache::{self, Cache},
+    shared,
+};
+use asimov_module::{ModuleManifest, ModuleName, normalization::normalize_url, resolve::Resolver};
+use asimov_runner::Executor;
 use clientele::crates::clap::Args;
 use color_print::ceprintln;
+use jiff::{Span, Timestamp, Zoned};
 use miette::Result;
+use std::{collections::HashMap, path::PathBuf, process::Stdio};
+
+/// How many URLs are read ahead of those being fetched, at least.
+const READ_AHEAD: usize = 16;
+
+/// How many days old a cached output may be to be used, unless told
+/// otherwise.
+const DEFAULT_MAX_AGE_DAYS: i64 = 1;
 
 #[derive(Args, Clone, Debug, Default)]
 pub struct SourceFetchArgs {
@@ -19,65 +34,413 @@ pub struct SourceFetchArgs {
     #[arg(value_name = "FORMAT", short = 'o', long)]
     output: Option<String>,
 
+    #[clap(flatten)]
+    target: OutputTargetArgs,
+
+    /// The number of URLs to fetch at once. With more than one, each
+    /// fetcher's output is written out whole once it is done.
+    #[arg(value_name = "N", short = 'j', long, default_value_t = 1)]
+    jobs: usize,
+
+    /// The number of URLs on one host to fetch at once.
+    #[arg(value_name = "N", long, default_value_t = 2)]
+    per_host: usize,
+
+    /// Keep fetching the remaining URLs after one fails.
+    #[arg(short = 'k', long)]
+    keep_going: bool,
+
+    /// Try the next module that can fetch a URL when one fails, reporting
+    /// the failures only if every one of them does.
+    #[arg(long)]
+    fallback: bool,
+
+    /// Use the cached output of an earlier fetch of a URL with the same
+    /// module, module version, and output format, if it is fresh, and cache
+    /// the outputs of new fetches.
+    #[arg(long)]
+    cache: bool,
+
+    /// How old a cached output may be to be used, such as `30m` or `2 days`.
+    /// Implies `--cache` [default: 1d]
+    #[arg(value_name = "AGE", long)]
+    max_age: Option<Span>,
+
+    /// Fetch anew even if a fresh output is cached, and cache the new one.
+    /// Implies `--cache`.
+    #[arg(long)]
+    refresh: bool,
+
+    /// Neither use nor store cached outputs.
+    #[arg(long, overrides_with_all = ["cache", "max_age", "refresh"])]
+    no_cache: bool,
+
+    /// Read more URLs from a file, one per line, or from stdin if `-`.
+    #[arg(value_name = "PATH", short = 'i', long)]
+    input_file: Option<PathBuf>,
+
+    /// The URLs, where `-` reads more from stdin, one per line.
     urls: Vec<String>,
 }
 
+/// What became of fetching a URL.
+#[derive(Debug)]
+enum Status {
+    Fetched,
+    /// Not fetched, since a fresh output was cached.
+    Cached,
+    Failed(SysexitsError),
+    /// Not fetched, since an earlier URL failed.
+    Skipped,
+}
+
+/// A module to fetch a URL with, and what its output is cached under.
+#[derive(Debug)]
+struct Candidate {
+    key: cache::Key,
+    fetcher: Executor,
+}
+
+#[derive(Debug)]
+struct Outcome {
+    url: String,
+    module: Option<String>,
+    status: Status,
+}
+
 pub async fn fetch(args: SourceFetchArgs, flags: &StandardOptions) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
 
     let installed_modules = shared::installed_modules(&registry, Some("fetcher")).await?;
+    let profile = shared::profile(None)?;
 
     let resolver = Resolver::try_from_iter(installed_modules.iter()).map_err(|e| {
         ceprintln!("<s,r>error:</> failed to build resolver: {e}");
         EX_UNAVAILABLE
     })?;
 
-    for input_url in args.urls {
-        if flags.verbose > 1 {
-            ceprintln!("<s,c>»</> Fetching <s>{}</>...", input_url);
-        }
+    let jobs = args.jobs.max(1);
+    let per_host = args.per_host.max(1);
+    let mut target = OutputTarget::new(&args.target, args.output.as_deref())?;
+    let caching = !args.no_cache && (args.cache || args.max_age.is_some() || args.refresh);
+    let cache = caching.then(Cache::default);
+    // cached outputs are fresh if fetched since then
+    let fresh_since = match &cache {
+        Some(_) => fresh_since(
+            args.max_age
+                .unwrap_or(Span::new().days(DEFAULT_MAX_AGE_DAYS)),
+        )?,
+        None => Timestamp::MAX,
+    };
+    // cached outputs are held back to be stored
+    let buffered = jobs > 1 || !target.is_stdout() || caching;
+
+    let mut urls = InputUrls::new(args.urls.clone(), args.input_file.as_deref())?;
+    let mut outcomes: Vec<Outcome> = Vec::new();
+    let mut pending: Vec<usize> = Vec::new();
+    let mut running = tokio::task::JoinSet::new();
+    let mut hosts: HashMap<String, usize> = HashMap::new();
+    let mut cache_keys: HashMap<usize, Vec<cache::Key>> = HashMap::new();
+    let mut stopping = false;
+    let mut exhausted = false;
+    let mut input_error = None;
+
+    loop {
+        // start the next URLs, as far as the limits allow
+        while !stopping && running.len() < jobs {
+            let Some(position) = pending.iter().position(|&index| {
+                host(&outcomes[index].url)
+                    .is_none_or(|host| hosts.get(&host).copied().unwrap_or(0) < per_host)
+            }) else {
+                break;
+            };
+            let index = pending.remove(position);
+            let outcome = &mut outcomes[index];
 
-        let input_url = normalize_url(&input_url).unwrap_or_else(|e| {
             if flags.verbose > 1 {
-                ceprintln!(
-                    "<s,y>warning:</> using given unmodified URL, normalization failed: {e}"
-                );
+                ceprintln!("<s,c>»</> Fetching <s>{}</>...", outcome.url);
             }
-            input_url.clone()
+
+            let prepared = prepare(
+                &outcome.url,
+                &args,
+                &registry,
+                &resolver,
+                &installed_modules,
+                &profile,
+                buffered,
+                flags,
+            )
+            .await;
+            let (url, candidates) = match prepared {
+                Ok(prepared) => prepared,
+                Err(code) => {
+                    target.write(&outcome.url, None, Err(None))?;
+                    outcome.status = Status::Failed(code);
+                    stopping = !args.keep_going;
+                    continue;
+                },
+            };
+            outcome.url = url.clone();
+
+            if let Some(cache) = &cache
+                && !args.refresh
+                && let Some((module, output)) = cached(cache, &candidates, fresh_since)
+            {
+                target.write(&outcome.url, Some(&module), Ok(&output))?;
+                if flags.verbose > 0 {
+                    ceprintln!("<s,g>✓</> Fetched <s>{}</> from the cache.", outcome.url);
+                }
+                outcome.module = Some(module);
+                outcome.status = Status::Cached;
+                continue;
+            }
+
+            if let Some(host) = host(&url) {
+                *hosts.entry(host).or_default() += 1;
+            }
+            let (keys, fetchers) = candidates
+                .into_iter()
+                .map(|candidate| {
+                    let module = candidate.key.module.clone();
+                    (candidate.key, (module, candidate.fetcher))
+                })
+                .unzip();
+            cache_keys.insert(index, keys);
+            running.spawn(async move { (index, Attempts::run(fetchers).await) });
+        }
+
+        // read URLs ahead only as far as they may soon be started
+        let reading = !exhausted && !stopping && pending.len() < READ_AHEAD.max(jobs);
+        if !reading && running.is_empty() {
+            break;
+        }
+
+        let finished = tokio::select! {
+            biased;
+            Some(finished) = running.join_next(), if !running.is_empty() => finished,
+            url = urls.next(), if reading => {
+                match url {
+                    Some(Ok(url)) => {
+                        outcomes.push(Outcome {
+                            url,
+                            module: None,
+                            status: Status::Skipped,
+                        });
+                        pending.push(outcomes.len() - 1);
+                    },
+                    Some(Err(code)) => {
+                        input_error = Some(code);
+                        exhausted = true;
+                    },
+                    None => exhausted = true,
+                }
+                continue;
+            },
+        };
+        let (index, attempts) = finished?;
+        let keys = cache_keys.remove(&index).unwrap_or_default();
+        let outcome = &mut outcomes[index];
+        if let Some(host) = host(&outcome.url)
+            && let Some(count) = hosts.get_mut(&host)
+        {
+            *count -= 1;
+        }
+
+        outcome.module = attempts.module().map(String::from);
+        attempts.report("fetcher", &outcome.url, flags);
+
+        match &attempts.succeeded {
+            Some((module, output)) => {
+                // written out whole, so that records from several fetchers
+                // don't interleave; empty unless the output was held back
+                target.write(&outcome.url, Some(module), Ok(output.get_ref()))?;
+                if let Some(cache) = &cache
+                    && let Some(key) = keys.iter().find(|key| key.module == *module)
+                    && let Err(e) = cache.put(key, output.get_ref())
+                {
+                    ceprintln!(
+                        "<s,y>warning:</> failed to cache the output for <s>{}</>: {e}",
+                        outcome.url
+                    );
+                }
+                if flags.verbose > 0 {
+                    ceprintln!("<s,g>✓</> Fetched <s>{}</>.", outcome.url);
+                }
+                outcome.status = Status::Fetched;
+            },
+            None => {
+                target.write(&outcome.url, attempts.module(), Err(attempts.status()))?;
+                outcome.status = Status::Failed(EX_UNAVAILABLE);
+                stopping = !args.keep_going;
+            },
+        }
+    }
+
+    if outcomes.len() > 1 {
+        summarize(&outcomes);
+    }
+
+    let failure = outcomes.iter().find_map(|outcome| match outcome.status {
+        Status::Failed(code) => Some(code),
+        _ => None,
+    });
+    match failure.or(input_error) {
+        Some(code) => Err(code.into()),
+        None => Ok(()),
+    }
+}
+
+/// Resolves the modules to fetch a URL with, and sets up their fetchers, to
+/// be tried in turn. Returns the normalized URL and the candidates, just the
+/// first unless falling back.
+#[allow(clippy::too_many_arguments)]
+async fn prepare(
+    input_url: &str,
+    args: &SourceFetchArgs,
+    registry: &asimov_registry::Registry,
+    resolver: &Resolver,
+    installed_modules: &[ModuleManifest],
+    profile: &str,
+    buffered: bool,
+    flags: &StandardOptions,
+) -> Result<(String, Vec<Candidate>), SysexitsError> {
+    let input_url = normalize_url(input_url).unwrap_or_else(|e| {
+        if flags.verbose > 1 {
+            ceprintln!("<s,y>warning:</> using given unmodified URL, normalization failed: {e}");
+        }
+        input_url.to_string()
+    });
+
+    let modules = resolver.resolve(&input_url).map_err(|e| {
+        ceprintln!("<s,r>error:</> unable to handle URL <s>{input_url}</>: {e}");
+        EX_USAGE
+    })?;
+
+    let mut modules = shared::pick_modules(
+        registry,
+        &input_url,
+        modules.as_slice(),
+        args.module.as_deref(),
+    )
+    .await?;
+    if !args.fallback {
+        modules.truncate(1);
+    }
+    // a fetcher that fails may have written some of its output already, so
+    // the output is held back when there is another fetcher to try
+    let buffered = buffered || modules.len() > 1;
+
+    let mut candidates = Vec::with_capacity(modules.len());
+    for module in modules {
+        let manifest = installed_modules
+            .iter()
+            .find(|manifest| manifest.name == module.name)
+            .expect("resolved modules should be installed");
+
+        let mut fetcher = Executor::new(format!("asimov-{}-fetcher", module.name));
+        fetcher
+            .command()
+            .args(
+                args.output
+                    .as_deref()
+                    .map(|output| format!("--output={output}")),
+            )
+            .args(flags.debug.then_some("--debug"))
+            .arg(&input_url)
+            .stdout(if buffered {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            })
+            .stderr(Stdio::piped());
+        shared::configure_program(fetcher.command().as_std_mut(), manifest, profile).map_err(
+            |e| match e.downcast_ref::<SysexitsError>() {
+                Some(code) => *code,
+                None => {
+                    tracing::error!("failed to configure module `{}`: {e}", module.name);
+                    EX_CONFIG
+                },
+            },
+        )?;
+
+        let version = match module.name.parse::<ModuleName>() {
+            Ok(module_name) => registry
+                .read_manifest(&module_name)
+                .await
+                .ok()
+                .and_then(|installed| installed.version),
+            Err(_) => None,
+        };
+        candidates.push(Candidate {
+            key: cache::Key {
+                url: input_url.clone(),
+                module: module.name.clone(),
+                version,
+                format: args.output.clone(),
+                profile: profile.to_string(),
+            },
+            fetcher,
         });
+    }
+
+    Ok((input_url, candidates))
+}
 
-        let modules = resolver.resolve(&input_url).map_err(|e| {
-            ceprintln!("<s,r>error:</> unable to handle URL <s>{input_url}</>: {e}");
+/// The time since when cached outputs are fresh, given how old they may be.
+fn fresh_since(max_age: Span) -> Result<Timestamp, SysexitsError> {
+    Zoned::now()
+        .checked_sub(max_age.abs())
+        .map(|zoned| zoned.timestamp())
+        .map_err(|e| {
+            ceprintln!("<s,r>error:</> invalid maximum age `{max_age}`: {e}");
             EX_USAGE
-        })?;
-
-        let module = shared::pick_module(
-            &registry,
-            &input_url,
-            modules.as_slice(),
-            args.module.as_deref(),
-        )
-        .await?;
-
-        let mut fetcher = asimov_runner::Fetcher::new(
-            format!("asimov-{}-fetcher", module.name),
-            &input_url,
-            GraphOutput::Inherited,
-            FetcherOptions::builder()
-                .maybe_output(args.output.as_deref())
-                .maybe_other(flags.debug.then_some("--debug"))
-                .build(),
-        );
-
-        let _ = fetcher.execute().await.map_err(|e| {
-            ceprintln!("<s,r>error:</> fetcher execution failed: {e}");
-            EX_UNAVAILABLE
-        })?;
-
-        if flags.verbose > 0 {
-            ceprintln!("<s,g>✓</> Fetched <s>{}</>.", input_url);
+        })
+}
+
+/// The first fresh cached output for any of the candidates, and the module
+/// that fetched it. Failing to read the cache is not an error, since the URL
+/// can be fetched regardless.
+fn cached(cache: &Cache, candidates: &[Candidate], since: Timestamp) -> Option<(String, Vec<u8>)> {
+    candidates
+        .iter()
+        .find_map(|candidate| match cache.get(&candidate.key, since) {
+            Ok(hit) => hit.map(|(entry, output)| (entry.key.module, output)),
+            Err(e) => {
+                tracing::warn!("failed to read the cache for `{}`: {e}", candidate.key.url);
+                None
+            },
+        })
+}
+
+/// The host of a URL, for limiting how many of its URLs are fetched at
+/// once. Other URLs, such as those of files, aren't limited.
+fn host(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|host| host.to_ascii_lowercase())
+}
+
+/// Prints the status of each URL to stderr, since stdout holds the fetched
+/// data.
+fn summarize(outcomes: &[Outcome]) {
+    let module_width = outcomes
+        .iter()
+        .map(|o| o.module.as_deref().unwrap_or("-").len())
+        .max()
+        .unwrap_or(0)
+        .max("MODULE".len());
+
+    ceprintln!("<s>STATUS   {:module_width$}  URL</>", "MODULE");
+    for outcome in outcomes {
+        let module = outcome.module.as_deref().unwrap_or("-");
+        let url = &outcome.url;
+        match outcome.status {
+            Status::Fetched => ceprintln!("<g>fetched</>  {module:module_width$}  {url}"),
+            Status::Cached => ceprintln!("<g>cached</>   {module:module_width$}  {url}"),
+            Status::Failed(_) => ceprintln!("<r>failed</>   {module:module_width$}  {url}"),
+            Status::Skipped => ceprintln!("<dim>skipped</>  {module:module_width$}  {url}"),
         }
     }
-
-    Ok(())
 }
@@ -1,13 +1,16 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
+use super::InputUrls;
+use crate::{BoxError, StandardOptions, SysexitsError::*, logs, shared};
 use asimov_module::{ModuleName, normalization::normalize_url, resolve::Resolver};
-use asimov_runner::{CatalogerOptions, GraphOutput};
+use asimov_runner::Executor;
 use color_print::ceprintln;
 use miette::Result;
+use std::{path::PathBuf, process::Stdio};
 
 pub async fn list(
     input_urls: Vec<String>,
+    input_file: Option<PathBuf>,
     module: Option<ModuleName>,
     limit: Option<usize>,
     output: Option<String>,
@@ -15,13 +18,16 @@ pub async fn list(
 ) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
     let installed_modules = shared::installed_modules(&registry, Some("cataloger")).await?;
+    let profile = shared::profile(None)?;
 
     let resolver = Resolver::try_from_iter(installed_modules.iter()).map_err(|e| {
         ceprintln!("<s,r>error:</> failed to build resolver: {e}");
         EX_UNAVAILABLE
     })?;
 
-    for input_url in input_urls {
+    let mut input_urls = InputUrls::new(input_urls, input_file.as_deref())?;
+    while let Some(input_url) = input_urls.next().await {
+        let input_url = input_url?;
         if flags.verbose > 1 {
             ceprintln!("<s,c>»</> Cataloging <s>{}</>...", input_url);
         }
@@ -44,21 +50,28 @@ pub async fn list(
             shared::pick_module(&registry, &input_url, modules.as_slice(), module.as_deref())
                 .await?;
 
-        let mut cataloger = asimov_runner::Cataloger::new(
-            format!("asimov-{}-cataloger", module.name),
-            &input_url,
-            GraphOutput::Inherited,
-            CatalogerOptions::builder()
-                .maybe_limit(limit)
-                .maybe_output(output.as_deref())
-                .maybe_other(flags.debug.then_some("--debug"))
-                .build(),
-        );
+        let manifest = installed_modules
+            .iter()
+            .find(|manifest| manifest.name == module.name)
+            .expect("resolved modules should be installed");
 
-        let _ = cataloger.execute().await.map_err(|e| {
-            ceprintln!("<s,r>error:</> cataloger execution failed: {e}");
-            EX_UNAVAILABLE
-        })?;
+        let mut cataloger = Executor::new(format!("asimov-{}-cataloger", module.name));
+        cataloger
+            .command()
+            .args(output.as_deref().map(|output| format!("--output={output}")))
+            .args(limit.map(|limit| format!("--limit={limit}")))
+            .args(flags.debug.then_some("--debug"))
+            .arg(&input_url)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::piped());
+        shared::configure_program(cataloger.command().as_std_mut(), manifest, &profile)?;
+
+        let _ = logs::execute(&module.name, &mut cataloger)
+            .await
+            .map_err(|e| {
+                ceprintln!("<s,r>error:</> cataloger execution failed: {e}");
+                EX_UNAVAILABLE
+            })?;
 
         if flags.verbose > 0 {
             ceprintln!("<s,g>✓</> Cataloged <s>{}</>.", input_url);
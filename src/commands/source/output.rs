@@ -0,0 +1,299 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{SysexitsError, SysexitsError::*};
+use clientele::crates::clap::Args;
+use color_print::ceprintln;
+use jiff::Timestamp;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// The name of the index written to an output directory.
+const INDEX_FILE_NAME: &str = "index.jsonl";
+
+/// How long a file name made from a URL may be, before its extension.
+const MAX_FILE_STEM_LENGTH: usize = 100;
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct OutputTargetArgs {
+    /// Write the output for each URL to its own file in a directory, named
+    /// after the URL, and index them in `index.jsonl` there. Files already
+    /// in the directory are kept.
+    #[arg(value_name = "DIR", long, conflicts_with = "output_file")]
+    output_dir: Option<PathBuf>,
+
+    /// Append the output for every URL to a file, and index it in
+    /// `<FILE>.index.jsonl`.
+    #[arg(value_name = "FILE", long)]
+    output_file: Option<PathBuf>,
+}
+
+/// Where the output for each URL goes: to stdout, or else to files along
+/// with an index recording which URL's output went where.
+#[derive(Debug)]
+pub(super) struct OutputTarget {
+    files: Option<Files>,
+}
+
+#[derive(Debug)]
+struct Files {
+    kind: FilesKind,
+    index: File,
+}
+
+#[derive(Debug)]
+enum FilesKind {
+    Dir { path: PathBuf, extension: String },
+    File { name: String, file: File },
+}
+
+/// A line of the index.
+#[derive(Debug, Serialize)]
+struct IndexEntry<'a> {
+    url: &'a str,
+    /// The output file, relative to the index, unless there was no output.
+    file: Option<&'a str>,
+    /// Where the URL's output starts in the file, and how long it is.
+    offset: Option<u64>,
+    length: Option<u64>,
+    module: Option<&'a str>,
+    /// When the output was written, in RFC 3339 format.
+    time: String,
+    /// The exit status of the module's program, if it ran and wasn't ended
+    /// by a signal.
+    status: Option<i32>,
+}
+
+impl OutputTarget {
+    /// Sets up the output directory or file, if one is given. The format is
+    /// that of the output, for naming files by.
+    pub fn new(args: &OutputTargetArgs, format: Option<&str>) -> Result<Self, SysexitsError> {
+        let open_index = |path: &Path| {
+            append(path).map_err(|e| {
+                ceprintln!("<s,r>error:</> failed to open `{}`: {e}", path.display());
+                EX_CANTCREAT
+            })
+        };
+
+        let files = if let Some(dir) = &args.output_dir {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ceprintln!("<s,r>error:</> failed to create `{}`: {e}", dir.display());
+                EX_CANTCREAT
+            })?;
+            Some(Files {
+                index: open_index(&dir.join(INDEX_FILE_NAME))?,
+                kind: FilesKind::Dir {
+                    path: dir.clone(),
+                    extension: extension(format),
+                },
+            })
+        } else if let Some(path) = &args.output_file {
+            let file = append(path).map_err(|e| {
+                ceprintln!("<s,r>error:</> failed to open `{}`: {e}", path.display());
+                EX_CANTCREAT
+            })?;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Some(Files {
+                index: open_index(&path.with_file_name(format!("{name}.index.jsonl")))?,
+                kind: FilesKind::File { name, file },
+            })
+        } else {
+            None
+        };
+
+        Ok(Self { files })
+    }
+
+    /// Whether the output goes to stdout, rather than to files.
+    pub fn is_stdout(&self) -> bool {
+        self.files.is_none()
+    }
+
+    /// Writes the output for a URL, or records that there is none since
+    /// handling it failed.
+    pub fn write(
+        &mut self,
+        url: &str,
+        module: Option<&str>,
+        output: Result<&[u8], Option<i32>>,
+    ) -> Result<(), SysexitsError> {
+        let Some(files) = &mut self.files else {
+            if let Ok(output) = output {
+                let mut stdout = std::io::stdout().lock();
+                stdout
+                    .write_all(output)
+                    .and_then(|_| stdout.flush())
+                    .map_err(|e| {
+                        tracing::error!("failed to write to stdout: {e}");
+                        EX_IOERR
+                    })?;
+            }
+            return Ok(());
+        };
+
+        let (written, status) = match output {
+            Ok(output) => {
+                let (name, offset) = files.kind.write(url, output)?;
+                (Some((name, offset, output.len() as u64)), Some(0))
+            },
+            Err(status) => (None, status),
+        };
+        let entry = IndexEntry {
+            url,
+            file: written.as_ref().map(|(name, _, _)| name.as_str()),
+            offset: written.as_ref().map(|(_, offset, _)| *offset),
+            length: written.as_ref().map(|(_, _, length)| *length),
+            module,
+            time: Timestamp::now().to_string(),
+            status,
+        };
+
+        let mut line = serde_json::to_vec(&entry).expect("index entries should serialize");
+        line.push(b'\n');
+        files.index.write_all(&line).map_err(|e| {
+            tracing::error!("failed to write to the output index: {e}");
+            EX_IOERR
+        })
+    }
+}
+
+impl FilesKind {
+    /// Writes the output for a URL, returning the name of the file written
+    /// to and where in it the output starts.
+    fn write(&mut self, url: &str, output: &[u8]) -> Result<(String, u64), SysexitsError> {
+        match self {
+            FilesKind::Dir { path, extension } => {
+                let stem = file_stem(url);
+                // never a file already there, which an earlier run's index
+                // may refer to
+                for n in 1.. {
+                    let name = match n {
+                        1 => format!("{stem}.{extension}"),
+                        n => format!("{stem}-{n}.{extension}"),
+                    };
+                    let file_path = path.join(&name);
+                    let mut file = match File::create_new(&file_path) {
+                        Ok(file) => file,
+                        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                        Err(e) => {
+                            ceprintln!(
+                                "<s,r>error:</> failed to create `{}`: {e}",
+                                file_path.display()
+                            );
+                            return Err(EX_CANTCREAT);
+                        },
+                    };
+                    file.write_all(output).map_err(|e| {
+                        ceprintln!(
+                            "<s,r>error:</> failed to write `{}`: {e}",
+                            file_path.display()
+                        );
+                        EX_IOERR
+                    })?;
+                    return Ok((name, 0));
+                }
+                unreachable!("some file name is unused")
+            },
+            FilesKind::File { name, file } => {
+                let offset = file
+                    .seek(SeekFrom::End(0))
+                    .and_then(|offset| file.write_all(output).map(|_| offset))
+                    .map_err(|e| {
+                        ceprintln!("<s,r>error:</> failed to write `{name}`: {e}");
+                        EX_IOERR
+                    })?;
+                Ok((name.clone(), offset))
+            },
+        }
+    }
+}
+
+fn append(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// The file extension for an output format, which is the format's own name
+/// unless it has a more usual one. Modules write JSON unless told otherwise.
+fn extension(format: Option<&str>) -> String {
+    match format.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("") => "json".into(),
+        Some("cli" | "text") => "txt".into(),
+        Some("turtle") => "ttl".into(),
+        Some("ntriples" | "n-triples") => "nt".into(),
+        Some("nquads" | "n-quads") => "nq".into(),
+        Some("rdfxml" | "rdf/xml") => "rdf".into(),
+        Some(format) => {
+            let extension: String = format
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect();
+            if extension.is_empty() {
+                "out".into()
+            } else {
+                extension
+            }
+        },
+    }
+}
+
+/// A file name for a URL, without its scheme, that is safe on every
+/// platform: anything but letters, digits, `.` and `-` becomes `_`.
+fn file_stem(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let mut stem = String::new();
+    for c in rest.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+            c
+        } else {
+            '_'
+        };
+        // runs of replaced characters are collapsed
+        if !(c == '_' && stem.ends_with('_')) {
+            stem.push(c);
+        }
+    }
+    let stem: String = stem
+        .trim_matches(['_', '.'])
+        .chars()
+        .take(MAX_FILE_STEM_LENGTH)
+        .collect();
+    if stem.is_empty() {
+        "output".into()
+    } else {
+        stem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_files_after_urls() {
+        assert_eq!(
+            file_stem("https://example.org/a/b?q=1&r=2"),
+            "example.org_a_b_q_1_r_2"
+        );
+        assert_eq!(file_stem("https://example.org/"), "example.org");
+        assert_eq!(file_stem("file:///../../etc/passwd"), "etc_passwd");
+        assert_eq!(file_stem("https://"), "output");
+        assert_eq!(file_stem(&"x".repeat(500)).len(), MAX_FILE_STEM_LENGTH);
+    }
+
+    #[test]
+    fn extensions_follow_the_format() {
+        assert_eq!(extension(None), "json");
+        assert_eq!(extension(Some("jsonld")), "jsonld");
+        assert_eq!(extension(Some("turtle")), "ttl");
+        assert_eq!(extension(Some("../x")), "x");
+    }
+}
@@ -1,87 +1,82 @@
 // This is free and unencumbered software released into the public domain.
 
+use super::{Attempts, InputUrls, OutputTarget, OutputTargetArgs};
 use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
-use asimov_module::{ModuleName, normalization::normalize_url, resolve::Resolver};
-use asimov_runner::{GraphOutput, Input, ReaderOptions};
+use asimov_module::{ModuleName, resolve::Resolver};
+use asimov_runner::Executor;
 use color_print::ceprintln;
 use miette::Result;
+use std::{path::PathBuf, process::Stdio};
 
 pub async fn read(
     input_urls: Vec<String>,
+    input_file: Option<PathBuf>,
     module: Option<ModuleName>,
+    fallback: bool,
+    target: OutputTargetArgs,
     flags: &StandardOptions,
 ) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
 
     let installed_modules = shared::installed_modules(&registry, Some("reader")).await?;
+    let profile = shared::profile(None)?;
 
     let resolver = Resolver::try_from_iter(installed_modules.iter()).map_err(|e| {
         ceprintln!("<s,r>error:</> failed to build resolver: {e}");
         EX_UNAVAILABLE
     })?;
 
-    for input_url in input_urls {
+    let mut target = OutputTarget::new(&target, None)?;
+
+    let mut input_urls = InputUrls::new(input_urls, input_file.as_deref())?;
+    while let Some(input_url) = input_urls.next().await {
+        let input_url = input_url?;
         if flags.verbose > 1 {
             ceprintln!("<s,c>»</> Reading <s>{}</> ...", input_url);
         }
 
-        let mime_modules = infer::get_from_path(&input_url)
-            .inspect_err(|e| {
-                if flags.verbose > 1 {
-                    ceprintln!(
-                        "<s,y>warning:</> failed to determine MIME type of <s>{input_url}</>: {e}"
-                    )
-                }
-            })
-            .ok()
-            .flatten()
-            .map(|t| t.mime_type())
-            .and_then(|mt| mt.parse().ok())
-            .map(|mime_type| resolver.resolve_content_type(&mime_type))
-            .unwrap_or_default();
-
-        let normalized_url = normalize_url(&input_url).unwrap_or_else(|e| {
-            if flags.verbose > 1 {
-                ceprintln!(
-                    "<s,y>warning:</> using given unmodified URL, normalization failed: {e}"
-                );
-            }
-            input_url.clone()
-        });
-
-        let url_modules = resolver
-            .resolve(&normalized_url)
-            .inspect_err(|e| {
-                if flags.verbose > 1 {
-                    ceprintln!(
-                        "<s,r>warning:</> failed while resolving URL <s>{normalized_url}</>: {e}"
-                    );
-                }
-            })
-            .unwrap_or_default();
+        let modules = shared::read_candidates(&resolver, &input_url, flags);
 
-        // mime modules first for prioritization
-        let modules = [mime_modules, url_modules].concat();
-
-        let module =
-            shared::pick_module(&registry, &input_url, &modules, module.as_deref()).await?;
+        let mut modules =
+            shared::pick_modules(&registry, &input_url, &modules, module.as_deref()).await?;
+        if !fallback {
+            modules.truncate(1);
+        }
+        // a reader that fails may have written some of its output already,
+        // so the output is held back when there is another reader to try
+        let buffered = modules.len() > 1 || !target.is_stdout();
 
-        let mut reader = asimov_runner::Reader::new(
-            format!("asimov-{}-reader", module.name),
-            Input::Ignored,
-            GraphOutput::Inherited,
-            ReaderOptions::builder()
-                .other(&input_url)
-                .maybe_other(flags.debug.then_some("--debug"))
-                .build(),
-        );
+        let mut readers = Vec::with_capacity(modules.len());
+        for module in modules {
+            let manifest = installed_modules
+                .iter()
+                .find(|manifest| manifest.name == module.name)
+                .expect("resolved modules should be installed");
 
-        let mut output = reader.execute().await.map_err(|e| {
-            ceprintln!("<s,r>error:</> reader execution failed: {e}");
-            EX_UNAVAILABLE
-        })?;
+            let mut reader = Executor::new(format!("asimov-{}-reader", module.name));
+            reader
+                .command()
+                .arg(&input_url)
+                .args(flags.debug.then_some("--debug"))
+                .stdin(Stdio::null())
+                .stdout(if buffered {
+                    Stdio::piped()
+                } else {
+                    Stdio::inherit()
+                })
+                .stderr(Stdio::piped());
+            shared::configure_program(reader.command().as_std_mut(), manifest, &profile)?;
+            readers.push((module.name.clone(), reader));
+        }
 
-        tokio::io::copy(&mut output, &mut tokio::io::stdout()).await?;
+        let attempts = Attempts::run(readers).await;
+        attempts.report("reader", &input_url, flags);
+        let Some((module, output)) = &attempts.succeeded else {
+            target.write(&input_url, attempts.module(), Err(attempts.status()))?;
+            return Err(EX_UNAVAILABLE.into());
+        };
+        // empty unless the output was held back
+        target.write(&input_url, Some(module), Ok(output.get_ref()))?;
 
         if flags.verbose > 0 {
             ceprintln!("<s,g>✓</> Read <s>{}</>.", &input_url);
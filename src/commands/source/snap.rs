@@ -55,3 +55,6 @@ pub use log::*;
 
 mod save;
 pub use save::*;
+
+mod snapshot;
+use snapshot::*;
@@ -1,14 +1,18 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions};
+use super::SnapshotTaker;
+use crate::{BoxError, StandardOptions, shared};
 use asimov_env::paths::asimov_root;
 use asimov_module::normalization::normalize_url;
 use asimov_registry::Registry;
-use asimov_snapshot::{Options, Snapshotter};
 
 pub async fn create(urls: &[String], _flags: &StandardOptions) -> Result<(), BoxError> {
     let storage = asimov_snapshot::storage::Fs::for_dir(asimov_root().join("snapshots"))?;
-    let mut ss = Snapshotter::new(Registry::default(), storage, Options::default());
+    let enabled_modules = Registry::default().enabled_modules().await?;
+    let ss = SnapshotTaker::new(
+        enabled_modules.into_iter().map(|enabled| enabled.manifest),
+        &shared::profile(None)?,
+    )?;
 
     for url in urls {
         let url = normalize_url(url).unwrap_or_else(|e| {
@@ -19,7 +23,7 @@ pub async fn create(urls: &[String], _flags: &StandardOptions) -> Result<(), Box
             url.into()
         });
 
-        ss.snapshot(&url)
+        ss.snapshot(&storage, &url)
             .await
             .inspect_err(|e| tracing::error!("failed to snapshot the resource `{url}`: {e}"))?;
     }
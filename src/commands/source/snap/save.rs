@@ -1,22 +1,31 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{BoxError, StandardOptions, SysexitsError::*};
+use super::SnapshotTaker;
+use crate::commands::source::InputUrls;
+use crate::{BoxError, StandardOptions, SysexitsError::*, shared};
 use asimov_env::paths::asimov_root;
 use asimov_module::normalization::normalize_url;
 use clientele::crates::clap::Args;
 use color_print::ceprintln;
 use miette::Result;
+use std::path::PathBuf;
 
 #[derive(Args, Clone, Debug, Default)]
 pub struct SnapSaveArgs {
-    /// URL(s) to snapshot
+    /// Read more URLs from a file, one per line, or from stdin if `-`.
+    #[arg(value_name = "PATH", short = 'i', long)]
+    input_file: Option<PathBuf>,
+
+    /// URL(s) to snapshot, where `-` reads more from stdin, one per line
     urls: Vec<String>,
 }
 
 pub async fn save(args: &SnapSaveArgs, flags: &StandardOptions) -> Result<(), BoxError> {
     let registry = asimov_registry::Registry::default();
 
-    let _enabled_modules = registry
+    let profile = shared::profile(None)?;
+
+    let enabled_modules = registry
         .enabled_modules()
         .await
         .map_err(|e| {
@@ -40,9 +49,18 @@ pub async fn save(args: &SnapSaveArgs, flags: &StandardOptions) -> Result<(), Bo
             EX_UNAVAILABLE
         })?;
 
-    let mut snapshotter = asimov_snapshot::Snapshotter::new(registry, storage, Default::default());
+    let snapshotter = SnapshotTaker::new(
+        enabled_modules.into_iter().map(|enabled| enabled.manifest),
+        &profile,
+    )
+    .map_err(|e| {
+        ceprintln!("<s,r>error:</> failed to resolve enabled modules: {e}");
+        EX_UNAVAILABLE
+    })?;
 
-    for input_url in &args.urls {
+    let mut input_urls = InputUrls::new(args.urls.clone(), args.input_file.as_deref())?;
+    while let Some(input_url) = input_urls.next().await {
+        let input_url = input_url?;
         let input_url = normalize_url(&input_url).unwrap_or_else(|e| {
             if flags.verbose > 1 {
                 ceprintln!(
@@ -51,10 +69,13 @@ pub async fn save(args: &SnapSaveArgs, flags: &StandardOptions) -> Result<(), Bo
             }
             input_url.clone()
         });
-        snapshotter.snapshot(&input_url).await.map_err(|e| {
-            ceprintln!("<s,r>error:</> failed to create snapshot URL <s>{input_url}</>: {e}");
-            EX_UNAVAILABLE
-        })?;
+        snapshotter
+            .snapshot(&storage, &input_url)
+            .await
+            .map_err(|e| {
+                ceprintln!("<s,r>error:</> failed to create snapshot URL <s>{input_url}</>: {e}");
+                EX_UNAVAILABLE
+            })?;
     }
 
     Ok(())
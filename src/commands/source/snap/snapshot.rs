@@ -0,0 +1,97 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{BoxError, shared};
+use asimov_module::{ModuleManifest, resolve::Resolver};
+use asimov_runner::Executor;
+use asimov_snapshot::{Snapshot, storage::Storage};
+use jiff::Timestamp;
+use std::process::Stdio;
+
+/// Takes snapshots of URLs with the enabled modules that have a fetcher or
+/// a cataloger. Unlike `asimov_snapshot::Snapshotter`, it starts their
+/// programs configured for a profile, so that stored secrets reach them
+/// decrypted.
+pub struct SnapshotTaker {
+    modules: Vec<ModuleManifest>,
+    resolver: Resolver,
+    profile: String,
+}
+
+impl SnapshotTaker {
+    pub fn new(
+        enabled_modules: impl IntoIterator<Item = ModuleManifest>,
+        profile: &str,
+    ) -> Result<Self, BoxError> {
+        let modules: Vec<_> = enabled_modules
+            .into_iter()
+            .filter(|manifest| {
+                manifest
+                    .provides
+                    .programs
+                    .iter()
+                    .any(|p| p.ends_with("-fetcher") || p.ends_with("-cataloger"))
+            })
+            .collect();
+        let resolver = Resolver::try_from_iter(modules.iter())?;
+        Ok(Self {
+            modules,
+            resolver,
+            profile: profile.into(),
+        })
+    }
+
+    /// Fetches the content of a URL with the first module that handles it,
+    /// trying its fetcher before its cataloger, and saves it to the storage.
+    pub async fn snapshot(&self, storage: &impl Storage, url: &str) -> Result<Snapshot, BoxError> {
+        let module = self
+            .resolver
+            .resolve(url)?
+            .first()
+            .cloned()
+            .ok_or("no module found for creating snapshot")?;
+        let manifest = self
+            .modules
+            .iter()
+            .find(|manifest| manifest.name == module.name)
+            .expect("resolved modules should be enabled");
+
+        let programs = &manifest.provides.programs;
+        let fetcher = programs.iter().find(|p| p.ends_with("-fetcher"));
+        let cataloger = programs.iter().find(|p| p.ends_with("-cataloger"));
+
+        let mut errors = Vec::new();
+        for (kind, program) in [("fetcher", fetcher), ("cataloger", cataloger)] {
+            let Some(program) = program else {
+                continue;
+            };
+            let mut executor = Executor::new(program);
+            executor
+                .command()
+                .arg(url)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            shared::configure_program(executor.command().as_std_mut(), manifest, &self.profile)?;
+
+            let start_timestamp = Timestamp::now();
+            match executor.execute().await {
+                Ok(output) => {
+                    let snapshot = Snapshot {
+                        url: url.into(),
+                        data: output.into_inner(),
+                        start_timestamp,
+                        end_timestamp: Some(Timestamp::now()),
+                    };
+                    storage.save(&snapshot)?;
+                    return Ok(snapshot);
+                },
+                Err(e) => {
+                    tracing::debug!("failed creating a snapshot with {kind}: {e}");
+                    errors.push(format!("{kind}: {e}"));
+                },
+            }
+        }
+
+        Err(format!("every program failed - {}", errors.join(", ")).into())
+    }
+}
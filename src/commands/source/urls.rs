@@ -0,0 +1,167 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{SysexitsError, SysexitsError::*};
+use color_print::ceprintln;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+use tokio::sync::mpsc;
+
+/// How many lines are read ahead of the URLs being worked on.
+const READ_AHEAD: usize = 64;
+
+/// The URLs to work on: those given as arguments, where `-` stands for the
+/// lines of stdin, followed by the lines of an input file. Lines are read as
+/// they arrive, so that one command's output can be piped into another.
+/// Stdin can be given only once, as an argument or as the input file.
+#[derive(Debug)]
+pub struct InputUrls {
+    sources: VecDeque<Source>,
+}
+
+#[derive(Debug)]
+enum Source {
+    Url(String),
+    Lines {
+        name: String,
+        lines: mpsc::Receiver<std::io::Result<String>>,
+    },
+}
+
+impl InputUrls {
+    pub fn new(urls: Vec<String>, input_file: Option<&Path>) -> Result<Self, SysexitsError> {
+        // each reader of stdin would get some of its lines
+        let stdin_count = urls.iter().filter(|url| *url == "-").count()
+            + usize::from(input_file.is_some_and(|path| path.as_os_str() == "-"));
+        if stdin_count > 1 {
+            ceprintln!(
+                "<s,r>error:</> stdin can be read from only once, but was given {stdin_count} times"
+            );
+            return Err(EX_USAGE);
+        }
+
+        let mut sources: VecDeque<Source> = urls
+            .into_iter()
+            .map(|url| match url.as_str() {
+                "-" => read_lines("stdin".into(), Box::new(std::io::stdin())),
+                _ => Source::Url(url),
+            })
+            .collect();
+
+        match input_file {
+            Some(path) if path.as_os_str() == "-" => {
+                sources.push_back(read_lines("stdin".into(), Box::new(std::io::stdin())));
+            },
+            Some(path) => {
+                let file = std::fs::File::open(path).map_err(|e| {
+                    ceprintln!("<s,r>error:</> failed to open `{}`: {e}", path.display());
+                    EX_NOINPUT
+                })?;
+                sources.push_back(read_lines(path.display().to_string(), Box::new(file)));
+            },
+            None => (),
+        }
+
+        Ok(Self { sources })
+    }
+
+    /// The next URL, once it has been read.
+    pub async fn next(&mut self) -> Option<Result<String, SysexitsError>> {
+        loop {
+            match self.sources.front_mut()? {
+                Source::Url(_) => {
+                    let Some(Source::Url(url)) = self.sources.pop_front() else {
+                        unreachable!()
+                    };
+                    return Some(Ok(url));
+                },
+                Source::Lines { name, lines } => match lines.recv().await {
+                    Some(Ok(url)) => return Some(Ok(url)),
+                    Some(Err(e)) => {
+                        ceprintln!("<s,r>error:</> failed to read URLs from {name}: {e}");
+                        self.sources.clear();
+                        return Some(Err(EX_IOERR));
+                    },
+                    None => {
+                        self.sources.pop_front();
+                    },
+                },
+            }
+        }
+    }
+}
+
+/// Reads URLs from the lines of a reader, in the background.
+fn read_lines(name: String, reader: Box<dyn Read + Send>) -> Source {
+    let (sender, lines) = mpsc::channel(READ_AHEAD);
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let url = match line {
+                Ok(line) => match url_in_line(&line) {
+                    Some(url) => Ok(url.to_string()),
+                    None => continue,
+                },
+                Err(e) => Err(e),
+            };
+            let failed = url.is_err();
+            // stop once the URLs are no longer wanted
+            if sender.blocking_send(url).is_err() || failed {
+                break;
+            }
+        }
+    });
+    Source::Lines { name, lines }
+}
+
+/// The URL on a line, ignoring blank lines and `#` comments.
+fn url_in_line(line: &str) -> Option<&str> {
+    let line = line.trim();
+    (!line.is_empty() && !line.starts_with('#')).then_some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        assert_eq!(
+            url_in_line("  https://example.org/ \r"),
+            Some("https://example.org/")
+        );
+        assert_eq!(url_in_line("   "), None);
+        assert_eq!(url_in_line("# https://example.org/"), None);
+    }
+
+    #[test]
+    fn reads_stdin_at_most_once() {
+        let result = InputUrls::new(vec!["-".into()], Some(Path::new("-")));
+        assert_eq!(result.unwrap_err(), EX_USAGE);
+        let result = InputUrls::new(vec!["-".into(), "-".into()], None);
+        assert_eq!(result.unwrap_err(), EX_USAGE);
+    }
+
+    #[tokio::test]
+    async fn reads_arguments_then_the_input_file() {
+        let dir = temp_dir::TempDir::new().unwrap();
+        let path = dir.child("urls.txt");
+        std::fs::write(&path, "# crawl\nhttps://b.example/\n\nhttps://c.example/\n").unwrap();
+
+        let mut urls = InputUrls::new(vec!["https://a.example/".into()], Some(&path)).unwrap();
+        let mut read = Vec::new();
+        while let Some(url) = urls.next().await {
+            read.push(url.unwrap());
+        }
+
+        assert_eq!(
+            read,
+            [
+                "https://a.example/",
+                "https://b.example/",
+                "https://c.example/"
+            ]
+        );
+    }
+}
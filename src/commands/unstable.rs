@@ -1,9 +1,14 @@
 // This is free and unencumbered software released into the public domain.
 
-use clientele::crates::clap::Subcommand;
+use crate::BoxError;
+use clientele::{StandardOptions, crates::clap::Subcommand};
 
 #[derive(Debug, Subcommand)]
 pub enum UnstableCommand {
+    /// Manage the local cache of fetched sources
+    #[cfg(feature = "cache")]
+    #[clap(subcommand)]
+    Cache(cache::CacheCommand),
     // /// Add, remove, and resolve handles and their associated endpoints
     // #[cfg(feature = "handle")]
     // #[clap(subcommand)]
@@ -34,6 +39,15 @@ pub enum UnstableCommand {
     // },
 }
 
+impl UnstableCommand {
+    pub async fn run(self, flags: &StandardOptions) -> Result<(), BoxError> {
+        match self {
+            #[cfg(feature = "cache")]
+            UnstableCommand::Cache(command) => command.run(flags).await,
+        }
+    }
+}
+
 // #[cfg(feature = "ask")]
 // Ask {
 //     module,
@@ -1,14 +1,15 @@
 // This is free and unencumbered software released into the public domain.
 
-use asimov_runner::PrompterOptions;
+use asimov_runner::{Executor, Prompt};
 use color_print::ceprintln;
 
 use crate::{
     StandardOptions,
     SysexitsError::{self, *},
-    shared,
+    logs, shared,
 };
 use asimov_module::ModuleName;
+use std::process::Stdio;
 
 pub async fn ask(
     input: String,
@@ -101,22 +102,38 @@ pub async fn ask(
 
     let program = format!("asimov-{}-prompter", module.name);
 
-    let mut prompter = asimov_runner::Prompter::new(
-        program,
-        input.into(),
-        asimov_runner::Output::Captured,
-        PrompterOptions::builder()
-            .maybe_other(flags.debug.then_some("--debug"))
-            .maybe_model(model)
-            .build(),
-    );
-
-    let result = prompter.execute().await.map_err(|e| {
-        ceprintln!("<s,r>error:</> prompter execution failed: {e}");
-        EX_UNAVAILABLE
+    // as `asimov_runner::Prompter` runs it, but logging its stderr
+    let mut prompter = Executor::new(program);
+    prompter
+        .command()
+        .args(model.map(|model| format!("--model={model}")))
+        .args(flags.debug.then_some("--debug"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let profile = shared::profile(None)?;
+    shared::configure_program(prompter.command().as_std_mut(), module, &profile).map_err(|e| {
+        match e.downcast_ref::<SysexitsError>() {
+            Some(code) => *code,
+            None => {
+                ceprintln!(
+                    "<s,r>error:</> failed to configure module <s>{}</>: {e}",
+                    module.name
+                );
+                EX_CONFIG
+            },
+        }
     })?;
 
-    println!("{result}");
+    let prompt = Prompt::from(input).to_string();
+    let result = logs::execute_with_input(&module.name, &mut prompter, prompt.into_bytes())
+        .await
+        .map_err(|e| {
+            ceprintln!("<s,r>error:</> prompter execution failed: {e}");
+            EX_UNAVAILABLE
+        })?;
+
+    println!("{}", String::from_utf8_lossy(&result.into_inner()));
 
     Ok(())
 }
@@ -1 +1,261 @@
 // This is free and unencumbered software released into the public domain.
+
+use crate::{
+    BoxError,
+    SysexitsError::*,
+    cache::{Cache, Entry},
+    timestamps::format_ts_diff,
+};
+use asimov_module::normalization::normalize_url;
+use clientele::{StandardOptions, crates::clap::Subcommand};
+use color_print::{ceprintln, cprintln};
+use jiff::{Span, Timestamp, Zoned, tz::TimeZone};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// List the cached fetches of sources, newest first
+    #[clap(alias = "ls")]
+    List {
+        /// Set the output format [default: cli] [possible values: cli, jsonl]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
+    },
+
+    /// Remove cached fetches, all of them unless limited by age
+    Purge {
+        /// Remove only fetches older than this, such as `12h` or `7 days`
+        #[arg(value_name = "AGE", long)]
+        older_than: Option<Span>,
+    },
+
+    /// Remove the cached fetches of URLs
+    #[clap(alias = "remove")]
+    Rm {
+        /// The URLs whose cached fetches to remove
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+
+    /// Show how many fetches are cached, and how much space they take
+    Stats {
+        /// Set the output format [default: cli] [possible values: cli, json]
+        #[arg(value_name = "FORMAT", short = 'o', long)]
+        output: Option<String>,
+    },
+}
+
+impl CacheCommand {
+    pub async fn run(self, flags: &StandardOptions) -> Result<(), BoxError> {
+        use CacheCommand::*;
+        let cache = Cache::default();
+        match self {
+            List { output } => list(&cache, output.as_deref().unwrap_or("cli"), flags),
+            Purge { older_than } => purge(&cache, older_than, flags),
+            Rm { urls } => rm(&cache, urls, flags),
+            Stats { output } => stats(&cache, output.as_deref().unwrap_or("cli"), flags),
+        }
+    }
+}
+
+fn entries(cache: &Cache) -> Result<Vec<Entry>, BoxError> {
+    let mut entries = cache.entries().map_err(|e| {
+        tracing::error!("failed to read the cache: {e}");
+        EX_IOERR
+    })?;
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp()));
+    Ok(entries)
+}
+
+fn list(cache: &Cache, output: &str, _flags: &StandardOptions) -> Result<(), BoxError> {
+    let entries = entries(cache)?;
+
+    if output == "jsonl" {
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
+        }
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        ceprintln!("<s,dim>note:</> No fetches are cached.");
+        return Ok(());
+    }
+
+    let now = Zoned::now();
+    for entry in &entries {
+        let age = entry
+            .timestamp()
+            .and_then(|time| format_ts_diff(&now, &time.to_zoned(TimeZone::system())).ok())
+            .unwrap_or_else(|| entry.time.clone());
+        let module = match &entry.key.version {
+            Some(version) => format!("{} {version}", entry.key.module),
+            None => entry.key.module.clone(),
+        };
+        let format = entry
+            .key
+            .format
+            .as_deref()
+            .map(|format| format!(" as {format}"))
+            .unwrap_or_default();
+        cprintln!(
+            "<s>{}</> <dim>({module}{format}, {}, {age})</>",
+            entry.key.url,
+            format_size(entry.size)
+        );
+    }
+    Ok(())
+}
+
+fn purge(cache: &Cache, older_than: Option<Span>, flags: &StandardOptions) -> Result<(), BoxError> {
+    let before = older_than
+        .map(|age| {
+            Zoned::now()
+                .checked_sub(age.abs())
+                .map(|zoned| zoned.timestamp())
+                .map_err(|e| {
+                    ceprintln!("<s,r>error:</> invalid age `{age}`: {e}");
+                    EX_USAGE
+                })
+        })
+        .transpose()?
+        .unwrap_or(Timestamp::MAX);
+
+    let mut removed = 0;
+    for entry in entries(cache)? {
+        // entries whose time can't be read are as good as stale
+        if entry.is_fresh(before) {
+            continue;
+        }
+        remove(cache, &entry)?;
+        removed += 1;
+    }
+    let freed = collect_garbage(cache)?;
+
+    if flags.verbose > 0 {
+        ceprintln!(
+            "<s,g>✓</> Removed {removed} cached fetch{}, freeing {}.",
+            if removed == 1 { "" } else { "es" },
+            format_size(freed)
+        );
+    }
+    Ok(())
+}
+
+fn rm(cache: &Cache, urls: Vec<String>, flags: &StandardOptions) -> Result<(), BoxError> {
+    let entries = entries(cache)?;
+
+    let mut missing = false;
+    for url in urls {
+        // fetches are cached under the normalized URL
+        let normalized = normalize_url(&url).unwrap_or_else(|_| url.clone());
+        let matching: Vec<&Entry> = entries
+            .iter()
+            .filter(|entry| entry.key.url == normalized || entry.key.url == url)
+            .collect();
+        if matching.is_empty() {
+            ceprintln!("<s,r>error:</> no fetch of <s>{url}</> is cached");
+            missing = true;
+            continue;
+        }
+        for entry in &matching {
+            remove(cache, entry)?;
+        }
+        if flags.verbose > 0 {
+            ceprintln!("<s,g>✓</> Removed the cached fetches of <s>{url}</>.");
+        }
+    }
+    collect_garbage(cache)?;
+
+    if missing {
+        return Err(EX_NOINPUT.into());
+    }
+    Ok(())
+}
+
+fn stats(cache: &Cache, output: &str, _flags: &StandardOptions) -> Result<(), BoxError> {
+    let entries = entries(cache)?;
+
+    let urls: HashSet<&str> = entries.iter().map(|e| e.key.url.as_str()).collect();
+    // outputs that several fetches share are stored once
+    let mut objects: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut modules: BTreeMap<&str, usize> = BTreeMap::new();
+    for entry in &entries {
+        objects.insert(&entry.object, entry.size);
+        *modules.entry(&entry.key.module).or_default() += 1;
+    }
+    let size: u64 = objects.values().sum();
+    // newest first
+    let newest = entries.first().map(|entry| entry.time.as_str());
+    let oldest = entries.last().map(|entry| entry.time.as_str());
+
+    if output == "json" {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "fetches": entries.len(),
+                "urls": urls.len(),
+                "outputs": objects.len(),
+                "size": size,
+                "oldest": oldest,
+                "newest": newest,
+                "modules": modules,
+            }))?
+        );
+        return Ok(());
+    }
+
+    cprintln!("<s>Cached fetches:</> {}", entries.len());
+    cprintln!("<s>URLs:</> {}", urls.len());
+    cprintln!(
+        "<s>Size:</> {} in {} output{}",
+        format_size(size),
+        objects.len(),
+        if objects.len() == 1 { "" } else { "s" }
+    );
+    if let (Some(oldest), Some(newest)) = (oldest, newest) {
+        cprintln!("<s>Oldest:</> {oldest}");
+        cprintln!("<s>Newest:</> {newest}");
+    }
+    if !modules.is_empty() {
+        cprintln!("<s>Modules:</>");
+        for (module, count) in modules {
+            println!("  {module}: {count}");
+        }
+    }
+    Ok(())
+}
+
+fn remove(cache: &Cache, entry: &Entry) -> Result<(), BoxError> {
+    cache.remove(entry).map_err(|e| {
+        tracing::error!(
+            "failed to remove the cached fetch of `{}`: {e}",
+            entry.key.url
+        );
+        EX_IOERR.into()
+    })
+}
+
+fn collect_garbage(cache: &Cache) -> Result<u64, BoxError> {
+    cache.collect_garbage().map_err(|e| {
+        tracing::error!("failed to remove unused cached outputs: {e}");
+        EX_IOERR.into()
+    })
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
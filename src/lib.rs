@@ -1,9 +1,16 @@
 // This is free and unencumbered software released into the public domain.
 
 pub mod aliases;
+#[cfg(any(feature = "source", feature = "cache"))]
+pub mod cache;
 pub mod commands;
+#[cfg(any(feature = "module", feature = "source", feature = "agent"))]
+pub mod logs;
 pub mod options {}
+pub mod preferences;
 pub mod registry;
+#[cfg(any(feature = "module", feature = "source"))]
+pub mod secrets;
 pub mod shared;
 
 pub(crate) mod timestamps;
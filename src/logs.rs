@@ -0,0 +1,196 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Logs of the module programs that the CLI runs.
+//!
+//! Each invocation of a module's program is recorded as a line of JSON in
+//! `$ASIMOV_ROOT/logs/modules/<module>.log`: its command line, when it
+//! started, how long it ran, its exit status, and what it wrote to stderr if
+//! that was captured. A log is rotated once it grows past [`MAX_LOG_SIZE`],
+//! keeping [`ROTATED_LOGS`] older ones, and is shown with `asimov module
+//! logs`. The environment is not recorded, since it holds configuration
+//! values and secrets.
+
+use asimov_env::paths::asimov_root;
+use asimov_runner::{Executor, ExecutorError, ExecutorResult};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsStr,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// The size past which a log is rotated.
+pub const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// How many rotated logs are kept for each module.
+pub const ROTATED_LOGS: usize = 2;
+
+/// How much of a program's stderr is recorded, from its end.
+const MAX_STDERR_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogEntry {
+    /// When the program started, in RFC 3339 format.
+    pub time: String,
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub duration_ms: u64,
+    /// The exit code, if the program ran and wasn't ended by a signal.
+    #[serde(default)]
+    pub status: Option<i32>,
+    /// Why the program couldn't be run, if it couldn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
+impl LogEntry {
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.time.parse().ok()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+/// The logs of a module, oldest first, the last being the current one.
+pub fn log_paths(module: &str) -> Vec<PathBuf> {
+    let dir = asimov_root().join("logs").join("modules");
+    let mut paths: Vec<PathBuf> = (1..=ROTATED_LOGS)
+        .rev()
+        .map(|n| dir.join(format!("{module}.log.{n}")))
+        .collect();
+    paths.push(dir.join(format!("{module}.log")));
+    paths
+}
+
+/// A running invocation of a module's program, to be logged once it ends.
+#[derive(Debug)]
+pub struct Invocation {
+    module: String,
+    program: String,
+    args: Vec<String>,
+    started: Timestamp,
+    instant: Instant,
+}
+
+impl Invocation {
+    pub fn start(module: &str, command: &std::process::Command) -> Self {
+        let text = |s: &OsStr| s.to_string_lossy().into_owned();
+        Self {
+            module: module.into(),
+            program: Path::new(command.get_program())
+                .file_name()
+                .map(text)
+                .unwrap_or_else(|| text(command.get_program())),
+            args: command.get_args().map(text).collect(),
+            started: Timestamp::now(),
+            instant: Instant::now(),
+        }
+    }
+
+    /// Records how the invocation ended. Failing to record it is not an
+    /// error, since the program ran regardless.
+    pub fn finish(self, status: Result<Option<i32>, String>, stderr: Option<&[u8]>) {
+        let stderr = stderr.filter(|s| !s.is_empty()).map(|stderr| {
+            let start = stderr.len().saturating_sub(MAX_STDERR_SIZE);
+            String::from_utf8_lossy(&stderr[start..]).into_owned()
+        });
+        let (status, error) = match status {
+            Ok(status) => (status, None),
+            Err(error) => (None, Some(error)),
+        };
+        let entry = LogEntry {
+            time: self.started.to_string(),
+            program: self.program,
+            args: self.args,
+            duration_ms: self.instant.elapsed().as_millis() as u64,
+            status,
+            error,
+            stderr,
+        };
+        if let Err(e) = append(&self.module, &entry) {
+            tracing::warn!("failed to log the invocation of `{}`: {e}", entry.program);
+        }
+    }
+}
+
+fn append(module: &str, entry: &LogEntry) -> std::io::Result<()> {
+    let paths = log_paths(module);
+    let current = paths.last().expect("there is a current log");
+    std::fs::create_dir_all(current.parent().expect("path should have a parent"))?;
+
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    let size = std::fs::metadata(current).map(|m| m.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 > MAX_LOG_SIZE {
+        // the oldest is replaced by the next oldest, and so on
+        for pair in paths.windows(2) {
+            if pair[1].exists() {
+                std::fs::rename(&pair[1], &pair[0])?;
+            }
+        }
+    }
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(current)?
+        .write_all(&line)
+}
+
+/// Runs a module's program as [`Executor::execute`] does, and logs it.
+pub async fn execute(module: &str, executor: &mut Executor) -> ExecutorResult {
+    run(module, executor, None).await
+}
+
+/// Runs a module's program with the given input on its stdin, and logs it.
+pub async fn execute_with_input(
+    module: &str,
+    executor: &mut Executor,
+    input: Vec<u8>,
+) -> ExecutorResult {
+    run(module, executor, Some(input)).await
+}
+
+async fn run(module: &str, executor: &mut Executor, input: Option<Vec<u8>>) -> ExecutorResult {
+    let invocation = Invocation::start(module, executor.command().as_std());
+
+    let mut process = match executor.spawn().await {
+        Ok(process) => process,
+        Err(e) => {
+            invocation.finish(Err(e.to_string()), None);
+            return Err(e);
+        },
+    };
+
+    if let Some(input) = input {
+        let mut stdin = process.stdin.take().expect("should capture stdin");
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            if let Err(e) = stdin.write_all(&input).await {
+                tracing::debug!("failed to write to stdin: {e}");
+            }
+        });
+    }
+
+    let output = match process.wait_with_output().await {
+        Ok(output) => output,
+        Err(e) => {
+            invocation.finish(Err(e.to_string()), None);
+            return Err(e.into());
+        },
+    };
+    invocation.finish(Ok(output.status.code()), Some(&output.stderr));
+
+    if !output.status.success() {
+        return Err(ExecutorError::from(output));
+    }
+    Ok(Cursor::new(output.stdout))
+}
@@ -80,10 +80,10 @@ enum Command {
         command: Option<SourceCommand>,
 
         #[clap(flatten)]
-        args: commands::source::SourceFetchArgs,
+        args: Box<commands::source::SourceFetchArgs>,
     },
 
-    #[cfg(feature = "unstable")]
+    #[cfg(any(feature = "unstable", feature = "cache"))]
     #[clap(flatten)]
     Unstable(commands::unstable::UnstableCommand),
 
@@ -307,18 +307,23 @@ pub async fn main() -> SysexitsError {
 
         #[cfg(feature = "source")]
         Source { command, args } => command
-            .unwrap_or(SourceCommand::Fetch { args })
+            .unwrap_or(SourceCommand::Fetch { args: *args })
             .run(flags)
             .await
             .map_err(sysexits)
             .map(|_| EX_OK),
 
+        #[cfg(any(feature = "unstable", feature = "cache"))]
+        Unstable(command) => command.run(flags).await.map_err(sysexits).map(|_| EX_OK),
+
         External(args) => {
             let cmd = ExternalSubcommand {
                 is_debug: flags.debug,
                 pipe_output: false,
             };
-            cmd.execute(&args[0], &args[1..]).map(|result| result.code)
+            cmd.execute(&args[0], &args[1..])
+                .await
+                .map(|result| result.code)
         },
     };
 
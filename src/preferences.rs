@@ -0,0 +1,287 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Preferred modules for URLs, chosen ahead of the resolver's order.
+//!
+//! Preferences are kept in `$ASIMOV_ROOT/modules/preferences.json` and
+//! managed with `asimov module prefer` and `asimov module unprefer`. Each
+//! maps a pattern to the modules to try first for the URLs it matches: a
+//! host such as `linkedin.com`, which matches its subdomains too; a host
+//! and path such as `github.com/asimov-modules`; or a URL prefix such as
+//! `https://example.org/api/`. When several patterns match, the longest
+//! one counts.
+
+use asimov_env::paths::asimov_root;
+use asimov_module::resolve::Module;
+use clientele::SysexitsError::*;
+use color_print::ceprintln;
+use serde::{Deserialize, Serialize};
+use std::{io, path::PathBuf, rc::Rc};
+
+const PREFERENCES_FILE_NAME: &str = "preferences.json";
+
+/// The format version of preference files written by this version of the CLI.
+const PREFERENCES_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Preferences {
+    pub version: u32,
+    #[serde(default)]
+    pub preferences: Vec<Preference>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Preference {
+    pub pattern: String,
+    /// The modules to use, most preferred first.
+    pub modules: Vec<String>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            version: PREFERENCES_VERSION,
+            preferences: Vec::new(),
+        }
+    }
+}
+
+impl Preferences {
+    pub fn path() -> PathBuf {
+        asimov_root().join("modules").join(PREFERENCES_FILE_NAME)
+    }
+
+    /// Reads the preference file, which need not exist.
+    pub fn load() -> crate::Result<Self> {
+        let path = Self::path();
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                tracing::error!("failed to read `{}`: {e}", path.display());
+                return Err(EX_IOERR);
+            },
+        };
+
+        let preferences: Self = serde_json::from_str(&content).map_err(|e| {
+            ceprintln!("<s,r>error:</> `{}` is malformed: {e}", path.display());
+            EX_CONFIG
+        })?;
+        if preferences.version > PREFERENCES_VERSION {
+            ceprintln!(
+                "<s,r>error:</> `{}` has format version {}, but this version of asimov reads up to version {PREFERENCES_VERSION}",
+                path.display(),
+                preferences.version
+            );
+            return Err(EX_CONFIG);
+        }
+        Ok(preferences)
+    }
+
+    pub fn save(&self) -> crate::Result<()> {
+        let path = Self::path();
+        let result = std::fs::create_dir_all(path.parent().expect("path should have a parent"))
+            .and_then(|_| {
+                let content = serde_json::to_string_pretty(self)?;
+                let temp_path = path.with_extension("tmp");
+                std::fs::write(&temp_path, content)?;
+                std::fs::rename(&temp_path, &path)
+            });
+        result.map_err(|e| {
+            tracing::error!("failed to write `{}`: {e}", path.display());
+            EX_IOERR
+        })
+    }
+
+    /// Makes a module the most preferred one for a pattern.
+    pub fn prefer(&mut self, pattern: &str, module: &str) {
+        let pattern = normalize_pattern(pattern);
+        match self.preferences.iter_mut().find(|p| p.pattern == pattern) {
+            Some(preference) => {
+                preference.modules.retain(|m| m != module);
+                preference.modules.insert(0, module.into());
+            },
+            None => self.preferences.push(Preference {
+                pattern,
+                modules: vec![module.into()],
+            }),
+        }
+    }
+
+    /// Removes a module, or else all modules, from the preferences for a
+    /// pattern. Returns whether anything was removed.
+    pub fn unprefer(&mut self, pattern: &str, module: Option<&str>) -> bool {
+        let pattern = normalize_pattern(pattern);
+        let Some(index) = self.preferences.iter().position(|p| p.pattern == pattern) else {
+            return false;
+        };
+
+        let preference = &mut self.preferences[index];
+        let removed = match module {
+            Some(module) => {
+                let count = preference.modules.len();
+                preference.modules.retain(|m| m != module);
+                preference.modules.len() < count
+            },
+            None => {
+                preference.modules.clear();
+                true
+            },
+        };
+        if preference.modules.is_empty() {
+            self.preferences.remove(index);
+        }
+        removed
+    }
+
+    /// The most specific preference that matches a URL, if any.
+    pub fn matching(&self, url: &str) -> Option<&Preference> {
+        self.preferences
+            .iter()
+            .filter(|preference| matches(&preference.pattern, url))
+            .max_by_key(|preference| preference.pattern.len())
+    }
+
+    /// Orders the modules resolved for a URL with the preferred ones first,
+    /// in order of preference, and the others in the order given.
+    pub fn order(&self, url: &str, modules: &[Rc<Module>]) -> Vec<Rc<Module>> {
+        let mut ordered: Vec<Rc<Module>> = Vec::with_capacity(modules.len());
+        if let Some(preference) = self.matching(url) {
+            for name in &preference.modules {
+                if let Some(module) = modules.iter().find(|module| module.name == *name) {
+                    ordered.push(module.clone());
+                } else {
+                    tracing::debug!(
+                        "preferred module `{name}` doesn't handle the URL `{url}` with this command"
+                    );
+                }
+            }
+        }
+        for module in modules {
+            if !ordered.iter().any(|m| m.name == module.name) {
+                ordered.push(module.clone());
+            }
+        }
+        ordered
+    }
+}
+
+/// Lowercases the host of a pattern, and drops a leading `*.` or `www.`,
+/// since a host matches its subdomains anyway.
+fn normalize_pattern(pattern: &str) -> String {
+    let pattern = pattern.trim();
+    if pattern.contains("://") {
+        return pattern.into();
+    }
+    let (host, path) = pattern.split_at(pattern.find('/').unwrap_or(pattern.len()));
+    let host = host.to_ascii_lowercase();
+    let host = host
+        .strip_prefix("*.")
+        .or_else(|| host.strip_prefix("www."))
+        .unwrap_or(&host);
+    format!("{host}{path}")
+}
+
+/// Whether a pattern matches a URL: as a prefix if it includes a scheme,
+/// and otherwise as a host, with an optional prefix of whole path segments.
+/// Either way, the match ends at the end of the host or of a path segment.
+pub fn matches(pattern: &str, url: &str) -> bool {
+    if pattern.contains("://") {
+        // so that `https://example.org` isn't a prefix of
+        // `https://example.org.evil.com/` or `https://example.org/apiv2` of
+        // `https://example.org/api`
+        return url.strip_prefix(pattern).is_some_and(|rest| {
+            rest.is_empty() || rest.starts_with(['/', '?', '#']) || pattern.ends_with('/')
+        });
+    }
+
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+
+    let (pattern_host, pattern_path) = pattern.split_at(pattern.find('/').unwrap_or(pattern.len()));
+    let host_matches = host.eq_ignore_ascii_case(pattern_host)
+        || host
+            .to_ascii_lowercase()
+            .ends_with(&format!(".{pattern_host}"));
+    // the path prefix ends at a segment boundary, so `/asimov` isn't a
+    // prefix of `/asimov-modules`
+    let path_matches = url.path().strip_prefix(pattern_path).is_some_and(|rest| {
+        rest.is_empty()
+            || rest.starts_with('/')
+            || pattern_path.is_empty()
+            || pattern_path.ends_with('/')
+    });
+    host_matches && path_matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_hosts_paths_and_prefixes() {
+        assert!(matches(
+            "linkedin.com",
+            "https://www.linkedin.com/in/someone"
+        ));
+        assert!(matches("linkedin.com", "https://linkedin.com/"));
+        assert!(!matches("linkedin.com", "https://notlinkedin.com/"));
+        assert!(matches(
+            "github.com/asimov-modules",
+            "https://github.com/asimov-modules/x"
+        ));
+        assert!(!matches(
+            "github.com/asimov-modules",
+            "https://github.com/other"
+        ));
+        assert!(matches("github.com/asimov", "https://github.com/asimov"));
+        assert!(!matches(
+            "github.com/asimov",
+            "https://github.com/asimov-modules/x"
+        ));
+        assert!(matches(
+            "https://example.org/api/",
+            "https://example.org/api/v1"
+        ));
+        assert!(!matches(
+            "https://example.org/api/",
+            "http://example.org/api/v1"
+        ));
+        assert!(matches("https://example.org", "https://example.org/"));
+        assert!(matches(
+            "https://example.org/api",
+            "https://example.org/api?q=1"
+        ));
+        assert!(!matches(
+            "https://example.org",
+            "https://example.org.evil.com/"
+        ));
+        assert!(!matches(
+            "https://example.org/api",
+            "https://example.org/apiv2"
+        ));
+    }
+
+    #[test]
+    fn the_most_specific_preference_counts() {
+        let mut preferences = Preferences::default();
+        preferences.prefer("*.github.com", "web");
+        preferences.prefer("github.com/asimov-modules", "github");
+        preferences.prefer("github.com/asimov-modules", "brightdata");
+
+        let matching = preferences
+            .matching("https://github.com/asimov-modules/asimov-web-module")
+            .unwrap();
+        assert_eq!(matching.modules, ["brightdata", "github"]);
+        assert_eq!(
+            preferences.matching("https://github.com/").unwrap().modules,
+            ["web"]
+        );
+
+        assert!(preferences.unprefer("github.com", None));
+        assert!(preferences.matching("https://github.com/").is_none());
+    }
+}
@@ -10,3 +10,14 @@ pub fn http_client() -> reqwest::Client {
         .build()
         .expect("Failed to build HTTP client")
 }
+
+/// A client for downloads, such as release manifests and archives, which may
+/// take longer than a request to a package index.
+pub fn download_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent("asimov-module-cli")
+        .connect_timeout(Duration::from_secs(10))
+        .read_timeout(Duration::from_secs(30))
+        .build()
+        .expect("Failed to build HTTP client")
+}
@@ -0,0 +1,489 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Encryption of stored secret configuration values.
+//!
+//! Secrets are encrypted with AES-256-GCM under a key read from a local key
+//! file or derived from a passphrase. Which of the two is in use is recorded
+//! in the keyring file under `$ASIMOV_ROOT/configs`. Each value is bound to
+//! its profile, module, and variable, so that an encrypted file can't be
+//! moved to stand in for another.
+//!
+//! The default key file is generated in `$ASIMOV_ROOT/configs`, next to the
+//! secrets it encrypts, so it only guards against their accidental
+//! disclosure, such as in a shared or backed up configuration directory,
+//! and not against anyone who can read `$ASIMOV_ROOT`. A key file kept
+//! elsewhere or a passphrase, as chosen with `asimov module config rekey`,
+//! guards against that too.
+
+use crate::BoxError;
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload, rand_core::RngCore},
+};
+use asimov_env::paths::asimov_root;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use clientele::SysexitsError::*;
+use color_print::ceprintln;
+use hmac::Hmac;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The environment variable that supplies the passphrase, when secrets are
+/// encrypted with one, instead of prompting for it.
+pub const PASSPHRASE_ENV: &str = "ASIMOV_SECRETS_PASSPHRASE";
+
+/// Marks a stored value as encrypted.
+const PREFIX: &str = "asimov-encrypted:v1:";
+
+const KEYRING_FILE_NAME: &str = ".keyring.json";
+const PREVIOUS_KEYRING_FILE_NAME: &str = ".keyring.old.json";
+const KEY_FILE_NAME: &str = ".secret.key";
+
+/// The PBKDF2-HMAC-SHA256 work factor for new passphrases.
+const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Encrypted under a passphrase's key to tell a wrong passphrase apart.
+const VERIFIER: &str = "asimov";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct KeyringFile {
+    version: u32,
+    #[serde(flatten)]
+    source: KeySource,
+}
+
+/// Where the key that encrypts secrets comes from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+enum KeySource {
+    KeyFile { path: PathBuf },
+    Passphrase(PassphraseKey),
+}
+
+/// How a key was derived from a passphrase, which is kept alongside what it
+/// encrypts, so that the passphrase alone derives the same key again.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PassphraseKey {
+    salt: String,
+    iterations: u32,
+    verifier: String,
+}
+
+/// The key that encrypts and decrypts stored secrets.
+pub struct Keyring {
+    cipher: Aes256Gcm,
+}
+
+/// A new key for `asimov module config rekey`, which takes effect only once
+/// committed, after everything has been re-encrypted with it. The previous
+/// key stays readable until retired, for any secret not yet replaced.
+pub struct NewKeyring {
+    keyring: Keyring,
+    source: KeySource,
+    /// A generated key to write to its key file on commit.
+    key: Option<[u8; 32]>,
+}
+
+/// The key to switch to with `asimov module config rekey`.
+pub enum NewKey {
+    /// A newly generated key in the default key file.
+    Generate,
+    /// The key in the given file, created if it doesn't exist.
+    KeyFile(PathBuf),
+    /// A key derived from the given passphrase.
+    Passphrase(String),
+}
+
+/// Whether a stored value is encrypted.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Decrypts a stored value if it is encrypted, and passes it through if not.
+/// The context names the profile, module, and variable the value is stored
+/// for, as in `default/serpapi/api-key`.
+pub fn reveal(context: &str, value: String) -> Result<String, BoxError> {
+    if !is_encrypted(&value) {
+        return Ok(value);
+    }
+    let keyring = keyring()?;
+    if let Some(plaintext) = keyring.try_decrypt(context, &value) {
+        return Ok(plaintext);
+    }
+    // a rekey that was interrupted leaves some secrets under the previous key
+    if let Some(previous) = previous_keyring()?
+        && let Some(plaintext) = previous.try_decrypt(context, &value)
+    {
+        return Ok(plaintext);
+    }
+    keyring.decrypt(context, &value)
+}
+
+/// Encrypts a value for storage, creating a key file on first use.
+pub fn conceal(context: &str, value: &str) -> Result<String, BoxError> {
+    Ok(keyring()?.encrypt(context, value))
+}
+
+/// The keyring in use, opened once per process. Opening it may prompt for
+/// the passphrase.
+pub fn keyring() -> Result<&'static Keyring, BoxError> {
+    static KEYRING: OnceLock<Keyring> = OnceLock::new();
+    if let Some(keyring) = KEYRING.get() {
+        return Ok(keyring);
+    }
+
+    let keyring = match read_keyring_file(KEYRING_FILE_NAME)? {
+        Some(source) => open(&source)?,
+        None => {
+            let new = NewKeyring::new(NewKey::Generate)?;
+            new.commit()?;
+            new.keyring
+        },
+    };
+    Ok(KEYRING.get_or_init(|| keyring))
+}
+
+/// The key in use before a rekey that didn't finish, if any.
+fn previous_keyring() -> Result<Option<&'static Keyring>, BoxError> {
+    static PREVIOUS: OnceLock<Option<Keyring>> = OnceLock::new();
+    if let Some(previous) = PREVIOUS.get() {
+        return Ok(previous.as_ref());
+    }
+
+    let previous = match read_keyring_file(PREVIOUS_KEYRING_FILE_NAME)? {
+        Some(source) => Some(open(&source)?),
+        None => None,
+    };
+    Ok(PREVIOUS.get_or_init(|| previous).as_ref())
+}
+
+fn configs_dir() -> PathBuf {
+    asimov_root().join("configs")
+}
+
+/// Where a key file that a rekey overwrites is kept until it is retired.
+fn previous_key_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".old");
+    path.with_file_name(file_name)
+}
+
+fn read_keyring_file(file_name: &str) -> Result<Option<KeySource>, BoxError> {
+    let path = configs_dir().join(file_name);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            tracing::error!("failed to read `{}`: {e}", path.display());
+            return Err(EX_IOERR.into());
+        },
+    };
+
+    let file: KeyringFile = serde_json::from_str(&content).map_err(|e| {
+        ceprintln!("<s,r>error:</> `{}` is malformed: {e}", path.display());
+        EX_CONFIG
+    })?;
+    Ok(Some(file.source))
+}
+
+fn open(source: &KeySource) -> Result<Keyring, BoxError> {
+    match source {
+        KeySource::KeyFile { path } => {
+            let content = std::fs::read(path).map_err(|e| {
+                ceprintln!(
+                    "<s,r>error:</> failed to read the secrets key file `{}`: {e}",
+                    path.display()
+                );
+                ceprintln!(
+                    "<s,dim>hint:</> Secrets stored with it can't be read without it; store them again after: <s>asimov module config rekey</>"
+                );
+                EX_CONFIG
+            })?;
+            Ok(Keyring::from_key_material(&content))
+        },
+        KeySource::Passphrase(key) => {
+            let passphrase = read_passphrase()?;
+            key.open(&passphrase).ok_or_else(|| {
+                ceprintln!("<s,r>error:</> wrong passphrase for stored secrets");
+                EX_NOPERM.into()
+            })
+        },
+    }
+}
+
+fn read_passphrase() -> Result<String, BoxError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        ceprintln!("<s,r>error:</> stored secrets are encrypted with a passphrase");
+        ceprintln!("<s,dim>hint:</> Provide it in the environment variable <s>{PASSPHRASE_ENV}</>");
+        return Err(EX_NOPERM.into());
+    }
+
+    dialoguer::Password::new()
+        .with_prompt("Passphrase for stored secrets")
+        .interact()
+        .map_err(|e| {
+            tracing::error!("failed to read the passphrase from the terminal: {e}");
+            EX_IOERR.into()
+        })
+}
+
+impl PassphraseKey {
+    /// Derives a new key from a passphrase, with a new salt.
+    pub fn new(passphrase: &str) -> (Self, Keyring) {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let keyring = Keyring::from_passphrase(passphrase, &salt, PBKDF2_ITERATIONS);
+        let key = Self {
+            salt: BASE64.encode(salt),
+            iterations: PBKDF2_ITERATIONS,
+            verifier: keyring.encrypt("", VERIFIER),
+        };
+        (key, keyring)
+    }
+
+    /// Derives the key again, unless the passphrase is the wrong one.
+    pub fn open(&self, passphrase: &str) -> Option<Keyring> {
+        let salt = BASE64.decode(&self.salt).ok()?;
+        let keyring = Keyring::from_passphrase(passphrase, &salt, self.iterations);
+        (keyring.try_decrypt("", &self.verifier).as_deref() == Some(VERIFIER)).then_some(keyring)
+    }
+}
+
+impl Keyring {
+    fn from_key_material(material: &[u8]) -> Self {
+        let key: [u8; 32] = Sha256::digest(material).into();
+        Self::from_key(&key)
+    }
+
+    fn from_passphrase(passphrase: &str, salt: &[u8], iterations: u32) -> Self {
+        let mut key = [0u8; 32];
+        pbkdf2_sha256(passphrase.as_bytes(), salt, iterations, &mut key);
+        Self::from_key(&key)
+    }
+
+    fn from_key(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    pub fn encrypt(&self, context: &str, value: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: context.as_bytes(),
+                },
+            )
+            .expect("encryption should not fail");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        format!("{PREFIX}{}", BASE64.encode(sealed))
+    }
+
+    pub fn decrypt(&self, context: &str, value: &str) -> Result<String, BoxError> {
+        self.try_decrypt(context, value).ok_or_else(|| {
+            ceprintln!("<s,r>error:</> the stored secret `{context}` can't be decrypted");
+            ceprintln!(
+                "<s,dim>hint:</> It was encrypted with another key, or altered; store it again with: <s>asimov module config set</>"
+            );
+            EX_DATAERR.into()
+        })
+    }
+
+    /// Decrypts a value, unless it was encrypted with another key or in
+    /// another context, or altered.
+    pub fn try_decrypt(&self, context: &str, value: &str) -> Option<String> {
+        let sealed = value
+            .strip_prefix(PREFIX)
+            .and_then(|encoded| BASE64.decode(encoded.trim()).ok())
+            .filter(|sealed| sealed.len() > 12)?;
+        let (nonce, ciphertext) = sealed.split_at(12);
+
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: context.as_bytes(),
+                },
+            )
+            .ok()?;
+
+        String::from_utf8(plaintext).ok()
+    }
+}
+
+impl NewKeyring {
+    pub fn new(new_key: NewKey) -> Result<Self, BoxError> {
+        Ok(match new_key {
+            NewKey::Generate => {
+                let mut key = [0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                Self {
+                    keyring: Keyring::from_key_material(&key),
+                    source: KeySource::KeyFile {
+                        path: configs_dir().join(KEY_FILE_NAME),
+                    },
+                    key: Some(key),
+                }
+            },
+            NewKey::KeyFile(path) => {
+                let path = std::path::absolute(&path)?;
+                match std::fs::read(&path) {
+                    Ok(content) => Self {
+                        keyring: Keyring::from_key_material(&content),
+                        source: KeySource::KeyFile { path },
+                        key: None,
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        let mut key = [0u8; 32];
+                        OsRng.fill_bytes(&mut key);
+                        Self {
+                            keyring: Keyring::from_key_material(&key),
+                            source: KeySource::KeyFile { path },
+                            key: Some(key),
+                        }
+                    },
+                    Err(e) => {
+                        ceprintln!(
+                            "<s,r>error:</> failed to read the key file `{}`: {e}",
+                            path.display()
+                        );
+                        return Err(EX_NOINPUT.into());
+                    },
+                }
+            },
+            NewKey::Passphrase(passphrase) => {
+                let (key, keyring) = PassphraseKey::new(&passphrase);
+                Self {
+                    keyring,
+                    source: KeySource::Passphrase(key),
+                    key: None,
+                }
+            },
+        })
+    }
+
+    pub fn keyring(&self) -> &Keyring {
+        &self.keyring
+    }
+
+    /// Makes the new key the one in use, keeping the previous one readable
+    /// until [`NewKeyring::retire_previous`].
+    pub fn commit(&self) -> Result<(), BoxError> {
+        let configs_dir = configs_dir();
+        std::fs::create_dir_all(&configs_dir)?;
+
+        if let Some(mut previous) = read_keyring_file(KEYRING_FILE_NAME)? {
+            if let (Some(_), KeySource::KeyFile { path: new_path }) = (&self.key, &self.source)
+                && let KeySource::KeyFile { path } = &mut previous
+                && path == new_path
+            {
+                let kept_path = previous_key_path(path);
+                write_private(&kept_path, &std::fs::read(&path)?)?;
+                *path = kept_path;
+            }
+            write_keyring_file(&configs_dir.join(PREVIOUS_KEYRING_FILE_NAME), previous)?;
+        }
+
+        if let (Some(key), KeySource::KeyFile { path }) = (&self.key, &self.source) {
+            write_private(path, key)?;
+        }
+        write_keyring_file(&configs_dir.join(KEYRING_FILE_NAME), self.source.clone())
+    }
+
+    /// Forgets the previous key, once no secret is encrypted with it anymore.
+    pub fn retire_previous(&self) -> Result<(), BoxError> {
+        if let KeySource::KeyFile { path } = &self.source {
+            remove_if_exists(&previous_key_path(path))?;
+        }
+        remove_if_exists(&configs_dir().join(PREVIOUS_KEYRING_FILE_NAME))
+    }
+}
+
+fn write_keyring_file(path: &Path, source: KeySource) -> Result<(), BoxError> {
+    let file = KeyringFile { version: 1, source };
+    write_private(path, serde_json::to_string_pretty(&file)?.as_bytes())
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), BoxError> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            tracing::error!("failed to remove `{}`: {e}", path.display());
+            Err(e.into())
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Replaces a file with content only the user can read.
+fn write_private(path: &Path, content: &[u8]) -> Result<(), BoxError> {
+    use std::io::Write;
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // the mode only applies to a new file
+    remove_if_exists(&temp_path)?;
+    options.open(&temp_path)?.write_all(content)?;
+    std::fs::rename(&temp_path, path).inspect_err(|e| {
+        tracing::error!("failed to write `{}`: {e}", path.display());
+    })?;
+    Ok(())
+}
+
+/// PBKDF2 with HMAC-SHA256, as specified in RFC 8018.
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, output: &mut [u8]) {
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, iterations, output)
+        .expect("HMAC should take a key of any length");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbkdf2_matches_the_rfc_7914_test_vector() {
+        let mut output = [0u8; 64];
+        pbkdf2_sha256(b"passwd", b"salt", 1, &mut output);
+        assert_eq!(
+            hex::encode(output),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+        );
+    }
+
+    #[test]
+    fn a_secret_decrypts_only_in_its_own_context() {
+        let keyring = Keyring::from_key(&[7; 32]);
+        let sealed = keyring.encrypt("default/demo/api-key", "s3cret");
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("s3cret"));
+
+        assert_eq!(
+            keyring.decrypt("default/demo/api-key", &sealed).unwrap(),
+            "s3cret"
+        );
+        assert!(keyring.decrypt("staging/demo/api-key", &sealed).is_err());
+    }
+}
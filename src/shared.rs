@@ -6,6 +6,12 @@ use clientele::{Subcommand, SubcommandsProvider, SysexitsError::*};
 use color_print::{ceprintln, cstr};
 use std::rc::Rc;
 
+/// The environment variable that selects the configuration profile.
+pub const PROFILE_ENV: &str = "ASIMOV_PROFILE";
+
+/// The configuration profile used when none is selected.
+pub const DEFAULT_PROFILE: &str = "default";
+
 /// Locates the given subcommand or prints an error.
 pub fn locate_subcommand(name: &str) -> Result<Subcommand> {
     match SubcommandsProvider::find("asimov-", name) {
@@ -61,12 +67,31 @@ pub async fn installed_modules(
     Ok(modules)
 }
 
+/// Picks the module to handle a URL: the one named, if it is among those
+/// resolved and enabled, or else the first enabled one of those resolved,
+/// with the preferred modules tried first.
 pub async fn pick_module(
     registry: &asimov_registry::Registry,
     url: impl AsRef<str>,
     modules: &[Rc<Module>],
     filter: Option<&str>,
 ) -> Result<Rc<Module>> {
+    let modules = pick_modules(registry, url, modules, filter).await?;
+    Ok(modules
+        .into_iter()
+        .next()
+        .expect("at least one module is picked"))
+}
+
+/// Picks the modules to try in turn for a URL: the one named, as
+/// [`pick_module`] does, or else every enabled one of those resolved, with
+/// the preferred modules first.
+pub async fn pick_modules(
+    registry: &asimov_registry::Registry,
+    url: impl AsRef<str>,
+    modules: &[Rc<Module>],
+    filter: Option<&str>,
+) -> Result<Vec<Rc<Module>>> {
     let url = url.as_ref();
 
     if let Some(filter) = filter {
@@ -101,49 +126,189 @@ pub async fn pick_module(
             );
             Err(EX_UNAVAILABLE)
         } else {
-            Ok(module.clone())
+            Ok(vec![module.clone()])
         }
     } else {
-        let mut iter = modules.iter();
-        loop {
-            let module = iter.next().ok_or_else(|| {
+        let picked = enabled_candidates(registry, url, modules).await?;
+        if picked.is_empty() {
+            ceprintln!("<s,r>error:</> failed to find a module to handle the URL <s>{url}</>");
+            let module_count = modules.len();
+            if module_count > 0 {
+                if module_count == 1 {
                     ceprintln!(
-                        "<s,r>error:</> failed to find a module to handle the URL <s>{url}</>"
+                        "<s,dim>hint:</> Found <s>{module_count}</> installed module that could handle this URL but is disabled."
                     );
-                    let module_count = modules.len();
-                    if module_count > 0 {
-                        if module_count == 1 {
-                            ceprintln!("<s,dim>hint:</> Found <s>{module_count}</> installed module that could handle this URL but is disabled.");
-                        } else {
-                            ceprintln!("<s,dim>hint:</> Found <s>{module_count}</> installed modules that could handle this URL but are disabled.");
-                        }
-                        ceprintln!("<s,dim>hint:</> A module can be enabled with: <s>asimov module enable <<module>></>");
-                        ceprintln!("<s,dim>hint:</> Available modules:");
-                        for module in modules {
-                            ceprintln!("<s,dim>hint:</>\t<s>{}</>", module.name);
-                        }
-                    }
-                    EX_UNAVAILABLE
-                })?;
-
-            let module_name = module.name.parse().map_err(|e| {
-                ceprintln!("<s,r>error:</> {e}");
-                EX_DATAERR
-            })?;
-
-            if registry
-                .is_module_enabled(&module_name)
-                .await
-                .map_err(|e| {
+                } else {
                     ceprintln!(
-                        "<s,r>error:</> error while checking whether module <s>{}</> is enabled: {e}",
-                        module.name
+                        "<s,dim>hint:</> Found <s>{module_count}</> installed modules that could handle this URL but are disabled."
                     );
-                    EX_IOERR
-                })?
-            {
-                return Ok(module.clone());
+                }
+                ceprintln!(
+                    "<s,dim>hint:</> A module can be enabled with: <s>asimov module enable <<module>></>"
+                );
+                ceprintln!("<s,dim>hint:</> Available modules:");
+                for module in modules {
+                    ceprintln!("<s,dim>hint:</>\t<s>{}</>", module.name);
+                }
             }
+            return Err(EX_UNAVAILABLE);
         }
+        Ok(picked)
     }
 }
+
+/// The enabled modules among those resolved for a URL, in the order that
+/// they are tried: the preferred modules first, then in the order of
+/// resolution. Unlike [`pick_modules`], finding none isn't an error.
+pub async fn enabled_candidates(
+    registry: &asimov_registry::Registry,
+    url: &str,
+    modules: &[Rc<Module>],
+) -> Result<Vec<Rc<Module>>> {
+    let ordered = crate::preferences::Preferences::load()?.order(url, modules);
+    let mut picked: Vec<Rc<Module>> = Vec::new();
+    for module in &ordered {
+        // a module that matches in several ways is resolved for each of them
+        if picked.iter().any(|m| m.name == module.name) {
+            continue;
+        }
+
+        let module_name = module.name.parse().map_err(|e| {
+            ceprintln!("<s,r>error:</> {e}");
+            EX_DATAERR
+        })?;
+
+        if registry
+            .is_module_enabled(&module_name)
+            .await
+            .map_err(|e| {
+                ceprintln!(
+                    "<s,r>error:</> error while checking whether module <s>{}</> is enabled: {e}",
+                    module.name
+                );
+                EX_IOERR
+            })?
+        {
+            picked.push(module.clone());
+        }
+    }
+    Ok(picked)
+}
+
+/// The content type of a local file, if it can be told from its content.
+#[cfg(any(feature = "module", feature = "source"))]
+pub fn content_type(path: &str, flags: &clientele::StandardOptions) -> Option<String> {
+    infer::get_from_path(path)
+        .inspect_err(|e| {
+            if flags.verbose > 1 {
+                ceprintln!("<s,y>warning:</> failed to determine MIME type of <s>{path}</>: {e}")
+            }
+        })
+        .ok()
+        .flatten()
+        .map(|t| t.mime_type().to_string())
+}
+
+/// The modules that handle an input of `asimov source read`, in the order
+/// it considers them: those that handle the content type of a local file
+/// first, then those that handle its URL.
+#[cfg(any(feature = "module", feature = "source"))]
+pub fn read_candidates(
+    resolver: &asimov_module::resolve::Resolver,
+    input_url: &str,
+    flags: &clientele::StandardOptions,
+) -> Vec<Rc<Module>> {
+    let mime_modules = content_type(input_url, flags)
+        .and_then(|mt| mt.parse().ok())
+        .map(|mime_type| resolver.resolve_content_type(&mime_type))
+        .unwrap_or_default();
+
+    let normalized_url =
+        asimov_module::normalization::normalize_url(input_url).unwrap_or_else(|e| {
+            if flags.verbose > 1 {
+                ceprintln!(
+                    "<s,y>warning:</> using given unmodified URL, normalization failed: {e}"
+                );
+            }
+            input_url.to_string()
+        });
+
+    let url_modules = resolver
+        .resolve(&normalized_url)
+        .inspect_err(|e| {
+            if flags.verbose > 1 {
+                ceprintln!(
+                    "<s,r>warning:</> failed while resolving URL <s>{normalized_url}</>: {e}"
+                );
+            }
+        })
+        .unwrap_or_default();
+
+    // mime modules first for prioritization
+    [mime_modules, url_modules].concat()
+}
+
+/// Determines the configuration profile to use: the given one, else the one
+/// selected by `ASIMOV_PROFILE`, else `default`. Profile names become
+/// directory names, so anything that could escape the configuration
+/// directory is rejected.
+pub fn profile(requested: Option<&str>) -> Result<String> {
+    let profile = match requested {
+        Some(profile) => profile.to_string(),
+        None => std::env::var(PROFILE_ENV)
+            .ok()
+            .filter(|profile| !profile.is_empty())
+            .unwrap_or_else(|| DEFAULT_PROFILE.into()),
+    };
+
+    let is_valid_name = !profile.starts_with('.')
+        && profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !is_valid_name {
+        ceprintln!("<s,r>error:</> invalid configuration profile name: `{profile}`");
+        return Err(EX_USAGE);
+    }
+
+    Ok(profile)
+}
+
+/// Hands a module's configuration in the given profile to one of its
+/// programs. Stored values are passed through the environment variables
+/// that the manifest names for them, decrypted and without overriding any
+/// already set, and the profile itself through `ASIMOV_PROFILE`. Every
+/// program of a module is to be started so, since stored secrets are
+/// encrypted.
+pub fn configure_program(
+    command: &mut std::process::Command,
+    manifest: &ModuleManifest,
+    profile: &str,
+) -> std::result::Result<(), crate::BoxError> {
+    command.env(PROFILE_ENV, profile);
+
+    let conf_dir = asimov_env::paths::asimov_root()
+        .join("configs")
+        .join(profile)
+        .join(&manifest.name);
+    for var in manifest
+        .config
+        .iter()
+        .flat_map(|config| config.variables.iter())
+    {
+        let Some(env_name) = var.environment.as_deref() else {
+            continue;
+        };
+        if var.name.starts_with('.') || var.name.contains(['/', '\\']) {
+            continue;
+        }
+        if std::env::var_os(env_name).is_some() {
+            continue;
+        }
+        if let Ok(value) = std::fs::read_to_string(conf_dir.join(&var.name)) {
+            let context = format!("{profile}/{}/{}", manifest.name, var.name);
+            command.env(env_name, crate::secrets::reveal(&context, value)?);
+        }
+    }
+
+    Ok(())
+}
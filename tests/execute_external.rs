@@ -6,8 +6,8 @@ use clientele::SysexitsError::*;
 mod shared;
 use shared::{Result, TEST_FILES, TEST_PREFIX};
 
-#[test]
-pub fn test_execute_external() -> Result<()> {
+#[tokio::test]
+pub async fn test_execute_external() -> Result<()> {
     let _dir = shared::init()?;
 
     for file in TEST_FILES.iter() {
@@ -19,7 +19,7 @@ pub fn test_execute_external() -> Result<()> {
         };
 
         let cd_name = file.name.trim_start_matches(TEST_PREFIX);
-        let result = external_cmd.execute(cd_name, &[]);
+        let result = external_cmd.execute(cd_name, &[]).await;
         // assert_eq!(result.is_ok(), file.should_be_listed);
 
         if let Ok(result) = result {
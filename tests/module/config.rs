@@ -1,11 +1,9 @@
 // This is free and unencumbered software released into the public domain.
 
+use crate::shared::{self, Run};
 use clientele::SysexitsError::*;
 use indoc::indoc;
-use std::{
-    path::{Path, PathBuf},
-    process::{Command, Stdio},
-};
+use std::path::{Path, PathBuf};
 use temp_dir::TempDir;
 
 type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
@@ -36,11 +34,6 @@ const MANIFEST: &str = indoc! {r#"
 
 struct Sandbox(TempDir);
 
-struct Run {
-    code: i32,
-    stdout: String,
-}
-
 impl Sandbox {
     fn new() -> Result<Self> {
         Self::with_manifest(MANIFEST)
@@ -48,9 +41,7 @@ impl Sandbox {
 
     fn with_manifest(manifest: &str) -> Result<Self> {
         let dir = TempDir::new()?;
-        let module_dir = dir.child("modules/installed/demo");
-        std::fs::create_dir_all(&module_dir)?;
-        std::fs::write(module_dir.join("manifest.json"), manifest)?;
+        shared::install_module(dir.path(), "demo", manifest)?;
         Ok(Self(dir))
     }
 
@@ -83,23 +74,14 @@ impl Sandbox {
     }
 
     fn module_env(&self, args: &[&str], env: &[(&str, &str)]) -> Result<Run> {
-        let mut command = Command::new(env!("CARGO_BIN_EXE_asimov"));
+        let mut command = shared::command(self.root(), &["module"]);
         command
-            .arg("module")
             .args(args)
-            .env("ASIMOV_ROOT", self.root())
             // start from a known environment, whatever the developer's shell has
             .env_remove(KEY_ENV)
             .env_remove(HOST_ENV)
-            .envs(env.iter().copied())
-            // never inherit a terminal: a test must fail rather than block
-            .stdin(Stdio::null());
-
-        let output = command.output()?;
-        Ok(Run {
-            code: output.status.code().expect("should exit normally"),
-            stdout: String::from_utf8(output.stdout)?,
-        })
+            .envs(env.iter().copied());
+        shared::run(&mut command)
     }
 }
 
@@ -181,6 +163,45 @@ fn a_rejected_batch_changes_nothing() -> Result {
     Ok(())
 }
 
+#[test]
+fn values_must_meet_the_declared_constraints() -> Result {
+    let sandbox = Sandbox::with_manifest(indoc! {r#"
+        {
+          "name": "demo",
+          "config": {
+            "variables": [{ "name": "port" }, { "name": "region" }]
+          }
+        }
+    "#})?;
+    // as recorded from the manifest that the module was installed with
+    std::fs::write(
+        sandbox
+            .root()
+            .join("modules/installed/demo/constraints.json"),
+        indoc! {r#"
+            {
+              "port": { "type": "integer", "min": 1, "max": 65535 },
+              "region": { "enum": ["eu", "us"] }
+            }
+        "#},
+    )?;
+
+    for assignment in ["port=http", "port=0", "region=asia"] {
+        let run = sandbox.config(&["set", "demo", "region=eu", assignment])?;
+        assert_eq!(run.code, EX_DATAERR as i32, "should reject `{assignment}`");
+        assert!(
+            !sandbox.value_file("region").exists(),
+            "a rejected batch was applied"
+        );
+    }
+
+    let run = sandbox.config(&["set", "demo", "port=8080", "region=us"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert_eq!(std::fs::read_to_string(sandbox.value_file("port"))?, "8080");
+
+    Ok(())
+}
+
 #[cfg(unix)]
 #[test]
 fn stored_values_are_private_to_the_user() -> Result {
@@ -323,3 +344,218 @@ fn setup_without_a_terminal_fails_rather_than_waiting() -> Result {
 
     Ok(())
 }
+
+#[cfg(unix)]
+#[test]
+fn edit_applies_a_valid_document_and_rejects_an_invalid_one() -> Result {
+    let sandbox = Sandbox::new()?;
+    sandbox.config(&["set", "demo", "host=first"])?;
+
+    let run = sandbox.config_env(
+        &["edit", "demo"],
+        &[("VISUAL", "sed -i -e 's/first/second/'")],
+    )?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert_eq!(
+        std::fs::read_to_string(sandbox.value_file("host"))?,
+        "second"
+    );
+
+    let run = sandbox.config_env(
+        &["edit", "demo"],
+        &[("VISUAL", "sed -i -e 's/^host: .*/nonexistent: value/'")],
+    )?;
+    assert_eq!(run.code, EX_DATAERR as i32);
+    assert_eq!(
+        std::fs::read_to_string(sandbox.value_file("host"))?,
+        "second",
+        "an invalid document was applied"
+    );
+
+    let run = sandbox.config_env(&["edit", "demo"], &[("VISUAL", "sed -i -e '/^host: /d'")])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(
+        !sandbox.value_file("host").exists(),
+        "a removed variable stayed set"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn export_and_import_carry_encrypted_secrets_to_another_machine() -> Result {
+    let passphrase = [("ASIMOV_CONFIG_PASSPHRASE", "correct horse")];
+
+    let source = Sandbox::new()?;
+    source.config(&["set", "demo", "host=source.example", "api-key=s3cret-value"])?;
+
+    let run = source.config(&["export", "demo"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(
+        !run.stdout.contains("api-key"),
+        "secrets must be opted into"
+    );
+
+    let run = source.config_env(&["export", "demo", "--include-secrets"], &passphrase)?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(
+        !run.stdout.contains("s3cret-value"),
+        "exported a plaintext secret"
+    );
+
+    let target = Sandbox::new()?;
+    let document = target.root().join("demo.yaml");
+    std::fs::write(&document, &run.stdout)?;
+    let document = document.to_str().expect("path should be UTF-8");
+
+    let run = target.config_env(
+        &["import", document],
+        &[("ASIMOV_CONFIG_PASSPHRASE", "wrong")],
+    )?;
+    assert_eq!(run.code, EX_NOPERM as i32);
+    assert!(
+        !target.value_file("host").exists(),
+        "a rejected import was applied"
+    );
+
+    let run = target.config_env(&["import", document], &passphrase)?;
+    assert_eq!(run.code, EX_OK as i32);
+    let host = target.config(&["get", "demo", "host"])?;
+    assert_eq!(host.stdout.trim(), "source.example");
+    let key = target.config(&["get", "demo", "api-key"])?;
+    assert_eq!(key.stdout.trim(), "s3cret-value");
+
+    // the environment still takes precedence over what was imported
+    let host = target.config_env(&["get", "demo", "host"], &[(HOST_ENV, "env.example")])?;
+    assert_eq!(host.stdout.trim(), "env.example");
+
+    Ok(())
+}
+
+#[test]
+fn config_export_writes_the_stored_value_rather_than_the_environment_one() -> Result {
+    let sandbox = Sandbox::new()?;
+    sandbox.config(&["set", "demo", "host=stored.example"])?;
+
+    let env = [(HOST_ENV, "env.example")];
+    let run = sandbox.config_env(&["export", "demo", "-o", "json"], &env)?;
+    assert_eq!(run.code, EX_OK as i32);
+    let document: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    assert_eq!(document["config"]["host"], "stored.example");
+
+    Ok(())
+}
+
+#[test]
+fn import_with_replace_unsets_what_the_document_leaves_out() -> Result {
+    let sandbox = Sandbox::new()?;
+    let document = sandbox.root().join("demo.yaml");
+    std::fs::write(&document, "version: 1\nmodule: demo\n")?;
+    let document = document.to_str().expect("path should be UTF-8");
+
+    sandbox.config(&["set", "demo", "host=kept.example"])?;
+    let run = sandbox.config(&["import", document])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(sandbox.value_file("host").exists(), "merging unset a value");
+
+    let run = sandbox.config(&["import", "--replace", document])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(
+        !sandbox.value_file("host").exists(),
+        "replacing kept a value"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn profiles_keep_separate_values() -> Result {
+    let sandbox = Sandbox::new()?;
+
+    sandbox.config(&["set", "demo", "host=prod.example"])?;
+    sandbox.config(&[
+        "set",
+        "--profile",
+        "staging",
+        "demo",
+        "host=staging.example",
+    ])?;
+
+    let run = sandbox.config(&["get", "demo", "host"])?;
+    assert_eq!(run.stdout.trim(), "prod.example");
+
+    let run = sandbox.config(&["get", "demo", "host", "--profile", "staging"])?;
+    assert_eq!(run.stdout.trim(), "staging.example");
+
+    let run = sandbox.config_env(&["get", "demo", "host"], &[("ASIMOV_PROFILE", "staging")])?;
+    assert_eq!(run.stdout.trim(), "staging.example");
+
+    let run = sandbox.config_env(&["profiles", "demo"], &[("ASIMOV_PROFILE", "staging")])?;
+    assert_eq!(run.code, EX_OK as i32);
+    let profiles: Vec<_> = run.stdout.lines().collect();
+    assert_eq!(profiles.len(), 2);
+    assert!(profiles[0].contains("default") && !profiles[0].contains('*'));
+    assert!(profiles[1].contains("staging") && profiles[1].contains('*'));
+
+    let run = sandbox.config(&["get", "demo", "host", "--profile", "../escape"])?;
+    assert_eq!(run.code, EX_USAGE as i32);
+
+    Ok(())
+}
+
+#[test]
+fn secrets_are_stored_encrypted_and_read_back_decrypted() -> Result {
+    let sandbox = Sandbox::new()?;
+
+    sandbox.config(&["set", "demo", "api-key=s3cret-value", "host=example.org"])?;
+
+    let stored = std::fs::read_to_string(sandbox.value_file("api-key"))?;
+    assert!(!stored.contains("s3cret-value"));
+    assert_eq!(
+        std::fs::read_to_string(sandbox.value_file("host"))?,
+        "example.org"
+    );
+
+    let run = sandbox.config(&["get", "demo", "api-key"])?;
+    assert_eq!(run.stdout.trim(), "s3cret-value");
+
+    let run = sandbox.config(&["get", "demo", "api-key", "--stored"])?;
+    assert_eq!(run.stdout.trim(), "s3cret-value");
+
+    Ok(())
+}
+
+#[test]
+fn rekey_encrypts_plaintext_secrets_and_keeps_them_readable() -> Result {
+    let sandbox = Sandbox::new()?;
+
+    // as stored before secrets were encrypted
+    std::fs::create_dir_all(sandbox.value_file("api-key").parent().unwrap())?;
+    std::fs::write(sandbox.value_file("api-key"), "s3cret-value")?;
+
+    let run = sandbox.config(&["get", "demo", "api-key"])?;
+    assert_eq!(run.stdout.trim(), "s3cret-value");
+
+    let key_file = sandbox.root().join("team.key");
+    let run = sandbox.config(&["rekey", "--key-file", key_file.to_str().unwrap()])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(key_file.exists());
+
+    let stored = std::fs::read_to_string(sandbox.value_file("api-key"))?;
+    assert!(!stored.contains("s3cret-value"));
+
+    let run = sandbox.config(&["get", "demo", "api-key"])?;
+    assert_eq!(run.stdout.trim(), "s3cret-value");
+
+    // rotating the key again still leaves the secret readable
+    let run = sandbox.config(&["rekey"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert_ne!(
+        std::fs::read_to_string(sandbox.value_file("api-key"))?,
+        stored
+    );
+    let run = sandbox.config(&["get", "demo", "api-key"])?;
+    assert_eq!(run.stdout.trim(), "s3cret-value");
+
+    Ok(())
+}
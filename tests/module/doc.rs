@@ -1,29 +1,13 @@
 // This is free and unencumbered software released into the public domain.
 
+use crate::{module, shared::Run};
 use clientele::SysexitsError::*;
-use std::process::{Command, Stdio};
 use temp_dir::TempDir;
 
 type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
 
-struct Run {
-    code: i32,
-    stdout: String,
-    stderr: String,
-}
-
 fn doc(root: &TempDir) -> Result<Run> {
-    let output = Command::new(env!("CARGO_BIN_EXE_asimov"))
-        .args(["module", "doc", "demo"])
-        .env("ASIMOV_ROOT", root.path())
-        .stdin(Stdio::null())
-        .output()?;
-
-    Ok(Run {
-        code: output.status.code().expect("should exit normally"),
-        stdout: String::from_utf8(output.stdout)?,
-        stderr: String::from_utf8(output.stderr)?,
-    })
+    module(root.path(), &["doc", "demo"])
 }
 
 #[test]
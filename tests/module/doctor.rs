@@ -0,0 +1,79 @@
+// This is free and unencumbered software released into the public domain.
+
+#![cfg(unix)]
+
+use crate::{module, shared};
+use clientele::SysexitsError::*;
+use std::os::unix::fs::PermissionsExt;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn install(root: &TempDir, name: &str, manifest: &str) -> Result {
+    shared::install_module(root.path(), name, manifest)?;
+
+    let bin_dir = root.child(format!("modules/installed/{name}/bin"));
+    std::fs::create_dir_all(&bin_dir)?;
+    let program = bin_dir.join(format!("asimov-{name}-fetcher"));
+    std::fs::write(&program, "#!/bin/sh\nexit 0\n")?;
+    std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755))?;
+
+    shared::enable_module(root.path(), name)
+}
+
+fn doctor(root: &TempDir) -> Result<(i32, serde_json::Value)> {
+    let run = module(root.path(), &["doctor", "-o", "json"])?;
+    Ok((run.code, serde_json::from_str(&run.stdout)?))
+}
+
+#[test]
+fn doctor_reports_healthy_modules_and_overlaps() -> Result {
+    let root = TempDir::new()?;
+    for name in ["web", "github"] {
+        let prefix = if name == "web" {
+            "https://"
+        } else {
+            "https://github.com/"
+        };
+        install(
+            &root,
+            name,
+            &format!(
+                r#"{{
+                  "name": "{name}",
+                  "provides": {{ "programs": ["asimov-{name}-fetcher"] }},
+                  "handles": {{ "url_prefixes": ["{prefix}"] }}
+                }}"#
+            ),
+        )?;
+    }
+
+    let (code, report) = doctor(&root)?;
+    assert_eq!(code, EX_OK as i32);
+    assert_eq!(report["summary"]["errors"], 0);
+    assert_eq!(report["modules"][0]["programs"][0]["help"], "ok");
+    assert_eq!(report["overlaps"][0]["handle"], "https://github.com/");
+
+    Ok(())
+}
+
+#[test]
+fn doctor_flags_an_enabled_module_missing_configuration() -> Result {
+    let root = TempDir::new()?;
+    install(
+        &root,
+        "demo",
+        r#"{
+          "name": "demo",
+          "provides": { "programs": ["asimov-demo-fetcher", "asimov-demo-configurator"] },
+          "config": { "variables": [{ "name": "api_key" }] }
+        }"#,
+    )?;
+
+    let (code, report) = doctor(&root)?;
+    assert_eq!(code, EX_CONFIG as i32);
+    assert_eq!(report["summary"]["errors"], 2);
+    assert_eq!(report["modules"][0]["programs"][1]["present"], false);
+
+    Ok(())
+}
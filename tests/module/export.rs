@@ -0,0 +1,98 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{
+    module,
+    shared::{self, command, run},
+};
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn sandbox() -> Result<TempDir> {
+    let root = TempDir::new()?;
+    shared::install_module(
+        root.path(),
+        "demo",
+        r#"{
+          "version": "1.2.3",
+          "name": "demo",
+          "config": {
+            "variables": [
+              { "name": "api-key", "secret": true },
+              { "name": "host", "environment": "DEMO_HOST" }
+            ]
+          }
+        }"#,
+    )?;
+    Ok(root)
+}
+
+#[test]
+fn export_leaves_out_secret_values() -> Result {
+    let root = sandbox()?;
+    let conf_dir = root.child("configs/default/demo");
+    std::fs::create_dir_all(&conf_dir)?;
+    std::fs::write(conf_dir.join("host"), "example.org")?;
+    std::fs::write(conf_dir.join("api-key"), "hunter2")?;
+
+    let run = module(root.path(), &["export", "-o", "json"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(!run.stdout.contains("hunter2"));
+
+    let environment: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    let demo = &environment["modules"][0];
+    assert_eq!(demo["name"], "demo");
+    assert_eq!(demo["version"], "1.2.3");
+    assert_eq!(demo["enabled"], false);
+    assert_eq!(demo["config"]["host"], "example.org");
+    assert_eq!(demo["secrets"][0], "api-key");
+
+    Ok(())
+}
+
+#[test]
+fn export_includes_stored_values_that_the_environment_overrides() -> Result {
+    let root = sandbox()?;
+    let conf_dir = root.child("configs/default/demo");
+    std::fs::create_dir_all(&conf_dir)?;
+    std::fs::write(conf_dir.join("host"), "example.org")?;
+
+    let run = run(command(root.path(), &["module", "export", "-o", "json"])
+        .env("DEMO_HOST", "override.example.org"))?;
+    assert_eq!(run.code, EX_OK as i32);
+
+    let environment: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    assert_eq!(environment["modules"][0]["config"]["host"], "example.org");
+
+    Ok(())
+}
+
+#[test]
+fn import_applies_an_environment_idempotently() -> Result {
+    let root = sandbox()?;
+    let conf_dir = root.child("configs/default/demo");
+    std::fs::create_dir_all(&conf_dir)?;
+    std::fs::write(conf_dir.join("api-key"), "hunter2")?;
+
+    let path = root.child("environment.yaml");
+    std::fs::write(
+        &path,
+        "version: 1\nmodules:\n- name: demo\n  version: 1.2.3\n  enabled: true\n  config:\n    host: example.org\n",
+    )?;
+
+    for _ in 0..2 {
+        let run = module(root.path(), &["import", path.to_str().unwrap()])?;
+        assert_eq!(run.code, EX_OK as i32);
+        assert_eq!(
+            std::fs::read_to_string(root.child("configs/default/demo/host"))?,
+            "example.org"
+        );
+        assert!(root.child("modules/enabled/demo").try_exists()?);
+    }
+
+    let run = module(root.path(), &["export"])?;
+    assert!(run.stdout.contains("host: example.org"));
+
+    Ok(())
+}
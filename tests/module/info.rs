@@ -0,0 +1,42 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{module, shared::Run};
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+const INDEX: &str = r#"{"name":"serpapi","summary":"Search with SerpApi.","provides":{"programs":["asimov-serpapi-fetcher"]},"handles":{"url_prefixes":["https://google.com/search"]},"config":{"variables":[{"name":"api-key","secret":true},{"name":"region","default":"us"}]}}
+"#;
+
+fn module_info(root: &TempDir, name: &str) -> Result<Run> {
+    module(root.path(), &["info", "--offline", "-o", "json", name])
+}
+
+#[test]
+fn shows_an_available_module_without_installing_it() -> Result {
+    let root = TempDir::new()?;
+    let cache_dir = root.child("cache/modules");
+    std::fs::create_dir_all(&cache_dir)?;
+    std::fs::write(cache_dir.join("index.jsonl"), INDEX)?;
+
+    let run = module_info(&root, "serpapi")?;
+    assert_eq!(run.code, EX_OK as i32);
+
+    let info: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    assert_eq!(info["manifest"]["name"], "serpapi");
+    assert_eq!(info["installed"], serde_json::Value::Null);
+    assert_eq!(
+        info["manifest"]["provides"]["programs"][0],
+        "asimov-serpapi-fetcher"
+    );
+    let config = info["config"].as_array().unwrap();
+    assert_eq!(config[0]["name"], "api-key");
+    assert_eq!(config[0]["required"], true);
+    assert_eq!(config[1]["required"], false);
+
+    let run = module_info(&root, "nonesuch")?;
+    assert_eq!(run.code, EX_UNAVAILABLE as i32);
+
+    Ok(())
+}
@@ -1,8 +1,8 @@
 // This is free and unencumbered software released into the public domain.
 
+use crate::{module, shared};
 use clientele::SysexitsError::*;
 use indoc::indoc;
-use std::process::{Command, Stdio};
 use temp_dir::TempDir;
 
 type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
@@ -10,10 +10,9 @@ type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
 #[test]
 fn install_enables_a_module_with_an_unset_optional_variable() -> Result {
     let root = TempDir::new()?;
-    let module_dir = root.child("modules/installed/demo");
-    std::fs::create_dir_all(&module_dir)?;
-    std::fs::write(
-        module_dir.join("manifest.json"),
+    shared::install_module(
+        root.path(),
+        "demo",
         indoc! {r#"
             {
               "name": "demo",
@@ -24,13 +23,9 @@ fn install_enables_a_module_with_an_unset_optional_variable() -> Result {
         "#},
     )?;
 
-    let output = Command::new(env!("CARGO_BIN_EXE_asimov"))
-        .args(["module", "install", "demo"])
-        .env("ASIMOV_ROOT", root.path())
-        .stdin(Stdio::null())
-        .output()?;
+    let run = module(root.path(), &["install", "demo"])?;
 
-    assert_eq!(output.status.code(), Some(EX_OK as i32));
+    assert_eq!(run.code, EX_OK as i32);
     assert!(root.child("modules/enabled/demo").try_exists()?);
 
     Ok(())
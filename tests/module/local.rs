@@ -0,0 +1,189 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::shared::{command, run};
+use clientele::SysexitsError::*;
+use indoc::indoc;
+use std::process::Command;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn module_source(dir: &std::path::Path) -> Result {
+    std::fs::create_dir_all(dir.join(".asimov"))?;
+    std::fs::write(
+        dir.join(".asimov/module.yaml"),
+        indoc! {r#"
+            name: demo
+            provides:
+              programs:
+                - asimov-demo-fetcher
+            config:
+              variables:
+                - name: port
+                  default_value: "8080"
+                  type: integer
+                  min: 1
+        "#},
+    )?;
+    std::fs::write(dir.join("asimov-demo-fetcher"), "#!/bin/sh\n")?;
+    std::fs::write(dir.join("README.md"), "# Demo\n")?;
+    Ok(())
+}
+
+fn install_from(root: &TempDir, args: &[&str]) -> Result<i32> {
+    Ok(run(command(root.path(), &["module", "install", "--from"]).args(args))?.code)
+}
+
+#[test]
+fn install_from_a_module_directory() -> Result {
+    let root = TempDir::new()?;
+    let source = TempDir::new()?;
+    module_source(source.path())?;
+
+    let code = install_from(&root, &[source.path().to_str().unwrap(), "demo@1.0.0"])?;
+    assert_eq!(code, EX_OK as i32);
+
+    let manifest = std::fs::read(root.child("modules/installed/demo/manifest.json"))?;
+    let manifest: serde_json::Value = serde_json::from_slice(&manifest)?;
+    assert_eq!(manifest["name"], "demo");
+    assert_eq!(manifest["version"], "1.0.0");
+    let constraints = std::fs::read(root.child("modules/installed/demo/constraints.json"))?;
+    let constraints: serde_json::Value = serde_json::from_slice(&constraints)?;
+    assert_eq!(
+        constraints,
+        serde_json::json!({ "port": { "type": "integer", "min": 1 } })
+    );
+    assert!(root.child("modules/installed/demo/doc/README.md").is_file());
+    assert!(root.child("libexec/asimov-demo-fetcher").try_exists()?);
+    assert!(root.child("modules/enabled/demo").try_exists()?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn install_from_an_archive() -> Result {
+    let root = TempDir::new()?;
+    let source = TempDir::new()?;
+    module_source(&source.child("demo"))?;
+
+    let archive = source.child("demo.tar.gz");
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(source.child("demo"))
+        .arg(".")
+        .status()?;
+    assert!(status.success());
+
+    let code = install_from(&root, &[archive.to_str().unwrap()])?;
+    assert_eq!(code, EX_OK as i32);
+    assert!(
+        root.child("modules/installed/demo/bin/asimov-demo-fetcher")
+            .is_file()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn install_from_rejects_a_missing_program() -> Result {
+    let root = TempDir::new()?;
+    let source = TempDir::new()?;
+    module_source(source.path())?;
+    std::fs::remove_file(source.child("asimov-demo-fetcher"))?;
+
+    let code = install_from(&root, &[source.path().to_str().unwrap()])?;
+    assert_eq!(code, EX_DATAERR as i32);
+    assert!(!root.child("modules/installed/demo").try_exists()?);
+
+    Ok(())
+}
+
+/// Publishes a release of a module to a mirror, laid out like GitHub
+/// releases, with an archive for this platform and its checksum.
+#[cfg(unix)]
+fn mirror_release(mirror: &std::path::Path, name: &str, requires: &[&str]) -> Result {
+    let release_dir = mirror.join(format!("asimov-{name}-module/1.0.0"));
+    let contents = release_dir.join("contents");
+    std::fs::create_dir_all(&contents)?;
+
+    let requires: String = requires.iter().map(|r| format!("\n    - {r}")).collect();
+    std::fs::write(
+        release_dir.join("module.yaml"),
+        format!(
+            "name: {name}\nprovides:\n  programs:\n    - asimov-{name}-fetcher\nrequires:\n  modules:{requires}\n"
+        ),
+    )?;
+    std::fs::write(
+        contents.join(format!("asimov-{name}-fetcher")),
+        "#!/bin/sh\n",
+    )?;
+
+    let os = if cfg!(target_os = "macos") {
+        "macos"
+    } else {
+        "linux"
+    };
+    let arch = if cfg!(any(target_arch = "aarch64", target_arch = "arm")) {
+        "arm"
+    } else {
+        "x86"
+    };
+    let archive = format!("asimov-{name}-module-{os}-{arch}.tar.gz");
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(release_dir.join(&archive))
+        .arg("-C")
+        .arg(&contents)
+        .arg(".")
+        .status()?;
+    assert!(status.success());
+    std::fs::remove_dir_all(contents)?;
+
+    let hash = <sha2::Sha256 as sha2::Digest>::digest(std::fs::read(release_dir.join(&archive))?);
+    std::fs::write(
+        release_dir.join("SHA256SUMS"),
+        format!("{}  {archive}\n", hex::encode(hash)),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn install_from_a_mirror_without_dependencies() -> Result {
+    let root = TempDir::new()?;
+    let mirror = TempDir::new()?;
+    mirror_release(mirror.path(), "base", &[])?;
+    mirror_release(mirror.path(), "app", &["base"])?;
+
+    let code = install_from(
+        &root,
+        &[mirror.path().to_str().unwrap(), "--no-deps", "app"],
+    )?;
+    assert_eq!(code, EX_OK as i32);
+    assert!(root.child("modules/installed/app").is_dir());
+    assert!(!root.child("modules/installed/base").try_exists()?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn install_from_a_mirror_rejects_a_mismatched_archive() -> Result {
+    let root = TempDir::new()?;
+    let mirror = TempDir::new()?;
+    mirror_release(mirror.path(), "demo", &[])?;
+    let checksums = mirror.child("asimov-demo-module/1.0.0/SHA256SUMS");
+    let listing = std::fs::read_to_string(&checksums)?;
+    let (_, archive) = listing.split_once("  ").unwrap();
+    std::fs::write(&checksums, format!("{}  {archive}", "0".repeat(64)))?;
+
+    let code = install_from(&root, &[mirror.path().to_str().unwrap(), "demo"])?;
+    assert_eq!(code, EX_DATAERR as i32);
+    assert!(!root.child("modules/installed/demo").try_exists()?);
+
+    Ok(())
+}
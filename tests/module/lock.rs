@@ -0,0 +1,97 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{module, shared};
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn sandbox() -> Result<TempDir> {
+    let root = TempDir::new()?;
+    shared::install_module(
+        root.path(),
+        "demo",
+        r#"{
+          "version": "1.2.3",
+          "name": "demo",
+          "links": ["https://crates.io/crates/asimov-demo-module"],
+          "provides": { "programs": ["asimov-demo-fetcher"] }
+        }"#,
+    )?;
+    let bin_dir = root.child("modules/installed/demo/bin");
+    std::fs::create_dir_all(&bin_dir)?;
+    std::fs::write(bin_dir.join("asimov-demo-fetcher"), "#!/bin/sh\n")?;
+    Ok(root)
+}
+
+#[test]
+fn lock_records_the_installed_modules() -> Result {
+    let root = sandbox()?;
+    let lockfile = root.child("asimov.lock");
+
+    let run = module(root.path(), &["lock", lockfile.to_str().unwrap()])?;
+    assert_eq!(run.code, EX_OK as i32);
+
+    let lock: serde_json::Value = serde_json::from_slice(&std::fs::read(&lockfile)?)?;
+    let demo = &lock["modules"][0];
+    assert_eq!(demo["name"], "demo");
+    assert_eq!(demo["version"], "1.2.3");
+    assert_eq!(demo["ecosystem"], "rust");
+    assert!(demo["checksum"].as_str().unwrap().starts_with("sha256:"));
+
+    Ok(())
+}
+
+#[test]
+fn install_locked_accepts_a_matching_installation() -> Result {
+    let root = sandbox()?;
+    let lockfile = root.child("asimov.lock");
+    module(root.path(), &["lock", lockfile.to_str().unwrap()])?;
+
+    let run = module(
+        root.path(),
+        &["install", "--locked", lockfile.to_str().unwrap()],
+    )?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert!(root.child("modules/enabled/demo").try_exists()?);
+
+    Ok(())
+}
+
+#[test]
+fn install_locked_rejects_a_modified_program() -> Result {
+    let root = sandbox()?;
+    let lockfile = root.child("asimov.lock");
+    module(root.path(), &["lock", lockfile.to_str().unwrap()])?;
+
+    std::fs::write(
+        root.child("modules/installed/demo/bin/asimov-demo-fetcher"),
+        "#!/bin/sh\necho tampered\n",
+    )?;
+
+    let run = module(
+        root.path(),
+        &["install", "--locked", lockfile.to_str().unwrap()],
+    )?;
+    assert_eq!(run.code, EX_DATAERR as i32);
+
+    Ok(())
+}
+
+#[test]
+fn install_locked_rejects_a_different_version() -> Result {
+    let root = sandbox()?;
+    let lockfile = root.child("asimov.lock");
+    module(root.path(), &["lock", lockfile.to_str().unwrap()])?;
+
+    let lock = std::fs::read_to_string(&lockfile)?.replace("1.2.3", "1.2.4");
+    std::fs::write(&lockfile, lock)?;
+
+    let run = module(
+        root.path(),
+        &["install", "--locked", lockfile.to_str().unwrap()],
+    )?;
+    assert_eq!(run.code, EX_DATAERR as i32);
+
+    Ok(())
+}
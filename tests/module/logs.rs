@@ -0,0 +1,72 @@
+// This is free and unencumbered software released into the public domain.
+
+#![cfg(unix)]
+
+use crate::{module, shared};
+use clientele::SysexitsError::*;
+use std::os::unix::fs::PermissionsExt;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+#[test]
+fn logs_show_how_a_fetcher_failed() -> Result {
+    let root = TempDir::new()?;
+    shared::install_module(
+        root.path(),
+        "demo",
+        r#"{
+          "name": "demo",
+          "provides": { "programs": ["asimov-demo-fetcher"] },
+          "handles": { "url_prefixes": ["https://example.org/"] }
+        }"#,
+    )?;
+    shared::enable_module(root.path(), "demo")?;
+
+    let fetcher = root.child("libexec/asimov-demo-fetcher");
+    std::fs::create_dir_all(root.child("libexec"))?;
+    std::fs::write(
+        &fetcher,
+        "#!/bin/sh\necho \"no access to $1\" >&2\nexit 77\n",
+    )?;
+    std::fs::set_permissions(&fetcher, std::fs::Permissions::from_mode(0o755))?;
+
+    let run = shared::asimov(
+        root.path(),
+        &["source", "fetch", "https://example.org/page"],
+    )?;
+    assert_ne!(run.code, EX_OK as i32);
+
+    let run = module(root.path(), &["logs", "demo", "-o", "jsonl"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    let entries: Vec<serde_json::Value> = run
+        .stdout
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["program"], "asimov-demo-fetcher");
+    assert_eq!(
+        entries[0]["args"],
+        serde_json::json!(["https://example.org/page"])
+    );
+    assert_eq!(entries[0]["status"], 77);
+    assert_eq!(
+        entries[0]["stderr"],
+        "no access to https://example.org/page\n"
+    );
+
+    let run = module(
+        root.path(),
+        &["logs", "demo", "--since", "1h", "-o", "jsonl"],
+    )?;
+    assert_eq!(run.stdout.lines().count(), 1);
+
+    let run = module(
+        root.path(),
+        &["logs", "demo", "--since", "2999-01-01", "-o", "jsonl"],
+    )?;
+    assert!(run.stdout.is_empty());
+
+    Ok(())
+}
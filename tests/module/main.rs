@@ -1,3 +1,25 @@
+#[path = "../shared.rs"]
+mod shared;
+
+use shared::Run;
+use std::path::Path;
+
 mod config;
 mod doc;
+mod doctor;
+mod export;
+mod info;
 mod install;
+mod local;
+mod lock;
+mod logs;
+mod outdated;
+mod resolve;
+mod rollback;
+mod search;
+mod verify;
+
+/// Runs `asimov module <args>` against the `$ASIMOV_ROOT` at `root`.
+fn module(root: &Path, args: &[&str]) -> shared::Result<Run> {
+    shared::asimov(root, &[&["module"], args].concat())
+}
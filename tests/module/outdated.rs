@@ -0,0 +1,20 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::module;
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+#[test]
+fn outdated_succeeds_with_no_modules_installed() -> Result {
+    let root = TempDir::new()?;
+
+    let run = module(root.path(), &["outdated", "-o", "json"])?;
+
+    assert_eq!(run.code, EX_OK as i32);
+    let report: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    assert_eq!(report, serde_json::json!([]));
+
+    Ok(())
+}
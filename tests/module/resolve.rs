@@ -0,0 +1,165 @@
+// This is free and unencumbered software released into the public domain.
+
+#![cfg(unix)]
+
+use crate::{module, shared};
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn install(root: &TempDir, name: &str, handles: &str, enabled: bool) -> Result {
+    shared::install_module(
+        root.path(),
+        name,
+        &format!(
+            r#"{{
+              "name": "{name}",
+              "provides": {{ "programs": ["asimov-{name}-fetcher"] }},
+              "handles": {handles}
+            }}"#
+        ),
+    )?;
+
+    if enabled {
+        shared::enable_module(root.path(), name)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn explain_shows_the_matching_rules_and_the_chosen_module() -> Result {
+    let root = TempDir::new()?;
+    install(
+        &root,
+        "web",
+        r#"{ "url_protocols": ["https"], "url_prefixes": ["https://example.org/"] }"#,
+        true,
+    )?;
+    install(
+        &root,
+        "example",
+        r#"{ "url_patterns": ["https://example.org/:page"] }"#,
+        false,
+    )?;
+
+    let run = module(
+        root.path(),
+        &[
+            "resolve",
+            "--explain",
+            "-o",
+            "json",
+            "https://example.org/about",
+        ],
+    )?;
+    assert_eq!(run.code, EX_OK as i32);
+
+    let explanation: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    let candidates = explanation["candidates"].as_array().unwrap();
+    assert_eq!(candidates.len(), 2);
+
+    assert_eq!(candidates[0]["name"], "web");
+    assert_eq!(candidates[0]["priority"], 1);
+    assert_eq!(candidates[0]["enabled"], true);
+    assert_eq!(candidates[0]["rules"].as_array().unwrap().len(), 2);
+
+    assert_eq!(candidates[1]["name"], "example");
+    assert_eq!(candidates[1]["priority"], 2);
+    assert_eq!(candidates[1]["enabled"], false);
+    assert_eq!(candidates[1]["rules"][0]["kind"], "url_patterns");
+
+    assert_eq!(explanation["chosen"]["fetch"], "web");
+    assert!(explanation["chosen"]["read"].is_null());
+
+    Ok(())
+}
+
+#[test]
+fn a_preferred_module_is_chosen_ahead_of_the_resolver_order() -> Result {
+    let root = TempDir::new()?;
+    install(&root, "web", r#"{ "url_protocols": ["https"] }"#, true)?;
+    install(
+        &root,
+        "linkedin",
+        r#"{ "url_prefixes": ["https://linkedin.com/"] }"#,
+        true,
+    )?;
+    install(
+        &root,
+        "brightdata",
+        r#"{ "url_protocols": ["https"] }"#,
+        true,
+    )?;
+
+    let url = "https://www.linkedin.com/in/someone";
+
+    let stdout = module(root.path(), &["resolve", url])?.stdout;
+    assert_eq!(stdout.lines().next(), Some("linkedin"));
+
+    let code = module(root.path(), &["prefer", "linkedin.com", "brightdata"])?.code;
+    assert_eq!(code, EX_OK as i32);
+
+    let stdout = module(root.path(), &["resolve", url])?.stdout;
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["brightdata", "linkedin", "web"]
+    );
+    let stdout = module(root.path(), &["resolve", "https://example.org/"])?.stdout;
+    assert_eq!(stdout.lines().next(), Some("web"), "the preference leaked");
+
+    let stdout = module(root.path(), &["resolve", "-o", "json", url])?.stdout;
+    let explanation: serde_json::Value = serde_json::from_str(&stdout)?;
+    assert_eq!(explanation["chosen"]["fetch"], "brightdata");
+    assert_eq!(
+        explanation["candidates"][0]["preferred_for"],
+        "linkedin.com"
+    );
+
+    let code = module(root.path(), &["unprefer", "linkedin.com"])?.code;
+    assert_eq!(code, EX_OK as i32);
+    let stdout = module(root.path(), &["resolve", url])?.stdout;
+    assert_eq!(stdout.lines().next(), Some("linkedin"));
+
+    Ok(())
+}
+
+#[test]
+fn explain_puts_readers_of_a_files_content_type_first_as_read_does() -> Result {
+    let root = TempDir::new()?;
+    for (name, handles) in [
+        ("files", r#"{ "url_protocols": ["file"] }"#),
+        ("image", r#"{ "content_types": ["image/png"] }"#),
+    ] {
+        shared::install_module(
+            root.path(),
+            name,
+            &format!(
+                r#"{{
+                  "name": "{name}",
+                  "provides": {{ "programs": ["asimov-{name}-reader"] }},
+                  "handles": {handles}
+                }}"#
+            ),
+        )?;
+        shared::enable_module(root.path(), name)?;
+    }
+    let file = root.child("picture.png");
+    std::fs::write(&file, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")?;
+
+    let run = module(
+        root.path(),
+        &["resolve", "--explain", "-o", "json", file.to_str().unwrap()],
+    )?;
+    assert_eq!(run.code, EX_OK as i32);
+
+    let explanation: serde_json::Value = serde_json::from_str(&run.stdout)?;
+    assert_eq!(explanation["chosen"]["read"], "image");
+    let candidates = explanation["candidates"].as_array().unwrap();
+    assert_eq!(candidates[0]["name"], "image");
+    assert_eq!(candidates[0]["rules"][0]["kind"], "content_types");
+    assert_eq!(candidates[0]["rules"][0]["rule"], "image/png");
+    assert_eq!(candidates[1]["name"], "files");
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::module;
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn write_module(dir: &std::path::Path, version: &str) -> Result {
+    std::fs::create_dir_all(dir.join("bin"))?;
+    std::fs::write(
+        dir.join("manifest.json"),
+        format!(
+            r#"{{
+              "version": "{version}",
+              "name": "demo",
+              "provides": {{ "programs": ["asimov-demo-fetcher"] }}
+            }}"#
+        ),
+    )?;
+    std::fs::write(
+        dir.join("bin/asimov-demo-fetcher"),
+        format!("#!/bin/sh\necho {version}\n"),
+    )?;
+    Ok(())
+}
+
+fn installed_version(root: &TempDir) -> Result<serde_json::Value> {
+    let manifest = std::fs::read(root.child("modules/installed/demo/manifest.json"))?;
+    let manifest: serde_json::Value = serde_json::from_slice(&manifest)?;
+    Ok(manifest["version"].clone())
+}
+
+#[test]
+fn rollback_restores_the_previous_version() -> Result {
+    let root = TempDir::new()?;
+    write_module(&root.child("modules/installed/demo"), "1.1.0")?;
+    write_module(&root.child("modules/history/demo/1.0.0"), "1.0.0")?;
+
+    assert_eq!(
+        module(root.path(), &["rollback", "demo"])?.code,
+        EX_OK as i32
+    );
+    assert_eq!(installed_version(&root)?, "1.0.0");
+    assert!(root.child("libexec/asimov-demo-fetcher").try_exists()?);
+    assert!(root.child("modules/history/demo/1.1.0").try_exists()?);
+    assert!(!root.child("modules/history/demo/1.0.0").try_exists()?);
+
+    // rolling back again returns to the version rolled back from
+    assert_eq!(
+        module(root.path(), &["rollback", "demo"])?.code,
+        EX_OK as i32
+    );
+    assert_eq!(installed_version(&root)?, "1.1.0");
+
+    Ok(())
+}
+
+#[test]
+fn rollback_fails_without_a_previous_version() -> Result {
+    let root = TempDir::new()?;
+    write_module(&root.child("modules/installed/demo"), "1.1.0")?;
+
+    assert_eq!(
+        module(root.path(), &["rollback", "demo"])?.code,
+        EX_UNAVAILABLE as i32
+    );
+    assert_eq!(installed_version(&root)?, "1.1.0");
+
+    Ok(())
+}
+
+#[test]
+fn rollback_restores_the_most_recently_kept_version_by_its_recorded_order() -> Result {
+    let root = TempDir::new()?;
+    write_module(&root.child("modules/installed/demo"), "1.2.0")?;
+    // kept later, but created first, as a copied `$ASIMOV_ROOT` may be
+    write_module(&root.child("modules/history/demo/1.1.0"), "1.1.0")?;
+    std::fs::write(root.child("modules/history/demo/1.1.0/.order"), "2")?;
+    write_module(&root.child("modules/history/demo/1.0.0"), "1.0.0")?;
+    std::fs::write(root.child("modules/history/demo/1.0.0/.order"), "1")?;
+
+    assert_eq!(
+        module(root.path(), &["rollback", "demo"])?.code,
+        EX_OK as i32
+    );
+    assert_eq!(installed_version(&root)?, "1.1.0");
+    assert!(!root.child("modules/installed/demo/.order").try_exists()?);
+    assert_eq!(
+        std::fs::read_to_string(root.child("modules/history/demo/1.2.0/.order"))?,
+        "3"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::shared::{Run, command, run};
+use clientele::SysexitsError::*;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+const INDEX: &str = r#"{"name":"youtube","summary":"Fetch videos from YouTube.","links":["https://pypi.org/project/asimov-youtube-module"],"provides":{"programs":["asimov-youtube-fetcher"]},"handles":{"url_prefixes":["https://youtube.com/"]}}
+{"name":"web","summary":"Fetch web pages, including YouTube pages.","links":["https://crates.io/crates/asimov-web-module"],"provides":{"programs":["asimov-web-fetcher"]}}
+{"name":"yaml","summary":"Read YAML files.","provides":{"programs":["asimov-yaml-reader"]}}
+"#;
+
+fn search(root: &TempDir, args: &[&str]) -> Result<Run> {
+    run(command(
+        root.path(),
+        &["module", "search", "--offline", "-o", "jsonl"],
+    )
+    .args(args))
+}
+
+fn names(run: &Run) -> Vec<String> {
+    run.stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|module| module["name"].as_str().map(String::from))
+        .collect()
+}
+
+#[test]
+fn searches_the_cached_index_offline() -> Result {
+    let root = TempDir::new()?;
+    let cache_dir = root.child("cache/modules");
+    std::fs::create_dir_all(&cache_dir)?;
+    std::fs::write(cache_dir.join("index.jsonl"), INDEX)?;
+    std::fs::write(cache_dir.join("index.json"), r#"{"checked_at":0}"#)?;
+
+    let run = search(&root, &["youtube"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert_eq!(names(&run), ["youtube", "web"]);
+
+    let run = search(&root, &["provides:fetcher", "type:rust"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert_eq!(names(&run), ["web"]);
+
+    let run = search(&root, &["handles:youtube.com"])?;
+    assert_eq!(names(&run), ["youtube"]);
+
+    Ok(())
+}
+
+#[test]
+fn offline_search_needs_a_cached_index() -> Result {
+    let root = TempDir::new()?;
+
+    let run = search(&root, &["youtube"])?;
+    assert_eq!(run.code, EX_UNAVAILABLE as i32);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::module;
+use clientele::SysexitsError::*;
+use indoc::indoc;
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+/// Installs a demo module from a local directory, which records its checksums.
+fn sandbox() -> Result<(TempDir, TempDir)> {
+    let root = TempDir::new()?;
+    let source = TempDir::new()?;
+    std::fs::create_dir_all(source.child(".asimov"))?;
+    std::fs::write(
+        source.child(".asimov/module.yaml"),
+        indoc! {r#"
+            name: demo
+            provides:
+              programs:
+                - asimov-demo-fetcher
+                - asimov-demo-configurator
+        "#},
+    )?;
+    std::fs::write(source.child("asimov-demo-fetcher"), "#!/bin/sh\n")?;
+    std::fs::write(source.child("asimov-demo-configurator"), "#!/bin/sh\n")?;
+
+    let run = module(
+        root.path(),
+        &["install", "--from", source.path().to_str().unwrap()],
+    )?;
+    assert_eq!(run.code, EX_OK as i32);
+    Ok((root, source))
+}
+
+#[test]
+fn verify_accepts_an_intact_module() -> Result {
+    let (root, _source) = sandbox()?;
+    assert!(root.child("modules/installed/demo/SHA256SUMS").is_file());
+
+    assert_eq!(module(root.path(), &["verify"])?.code, EX_OK as i32);
+
+    Ok(())
+}
+
+#[test]
+fn verify_detects_a_modified_program() -> Result {
+    let (root, _source) = sandbox()?;
+    std::fs::write(
+        root.child("modules/installed/demo/bin/asimov-demo-fetcher"),
+        "#!/bin/sh\necho tampered\n",
+    )?;
+
+    assert_eq!(
+        module(root.path(), &["verify", "demo"])?.code,
+        EX_DATAERR as i32
+    );
+
+    Ok(())
+}
+
+#[test]
+fn verify_detects_a_missing_configurator() -> Result {
+    let (root, _source) = sandbox()?;
+    std::fs::remove_file(root.child("libexec/asimov-demo-configurator"))?;
+
+    assert_eq!(
+        module(root.path(), &["verify", "demo"])?.code,
+        EX_DATAERR as i32
+    );
+
+    Ok(())
+}
@@ -3,6 +3,10 @@
 #![allow(unused)]
 
 use indoc::formatdoc;
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
 use temp_dir::TempDir;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -112,3 +116,56 @@ pub fn init() -> Result<TempDir> {
 
     Ok(dir)
 }
+
+/// What a run of `asimov` exited with and wrote.
+pub struct Run {
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Prepares `asimov <args>` to run against the `$ASIMOV_ROOT` at `root`.
+pub fn command(root: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_asimov"));
+    command
+        .args(args)
+        .env("ASIMOV_ROOT", root)
+        // start from the default profile, whatever the developer's shell has
+        .env_remove("ASIMOV_PROFILE")
+        // never inherit a terminal: a test must fail rather than block
+        .stdin(Stdio::null());
+    command
+}
+
+pub fn run(command: &mut Command) -> Result<Run> {
+    let output = command.output()?;
+    Ok(Run {
+        code: output.status.code().expect("should exit normally"),
+        stdout: String::from_utf8(output.stdout)?,
+        stderr: String::from_utf8(output.stderr)?,
+    })
+}
+
+/// Runs `asimov <args>` against the `$ASIMOV_ROOT` at `root`.
+pub fn asimov(root: &Path, args: &[&str]) -> Result<Run> {
+    run(&mut command(root, args))
+}
+
+/// Installs a module with the given manifest under the `$ASIMOV_ROOT` at
+/// `root`, without any of its programs.
+pub fn install_module(root: &Path, name: &str, manifest: &str) -> Result<()> {
+    let module_dir = root.join("modules/installed").join(name);
+    std::fs::create_dir_all(&module_dir)?;
+    std::fs::write(module_dir.join("manifest.json"), manifest)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn enable_module(root: &Path, name: &str) -> Result<()> {
+    std::fs::create_dir_all(root.join("modules/enabled"))?;
+    std::os::unix::fs::symlink(
+        format!("../installed/{name}"),
+        root.join("modules/enabled").join(name),
+    )?;
+    Ok(())
+}
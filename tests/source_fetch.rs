@@ -0,0 +1,294 @@
+// This is free and unencumbered software released into the public domain.
+
+#![cfg(unix)]
+
+mod shared;
+
+use clientele::SysexitsError::*;
+use shared::{Run, asimov, command};
+use std::{io::Write, os::unix::fs::PermissionsExt, process::Stdio};
+use temp_dir::TempDir;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+/// Installs a module whose fetcher writes two lines for a URL, its last
+/// argument, slowly, and fails for URLs containing `bad`.
+fn sandbox() -> Result<TempDir> {
+    let root = TempDir::new()?;
+    install(
+        &root,
+        "demo",
+        "#!/bin/sh\nfor url; do :; done\ncase \"$url\" in *bad*) exit 1;; esac\necho \"begin $url\"\nsleep 0.2\necho \"end $url\"\n",
+    )?;
+    Ok(root)
+}
+
+/// Installs and enables a module with the given fetcher script.
+fn install(root: &TempDir, name: &str, fetcher: &str) -> Result {
+    shared::install_module(
+        root.path(),
+        name,
+        &format!(
+            r#"{{
+              "name": "{name}",
+              "provides": {{ "programs": ["asimov-{name}-fetcher"] }},
+              "handles": {{ "url_protocols": ["https"] }}
+            }}"#
+        ),
+    )?;
+    shared::enable_module(root.path(), name)?;
+
+    let path = root.child(format!("libexec/asimov-{name}-fetcher"));
+    std::fs::create_dir_all(root.child("libexec"))?;
+    std::fs::write(&path, fetcher)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+fn fetch(root: &TempDir, args: &[&str]) -> Result<Run> {
+    asimov(root.path(), &[&["source", "fetch"], args].concat())
+}
+
+#[test]
+fn concurrent_fetches_keep_going_without_interleaving() -> Result {
+    let root = sandbox()?;
+    let urls = [
+        "https://a.example/1",
+        "https://a.example/2",
+        "https://a.example/bad",
+        "https://b.example/1",
+        "https://c.example/1",
+    ];
+
+    let mut args = vec!["--jobs", "4", "--per-host", "1", "--keep-going"];
+    args.extend(urls);
+    let run = fetch(&root, &args)?;
+    assert_ne!(run.code, EX_OK as i32);
+
+    let stdout = run.stdout;
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 8, "every good URL is fetched: {stdout}");
+    for pair in lines.chunks(2) {
+        let url = pair[0].strip_prefix("begin ").unwrap();
+        assert_eq!(pair[1], format!("end {url}"));
+    }
+
+    let stderr = run.stderr;
+    assert!(stderr.contains("https://a.example/bad"));
+    assert!(!stderr.contains("skipped"));
+
+    Ok(())
+}
+
+#[test]
+fn a_failure_stops_the_fetch_without_keep_going() -> Result {
+    let root = sandbox()?;
+
+    let run = fetch(
+        &root,
+        &[
+            "https://a.example/1",
+            "https://a.example/bad",
+            "https://a.example/2",
+        ],
+    )?;
+    assert_ne!(run.code, EX_OK as i32);
+    assert_eq!(
+        run.stdout,
+        "begin https://a.example/1\nend https://a.example/1\n"
+    );
+    assert!(run.stderr.contains("skipped"));
+
+    Ok(())
+}
+
+#[test]
+fn urls_are_read_from_stdin() -> Result {
+    let root = sandbox()?;
+
+    let mut child = command(
+        root.path(),
+        &["source", "fetch", "--jobs", "2", "https://a.example/1", "-"],
+    )
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"# more\nhttps://b.example/1\n\n  https://c.example/1\n")?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout)?;
+    for url in [
+        "https://a.example/1",
+        "https://b.example/1",
+        "https://c.example/1",
+    ] {
+        assert!(stdout.contains(&format!("end {url}\n")), "{url}: {stdout}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fallback_tries_the_next_module() -> Result {
+    let root = sandbox()?;
+    // preferred, but writes some output and then fails
+    install(
+        &root,
+        "flaky",
+        "#!/bin/sh\necho partial\necho broken >&2\nexit 1\n",
+    )?;
+    std::fs::write(
+        root.child("modules/preferences.json"),
+        r#"{ "version": 1, "preferences": [{ "pattern": "a.example", "modules": ["flaky"] }] }"#,
+    )?;
+
+    let run = fetch(&root, &["https://a.example/1"])?;
+    assert_ne!(run.code, EX_OK as i32);
+    assert!(run.stderr.contains("broken"));
+
+    let run = fetch(&root, &["--fallback", "https://a.example/1"])?;
+    assert_eq!(run.code, EX_OK as i32);
+    assert_eq!(
+        run.stdout,
+        "begin https://a.example/1\nend https://a.example/1\n"
+    );
+    assert!(run.stderr.is_empty());
+
+    // every module's error is reported once all of them fail
+    let run = fetch(&root, &["--fallback", "https://a.example/bad"])?;
+    assert_ne!(run.code, EX_OK as i32);
+    let stderr = run.stderr;
+    assert!(stderr.contains("flaky"), "{stderr}");
+    assert!(stderr.contains("demo"), "{stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn outputs_are_written_to_a_directory_with_an_index() -> Result {
+    let root = sandbox()?;
+    let dir = root.child("out");
+
+    let run = fetch(
+        &root,
+        &[
+            "--output-dir",
+            dir.to_str().unwrap(),
+            "--output",
+            "jsonl",
+            "--keep-going",
+            "https://a.example/1?x=y",
+            "https://a.example/bad",
+        ],
+    )?;
+    assert_ne!(run.code, EX_OK as i32);
+    assert!(run.stdout.is_empty());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("a.example_1_x_y.jsonl"))?,
+        "begin https://a.example/1?x=y\nend https://a.example/1?x=y\n"
+    );
+
+    let index: Vec<serde_json::Value> = std::fs::read_to_string(dir.join("index.jsonl"))?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(index.len(), 2);
+    assert_eq!(index[0]["file"], "a.example_1_x_y.jsonl");
+    assert_eq!(index[0]["module"], "demo");
+    assert_eq!(index[0]["status"], 0);
+    assert_eq!(index[1]["url"], "https://a.example/bad");
+    assert_eq!(index[1]["file"], serde_json::Value::Null);
+    assert_eq!(index[1]["status"], 1);
+
+    // another run into the directory leaves the earlier outputs in place
+    let run = fetch(
+        &root,
+        &[
+            "--output-dir",
+            dir.to_str().unwrap(),
+            "--output",
+            "jsonl",
+            "https://a.example/1?x=y",
+        ],
+    )?;
+    assert_eq!(run.code, EX_OK as i32);
+    let index: Vec<serde_json::Value> = std::fs::read_to_string(dir.join("index.jsonl"))?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(index.len(), 3);
+    assert_eq!(index[0]["file"], "a.example_1_x_y.jsonl");
+    assert_eq!(index[2]["file"], "a.example_1_x_y-2.jsonl");
+    assert!(dir.join("a.example_1_x_y-2.jsonl").is_file());
+
+    Ok(())
+}
+
+#[test]
+fn outputs_are_appended_to_a_file_with_an_index() -> Result {
+    let root = sandbox()?;
+    let file = root.child("out.txt");
+
+    for url in ["https://a.example/1", "https://b.example/1"] {
+        let run = fetch(&root, &["--output-file", file.to_str().unwrap(), url])?;
+        assert_eq!(run.code, EX_OK as i32);
+    }
+    let content = std::fs::read_to_string(&file)?;
+    assert!(content.starts_with("begin https://a.example/1\n"));
+
+    let index: Vec<serde_json::Value> = std::fs::read_to_string(root.child("out.txt.index.jsonl"))?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(index.len(), 2);
+    let (offset, length) = (
+        index[1]["offset"].as_u64().unwrap() as usize,
+        index[1]["length"].as_u64().unwrap() as usize,
+    );
+    assert_eq!(
+        &content[offset..offset + length],
+        "begin https://b.example/1\nend https://b.example/1\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn cached_outputs_are_used_while_fresh() -> Result {
+    let root = TempDir::new()?;
+    // a fetcher whose output differs every time
+    install(
+        &root,
+        "clock",
+        "#!/bin/sh\nfor url; do :; done\necho \"$url $(date +%s%N)\"\n",
+    )?;
+    let url = "https://a.example/1";
+
+    let first = fetch(&root, &["--cache", url])?;
+    assert_eq!(first.code, EX_OK as i32);
+    let cached = fetch(&root, &["--cache", url])?;
+    assert_eq!(cached.stdout, first.stdout);
+
+    for args in [
+        ["--cache", "--output=jsonl"],
+        ["--cache", "--no-cache"],
+        ["--max-age", "0s"],
+        ["--refresh", "--cache"],
+    ] {
+        let run = fetch(&root, &[&args[..], &[url]].concat())?;
+        assert_eq!(run.code, EX_OK as i32);
+        assert_ne!(run.stdout, first.stdout, "{args:?}");
+    }
+    // the refreshed output is cached in turn
+    let refreshed = fetch(&root, &["--cache", url])?;
+    assert_ne!(refreshed.stdout, first.stdout);
+    assert_eq!(fetch(&root, &["--cache", url])?.stdout, refreshed.stdout);
+
+    Ok(())
+}